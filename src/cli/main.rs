@@ -1,8 +1,12 @@
 // CLI main entry point
 // This provides the command-line interface for interacting with the daemon
 
+use chrono::{DateTime, Local, NaiveTime, TimeZone};
 use clap::{Parser, Subcommand};
-use kbd_backlight::ipc::{IpcClient, IpcMessage, IpcResponse, DEFAULT_SOCKET_PATH};
+use kbd_backlight::ipc::{
+    IpcClient, IpcMessage, IpcResponse, OverrideMode, PinMode, DEFAULT_SOCKET_PATH,
+};
+use kbd_backlight::video_detector::MPRIS_BUS_PREFIX;
 use kbd_backlight::{Error, Result};
 use std::process::Command;
 
@@ -19,11 +23,38 @@ enum Commands {
     /// Show current status
     Status,
 
-    /// Switch to a different profile
-    Profile { name: String },
+    /// Switch to a different profile, pinned until the network changes
+    Profile {
+        #[arg(required_unless_present = "auto")]
+        name: Option<String>,
+        /// Keep the profile until this time (HH:MM) instead of until the network changes
+        #[arg(long, value_name = "HH:MM", conflicts_with_all = ["forever", "auto"])]
+        until: Option<String>,
+        /// Keep the profile until `profile --auto` is run
+        #[arg(long, conflicts_with = "auto")]
+        forever: bool,
+        /// Release the pin and resume automatic location switching
+        #[arg(long, conflicts_with = "name")]
+        auto: bool,
+    },
 
-    /// Set manual brightness override
-    Set { brightness: u32 },
+    /// Explain why the backlight is at its current level
+    Why,
+
+    /// Set manual brightness override, held until `auto` unless given an end
+    Set {
+        brightness: u32,
+        /// End the override after this long, e.g. 30m, 2h or 1h30m
+        #[arg(long = "for", value_name = "DURATION", conflicts_with_all = ["until_next_schedule", "until_idle"])]
+        duration: Option<String>,
+        /// End the override when the next time schedule entry takes effect
+        /// (rejected if the active profile has none)
+        #[arg(long, conflicts_with = "until_idle")]
+        until_next_schedule: bool,
+        /// End the override once the idle timeout is reached
+        #[arg(long)]
+        until_idle: bool,
+    },
 
     /// Clear manual override and resume automatic control
     Auto,
@@ -31,6 +62,12 @@ enum Commands {
     /// List all available profiles
     List,
 
+    /// Show recent automatic profile switches and what triggered them
+    History,
+
+    /// Reload config.toml and the profiles without restarting the daemon
+    Reload,
+
     /// Add a time schedule to a profile
     Schedule {
         #[command(subcommand)]
@@ -70,10 +107,36 @@ async fn main() -> Result<()> {
 
     match cli.command {
         Commands::Status => handle_status().await,
-        Commands::Profile { name } => handle_profile(name).await,
-        Commands::Set { brightness } => handle_set(brightness).await,
+        Commands::Profile {
+            name,
+            until,
+            forever,
+            auto,
+        } => match name {
+            Some(name) if !auto => handle_profile(name, until, forever).await,
+            _ => handle_profile_auto().await,
+        },
+        Commands::Why => handle_why().await,
+        Commands::Set {
+            brightness,
+            duration,
+            until_next_schedule,
+            until_idle,
+        } => {
+            let until = match duration {
+                Some(duration) => {
+                    OverrideMode::Until((Local::now() + parse_duration(&duration)?).timestamp())
+                }
+                None if until_next_schedule => OverrideMode::UntilNextSchedule,
+                None if until_idle => OverrideMode::UntilIdle,
+                None => OverrideMode::Indefinite,
+            };
+            handle_set(brightness, until).await
+        }
         Commands::Auto => handle_auto().await,
         Commands::List => handle_list().await,
+        Commands::History => handle_history().await,
+        Commands::Reload => handle_reload().await,
         Commands::Schedule { action } => handle_schedule(action).await,
         Commands::Daemon { action } => handle_daemon(action),
     }
@@ -98,13 +161,76 @@ async fn handle_status() -> Result<()> {
                 "Fullscreen:        {}",
                 if info.is_fullscreen { "Yes" } else { "No" }
             );
+            println!(
+                "Video Playing:     {}",
+                if info.is_video_playing { "Yes" } else { "No" }
+            );
+            println!(
+                "In Meeting:        {}",
+                if info.in_meeting { "Yes" } else { "No" }
+            );
+
+            if let Some(ref battery) = info.battery {
+                println!(
+                    "Battery:           {}% ({})",
+                    battery.percentage, battery.status
+                );
+            }
+
+            if let Some(ref pin) = info.profile_pin {
+                println!("Profile Pin:       {}", pin);
+            }
 
             if let Some(override_val) = info.manual_override {
-                println!("Manual Override:   {} (active)", override_val);
+                let expiry = info.manual_override_expiry.as_deref().unwrap_or("active");
+                match info.manual_override_remaining {
+                    Some(remaining) => println!(
+                        "Manual Override:   {} ({}, {} left)",
+                        override_val,
+                        expiry,
+                        format_remaining(remaining)
+                    ),
+                    None => println!("Manual Override:   {} ({})", override_val, expiry),
+                }
             } else {
                 println!("Manual Override:   None");
             }
 
+            if let Some(ref error) = info.config_error {
+                println!();
+                println!("Config Error:      {}", error);
+                println!("                   (the previous configuration is still active)");
+            }
+
+            for warning in &info.config_warnings {
+                println!("Config Warning:    {}", warning);
+            }
+
+            if !info.media_players.is_empty() {
+                println!();
+                println!("Media Players:");
+                for player in &info.media_players {
+                    let name = player
+                        .bus_name
+                        .strip_prefix(MPRIS_BUS_PREFIX)
+                        .unwrap_or(&player.bus_name);
+                    let label = match &player.identity {
+                        Some(identity) => format!("{} ({})", name, identity),
+                        None => name.to_string(),
+                    };
+                    println!(
+                        "  - {:<32} {:<8}{}",
+                        label,
+                        player.status,
+                        if player.suppressing {
+                            "  [suppressing backlight]"
+                        } else {
+                            ""
+                        }
+                    );
+                }
+            }
+
             Ok(())
         }
         IpcResponse::Error(msg) => Err(Error::ipc_protocol(msg)),
@@ -112,16 +238,140 @@ async fn handle_status() -> Result<()> {
     }
 }
 
+/// Handle the why command
+async fn handle_why() -> Result<()> {
+    let client = IpcClient::new(DEFAULT_SOCKET_PATH);
+    let response = client.send_message(&IpcMessage::Explain).await?;
+
+    let trace = match response {
+        IpcResponse::Explanation(trace) => trace,
+        IpcResponse::Error(msg) => return Err(Error::ipc_protocol(msg)),
+        _ => return Err(Error::ipc_protocol("Unexpected response from daemon")),
+    };
+
+    match trace.winner {
+        Some(rule) => println!(
+            "Brightness {} (profile '{}', decided by the {} rule)",
+            trace.brightness, trace.profile, rule
+        ),
+        None => println!(
+            "Brightness {} (profile '{}', every rule that could apply is disabled)",
+            trace.brightness, trace.profile
+        ),
+    }
+    if trace.inputs.current_brightness != trace.brightness {
+        println!(
+            "The backlight is at {} and changes on the next update",
+            trace.inputs.current_brightness
+        );
+    }
+
+    println!();
+    println!("Rules:");
+    for rule in &trace.rules {
+        let marker = if Some(rule.rule) == trace.winner {
+            ">"
+        } else {
+            " "
+        };
+        println!(
+            "{} {:<9} {:<11} {}",
+            marker,
+            rule.rule.to_string(),
+            rule.status,
+            rule.reason
+        );
+    }
+
+    if !trace.limits.is_empty() {
+        println!();
+        println!("Limits:");
+        for limit in &trace.limits {
+            println!("  {}: {} -> {}", limit.limit, limit.from, limit.to);
+        }
+    }
+
+    let inputs = &trace.inputs;
+    let yes_no = |value: bool| if value { "Yes" } else { "No" };
+    println!();
+    println!("Inputs:");
+    println!("  Time:        {}", inputs.time);
+    match inputs.power_mode {
+        Some(mode) => println!("  Power:       {} ({})", inputs.power, mode),
+        None => println!("  Power:       {}", inputs.power),
+    }
+    if let Some(battery) = inputs.battery {
+        println!(
+            "  Battery:     {}%{}",
+            battery,
+            if inputs.discharging {
+                " (discharging)"
+            } else {
+                ""
+            }
+        );
+    }
+    if inputs.idle_seconds > 0 {
+        println!(
+            "  Idle:        {} ({}s since the last input)",
+            yes_no(inputs.idle),
+            inputs.idle_seconds
+        );
+    } else {
+        println!("  Idle:        {}", yes_no(inputs.idle));
+    }
+    println!("  Video:       {}", yes_no(inputs.video));
+    println!("  Fullscreen:  {}", yes_no(inputs.fullscreen));
+    println!("  In Meeting:  {}", yes_no(inputs.meeting));
+    if let Some(ref ssid) = inputs.ssid {
+        println!("  SSID:        {}", ssid);
+    }
+    if let Some(ref app_id) = inputs.app_id {
+        println!("  Focused App: {}", app_id);
+    }
+    println!(
+        "  Brightness:  {} of {}",
+        inputs.current_brightness, inputs.max_brightness
+    );
+
+    Ok(())
+}
+
 /// Handle the profile switch command
-async fn handle_profile(name: String) -> Result<()> {
+async fn handle_profile(name: String, until: Option<String>, forever: bool) -> Result<()> {
+    let pin = match until.as_deref() {
+        Some(time) => {
+            let (hour, minute) = parse_time(time)?;
+            PinMode::Until(next_occurrence(hour, minute, Local::now())?.timestamp())
+        }
+        None if forever => PinMode::Indefinite,
+        None => PinMode::UntilNetworkChange,
+    };
+
     let client = IpcClient::new(DEFAULT_SOCKET_PATH);
     let response = client
-        .send_message(&IpcMessage::SetProfile(name.clone()))
+        .send_message(&IpcMessage::SetProfile {
+            name: name.clone(),
+            pin,
+        })
         .await?;
 
     match response {
         IpcResponse::ProfileChanged => {
-            println!("Switched to profile: {}", name);
+            match pin {
+                PinMode::UntilNetworkChange => {
+                    println!("Switched to profile: {} (until the network changes)", name)
+                }
+                PinMode::Until(_) => println!(
+                    "Switched to profile: {} (until {})",
+                    name,
+                    until.as_deref().unwrap_or_default()
+                ),
+                PinMode::Indefinite => println!(
+                    "Switched to profile: {} (until `kbd-backlight profile --auto`)",
+                    name
+                ),
+            }
             Ok(())
         }
         IpcResponse::Error(msg) => Err(Error::ipc_protocol(msg)),
@@ -129,16 +379,147 @@ async fn handle_profile(name: String) -> Result<()> {
     }
 }
 
+/// Handle `profile --auto` (release the profile pin)
+async fn handle_profile_auto() -> Result<()> {
+    let client = IpcClient::new(DEFAULT_SOCKET_PATH);
+    let response = client.send_message(&IpcMessage::ReleaseProfilePin).await?;
+
+    match response {
+        IpcResponse::Ok => {
+            println!("Profile pin released. Resuming automatic location switching.");
+            Ok(())
+        }
+        IpcResponse::Error(msg) => Err(Error::ipc_protocol(msg)),
+        _ => Err(Error::ipc_protocol("Unexpected response from daemon")),
+    }
+}
+
+/// Parse a HH:MM time of day
+fn parse_time(time: &str) -> Result<(u8, u8)> {
+    let parts: Vec<&str> = time.split(':').collect();
+    if parts.len() != 2 {
+        return Err(Error::Parse(format!(
+            "Invalid time format '{}'. Expected HH:MM",
+            time
+        )));
+    }
+
+    let hour: u8 = parts[0]
+        .parse()
+        .map_err(|_| Error::Parse(format!("Invalid hour: {}", parts[0])))?;
+    let minute: u8 = parts[1]
+        .parse()
+        .map_err(|_| Error::Parse(format!("Invalid minute: {}", parts[1])))?;
+
+    // Validate hour and minute ranges
+    if hour > 23 {
+        return Err(Error::Parse(format!("Hour must be 0-23, got {}", hour)));
+    }
+    if minute > 59 {
+        return Err(Error::Parse(format!("Minute must be 0-59, got {}", minute)));
+    }
+
+    Ok((hour, minute))
+}
+
+/// The next time the clock shows HH:MM, today or tomorrow
+fn next_occurrence(hour: u8, minute: u8, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let time = NaiveTime::from_hms_opt(hour.into(), minute.into(), 0)
+        .ok_or_else(|| Error::Parse(format!("Invalid time {}:{:02}", hour, minute)))?;
+
+    let mut date = now.date_naive();
+    loop {
+        // Skip times that fall into a DST gap
+        if let Some(candidate) = date.and_time(time).and_local_timezone(Local).earliest() {
+            if candidate > now {
+                return Ok(candidate);
+            }
+        }
+        date = date
+            .succ_opt()
+            .ok_or_else(|| Error::Parse("Date out of range".to_string()))?;
+    }
+}
+
+/// Parse a duration such as `30m`, `2h`, `90s` or `1h30m`
+fn parse_duration(text: &str) -> Result<chrono::Duration> {
+    let invalid = || {
+        Error::Parse(format!(
+            "Invalid duration '{}'. Expected e.g. 30m, 2h or 1h30m",
+            text
+        ))
+    };
+
+    let mut total = chrono::Duration::zero();
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let value: i64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let unit = rest[digits..].chars().next().ok_or_else(invalid)?;
+        let part = match unit {
+            'h' => chrono::Duration::try_hours(value),
+            'm' => chrono::Duration::try_minutes(value),
+            's' => chrono::Duration::try_seconds(value),
+            _ => None,
+        }
+        .ok_or_else(invalid)?;
+        total = total.checked_add(&part).ok_or_else(invalid)?;
+        rest = &rest[digits + unit.len_utf8()..];
+    }
+
+    if total <= chrono::Duration::zero() {
+        return Err(Error::Parse(format!(
+            "Duration '{}' must be positive",
+            text
+        )));
+    }
+    Ok(total)
+}
+
+/// Format a number of seconds as e.g. `1h 05m` or `42s`
+fn format_remaining(seconds: u64) -> String {
+    match (seconds / 3600, seconds % 3600 / 60) {
+        (0, 0) => format!("{}s", seconds),
+        (0, minutes) => format!("{}m", minutes),
+        (hours, minutes) => format!("{}h {:02}m", hours, minutes),
+    }
+}
+
 /// Handle the manual brightness set command
-async fn handle_set(brightness: u32) -> Result<()> {
+async fn handle_set(brightness: u32, until: OverrideMode) -> Result<()> {
     let client = IpcClient::new(DEFAULT_SOCKET_PATH);
     let response = client
-        .send_message(&IpcMessage::SetManualBrightness(brightness))
+        .send_message(&IpcMessage::SetManualBrightness { brightness, until })
         .await?;
 
     match response {
         IpcResponse::BrightnessSet => {
-            println!("Manual brightness set to: {}", brightness);
+            match until {
+                OverrideMode::Indefinite => println!(
+                    "Manual brightness set to: {} (until `kbd-backlight auto`)",
+                    brightness
+                ),
+                OverrideMode::Until(until) => {
+                    let until = Local.timestamp_opt(until, 0).single();
+                    println!(
+                        "Manual brightness set to: {} (until {})",
+                        brightness,
+                        until.map_or_else(String::new, |time| time.format("%H:%M").to_string())
+                    )
+                }
+                OverrideMode::UntilNextSchedule => println!(
+                    "Manual brightness set to: {} (until the next schedule entry)",
+                    brightness
+                ),
+                OverrideMode::UntilIdle => {
+                    println!("Manual brightness set to: {} (until idle)", brightness)
+                }
+            }
             Ok(())
         }
         IpcResponse::Error(msg) => Err(Error::ipc_protocol(msg)),
@@ -182,6 +563,54 @@ async fn handle_list() -> Result<()> {
     }
 }
 
+/// Handle the location history command
+async fn handle_history() -> Result<()> {
+    let client = IpcClient::new(DEFAULT_SOCKET_PATH);
+    let response = client.send_message(&IpcMessage::GetLocationHistory).await?;
+
+    match response {
+        IpcResponse::LocationHistory(changes) => {
+            if changes.is_empty() {
+                println!("No automatic profile switches since the daemon started.");
+                return Ok(());
+            }
+
+            println!("Location History");
+            println!("================");
+            for change in changes {
+                println!(
+                    "{}  {} -> {} ({})",
+                    change.timestamp, change.from_profile, change.to_profile, change.reason
+                );
+                println!("                     {}", change.network);
+            }
+            Ok(())
+        }
+        IpcResponse::Error(msg) => Err(Error::ipc_protocol(msg)),
+        _ => Err(Error::ipc_protocol("Unexpected response from daemon")),
+    }
+}
+
+/// Handle the reload command
+async fn handle_reload() -> Result<()> {
+    let client = IpcClient::new(DEFAULT_SOCKET_PATH);
+    let response = client.send_message(&IpcMessage::Reload).await?;
+
+    match response {
+        IpcResponse::Ok => {
+            println!("Configuration reloaded.");
+            if let IpcResponse::Status(info) = client.send_message(&IpcMessage::GetStatus).await? {
+                for warning in info.config_warnings {
+                    eprintln!("Warning: {}", warning);
+                }
+            }
+            Ok(())
+        }
+        IpcResponse::Error(msg) => Err(Error::ipc_protocol(msg)),
+        _ => Err(Error::ipc_protocol("Unexpected response from daemon")),
+    }
+}
+
 /// Handle the schedule add command
 async fn handle_schedule(action: ScheduleAction) -> Result<()> {
     match action {
@@ -191,28 +620,7 @@ async fn handle_schedule(action: ScheduleAction) -> Result<()> {
             brightness,
         } => {
             // Parse the time string (HH:MM format)
-            let parts: Vec<&str> = time.split(':').collect();
-            if parts.len() != 2 {
-                return Err(Error::Parse(format!(
-                    "Invalid time format '{}'. Expected HH:MM",
-                    time
-                )));
-            }
-
-            let hour: u8 = parts[0]
-                .parse()
-                .map_err(|_| Error::Parse(format!("Invalid hour: {}", parts[0])))?;
-            let minute: u8 = parts[1]
-                .parse()
-                .map_err(|_| Error::Parse(format!("Invalid minute: {}", parts[1])))?;
-
-            // Validate hour and minute ranges
-            if hour > 23 {
-                return Err(Error::Parse(format!("Hour must be 0-23, got {}", hour)));
-            }
-            if minute > 59 {
-                return Err(Error::Parse(format!("Minute must be 0-59, got {}", minute)));
-            }
+            let (hour, minute) = parse_time(&time)?;
 
             let client = IpcClient::new(DEFAULT_SOCKET_PATH);
             let response = client
@@ -307,6 +715,59 @@ fn handle_daemon(action: DaemonAction) -> Result<()> {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("18:30").unwrap(), (18, 30));
+        assert!(parse_time("24:00").is_err());
+        assert!(parse_time("18-30").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(
+            parse_duration("30m").unwrap(),
+            chrono::Duration::minutes(30)
+        );
+        assert_eq!(parse_duration("2h").unwrap(), chrono::Duration::hours(2));
+        assert_eq!(
+            parse_duration("1h30m").unwrap(),
+            chrono::Duration::minutes(90)
+        );
+        assert_eq!(
+            parse_duration("90s").unwrap(),
+            chrono::Duration::seconds(90)
+        );
+        for invalid in ["", "30", "m", "30x", "1.5h", "-5m", "0m", "h30"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_format_remaining() {
+        assert_eq!(format_remaining(42), "42s");
+        assert_eq!(format_remaining(29 * 60 + 59), "29m");
+        assert_eq!(format_remaining(3_900), "1h 05m");
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        let now = Local.with_ymd_and_hms(2025, 1, 6, 12, 0, 0).unwrap();
+        assert_eq!(
+            next_occurrence(18, 0, now).unwrap(),
+            Local.with_ymd_and_hms(2025, 1, 6, 18, 0, 0).unwrap()
+        );
+        // Times already passed today roll over to tomorrow
+        assert_eq!(
+            next_occurrence(9, 0, now).unwrap(),
+            Local.with_ymd_and_hms(2025, 1, 7, 9, 0, 0).unwrap()
+        );
+        assert_eq!(
+            next_occurrence(12, 0, now).unwrap(),
+            Local.with_ymd_and_hms(2025, 1, 7, 12, 0, 0).unwrap()
+        );
+    }
 
     #[test]
     fn test_time_parsing_valid() {
@@ -1,8 +1,17 @@
 // Configuration management module
 // This module will handle loading, validation, and persistence of configuration
 
+use crate::holidays::Holidays;
+use crate::location::NetworkFingerprint;
+use crate::power::{PowerMode, PowerState};
+use crate::rules::{Condition, Variable};
+use crate::solar::{self, SunTimes};
+use crate::video_detector::MPRIS_BUS_PREFIX;
 use crate::{Error, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -15,6 +24,147 @@ pub struct Config {
     pub active_profile: String,
     #[serde(default)]
     pub auto_switch_location: bool,
+    /// Profile used when no location matches the current network
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_profile: Option<String>,
+    /// Profile used when no location matches and WiFi is disconnected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offline_profile: Option<String>,
+    /// Seconds a new network must stay unchanged before it can switch profiles
+    #[serde(default = "default_location_settle_time")]
+    pub location_settle_time: u64,
+    /// Manual profile selection that holds off automatic location switching
+    #[serde(skip)]
+    pub profile_pin: Option<ProfilePin>,
+    /// Brightness set with `kbd-backlight set`, overriding the rules until it ends
+    #[serde(skip)]
+    pub manual_override: Option<ManualOverride>,
+    /// Location for sunrise/sunset schedules (degrees, north positive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    /// Location for sunrise/sunset schedules (degrees, east positive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// Brightness when no schedule applies, for profiles without their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_brightness: Option<BrightnessValue>,
+    /// Holidays file (`.ics` or one date per line), relative to the config directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub holidays: Option<PathBuf>,
+    /// Dates loaded from `holidays`
+    #[serde(skip)]
+    pub holiday_calendar: Holidays,
+}
+
+/// Keeps a manually selected profile from being replaced by location switching
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "kebab-case")]
+pub enum ProfilePin {
+    /// Released once the network differs from the one the profile was pinned on
+    UntilNetworkChange { network: NetworkFingerprint },
+    /// Released at the given Unix timestamp
+    UntilTime { until: i64 },
+    /// Held until explicitly released
+    Indefinite,
+}
+
+impl ProfilePin {
+    /// Whether a time-limited pin has run out
+    pub fn is_expired(&self, now: DateTime<Local>) -> bool {
+        matches!(self, ProfilePin::UntilTime { until } if now.timestamp() >= *until)
+    }
+
+    /// Whether the pin still holds on the given network
+    pub fn holds_on(&self, network: &NetworkFingerprint) -> bool {
+        match self {
+            ProfilePin::UntilNetworkChange { network: pinned } => pinned.same_network(network),
+            ProfilePin::UntilTime { .. } | ProfilePin::Indefinite => true,
+        }
+    }
+}
+
+impl std::fmt::Display for ProfilePin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfilePin::UntilNetworkChange { .. } => write!(f, "until the network changes"),
+            ProfilePin::UntilTime { until } => match Local.timestamp_opt(*until, 0).single() {
+                Some(time) => write!(f, "until {}", time.format("%Y-%m-%d %H:%M")),
+                None => write!(f, "until {}", until),
+            },
+            ProfilePin::Indefinite => write!(f, "indefinitely"),
+        }
+    }
+}
+
+/// Manually set brightness that overrides the rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManualOverride {
+    pub brightness: u32,
+    #[serde(flatten)]
+    pub expiry: OverrideExpiry,
+}
+
+/// When a manual override ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "kebab-case")]
+pub enum OverrideExpiry {
+    /// Held until `kbd-backlight auto`
+    Indefinite,
+    /// Ends at the given Unix timestamp
+    UntilTime { until: i64 },
+    /// Ends when a time schedule entry takes effect after the given Unix timestamp
+    UntilNextSchedule { since: i64 },
+    /// Ends once the idle timeout is reached
+    UntilIdle,
+}
+
+impl std::fmt::Display for OverrideExpiry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OverrideExpiry::Indefinite => write!(f, "until `kbd-backlight auto`"),
+            OverrideExpiry::UntilTime { until } => match Local.timestamp_opt(*until, 0).single() {
+                Some(time) => write!(f, "until {}", time.format("%Y-%m-%d %H:%M")),
+                None => write!(f, "until {}", until),
+            },
+            OverrideExpiry::UntilNextSchedule { .. } => write!(f, "until the next schedule entry"),
+            OverrideExpiry::UntilIdle => write!(f, "until idle"),
+        }
+    }
+}
+
+fn default_location_settle_time() -> u64 {
+    5
+}
+
+/// Daemon state persisted in state.toml
+#[derive(Serialize, Deserialize)]
+struct State {
+    active_profile: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile_pin: Option<ProfilePin>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    manual_override: Option<ManualOverride>,
+}
+
+/// Why a profile was selected for the current network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocationSource {
+    /// One of the profile's location matchers fits the network
+    Matched,
+    /// Nothing matched and WiFi is disconnected
+    Offline,
+    /// Nothing matched
+    Fallback,
+}
+
+impl std::fmt::Display for LocationSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            LocationSource::Matched => "matched location",
+            LocationSource::Offline => "offline profile",
+            LocationSource::Fallback => "fallback profile",
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,671 +175,3152 @@ pub struct LocationProfile {
     #[serde(default = "default_true")]
     pub video_detection_enabled: bool,
     #[serde(default)]
-    pub wifi_networks: Vec<String>, // WiFi SSIDs for this profile
+    pub wifi_networks: Vec<SsidEntry>, // WiFi SSIDs for this profile
+    /// Richer location matchers (BSSID, gateway MAC, connection, wired interface)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<LocationMatcher>,
+    /// Deprecated: holds the backlight at 1 on AC; with an `on_ac` policy it only sets
+    /// `min_brightness = 1`
+    #[serde(default)]
+    pub ac_always_on: bool,
     #[serde(default)]
-    pub ac_always_on: bool, // Keep backlight on when on AC power (except during video)
+    pub media_players: MediaPlayerFilter,
+    /// Which signals turn the backlight off (defaults from video_detection_enabled)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppression_policy: Option<SuppressionPolicy>,
+    /// Fixed brightness while a webcam or screen share is active
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meeting: Option<MeetingRule>,
+    /// Seconds this profile stays active before location changes may switch away
+    #[serde(default)]
+    pub min_dwell_time: u64,
+    /// Brightness limits that apply while running on a low battery
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub battery_rules: Vec<BatteryRule>,
+    /// Overrides while running on AC power
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_ac: Option<PowerSourcePolicy>,
+    /// Overrides while running on battery
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_battery: Option<PowerSourcePolicy>,
+    /// Overrides per power-profiles-daemon mode, e.g. `[power_modes.power-saver]`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub power_modes: HashMap<PowerMode, PowerModeOverride>,
+    /// Brightness when no schedule applies (defaults to the global setting, then 0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_brightness: Option<BrightnessValue>,
+    /// Order in which rules are tried; unlisted rules follow in the default order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rule_order: Vec<RuleLayer>,
+    /// Rules switched on or off, e.g. `[enabled_rules] media = false`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub enabled_rules: HashMap<RuleLayer, bool>,
+    /// Conditional rules; the first whose condition holds sets the brightness
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_rules: Vec<CustomRule>,
 }
 
-fn default_true() -> bool {
-    true
-}
+impl LocationProfile {
+    /// All location matchers of this profile, with `wifi_networks` as SSID-only matchers
+    pub fn location_matchers(&self) -> impl Iterator<Item = LocationMatcher> + '_ {
+        self.wifi_networks
+            .iter()
+            .map(|ssid| LocationMatcher {
+                ssid: Some(ssid.clone()),
+                ..Default::default()
+            })
+            .chain(self.locations.iter().cloned())
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TimeSchedule {
-    pub hour: u8,
-    pub minute: u8,
-    pub brightness: u32,
-}
+    /// Best score of any of this profile's matchers against the current network
+    pub fn location_score(&self, fingerprint: &NetworkFingerprint) -> Option<u32> {
+        self.location_matchers()
+            .filter_map(|matcher| matcher.score(fingerprint))
+            .max()
+    }
 
-impl Config {
-    /// Load configuration from the XDG config directory
-    /// If the file doesn't exist, create a default configuration
-    pub fn load() -> Result<Self> {
-        let config_dir = Self::get_config_dir();
-        let config_path = config_dir.join("config.toml");
+    /// Get the effective suppression policy for this profile
+    ///
+    /// Profiles without an explicit policy only check video playback when video
+    /// detection is enabled, and use fullscreen detection alone when it is disabled.
+    /// Fullscreen windows in addition to video playback are opt-in.
+    pub fn suppression_policy(&self) -> SuppressionPolicy {
+        self.suppression_policy
+            .unwrap_or(if self.video_detection_enabled {
+                SuppressionPolicy::Video
+            } else {
+                SuppressionPolicy::Fullscreen
+            })
+    }
 
-        // Create default configuration if it doesn't exist
-        if !config_path.exists() {
-            let default_config = Self::default();
-            default_config.save()?;
-            return Ok(default_config);
+    /// Policy for the given power source, if the profile configures one
+    pub fn power_policy(&self, power: PowerState) -> Option<&PowerSourcePolicy> {
+        match power {
+            PowerState::AC => self.on_ac.as_ref(),
+            PowerState::Battery => self.on_battery.as_ref(),
+            PowerState::Unknown => None,
         }
+    }
 
-        // Load main config.toml
-        let content = fs::read_to_string(&config_path).map_err(|e| {
-            Error::config_error(
-                config_path.display().to_string(),
-                format!("Failed to read config file: {}", e),
-            )
-        })?;
-
-        let mut config: Config = toml::from_str(&content).map_err(|e| {
-            let error_msg = e.to_string();
-            if error_msg.contains("line") {
-                Error::ConfigSyntax(format!(
-                    "TOML syntax error in {}: {}",
-                    config_path.display(),
-                    error_msg
-                ))
-            } else {
-                Error::ConfigSyntax(format!(
-                    "Failed to parse config file {}: {}",
-                    config_path.display(),
-                    error_msg
-                ))
-            }
-        })?;
-
-        // Load all profile files from profiles/ directory
-        config.profiles = Self::load_profiles(&config_dir)?;
+    /// Time schedules in effect on the given power source
+    pub fn time_schedules_for(&self, power: PowerState) -> &[TimeSchedule] {
+        self.power_policy(power)
+            .and_then(|policy| policy.time_schedules.as_deref())
+            .unwrap_or(&self.time_schedules)
+    }
 
-        // Load active profile from state file
-        config.active_profile = Self::load_active_profile(&config_dir)?;
+    /// Idle timeout in effect on the given power source and power mode
+    /// A power mode override can only shorten the timeout
+    pub fn idle_timeout_for(&self, power: PowerState, mode: Option<PowerMode>) -> u64 {
+        let timeout = self
+            .power_policy(power)
+            .and_then(|policy| policy.idle_timeout)
+            .unwrap_or(self.idle_timeout);
+        mode.and_then(|mode| self.power_modes.get(&mode)?.idle_timeout)
+            .map_or(timeout, |mode_timeout| timeout.min(mode_timeout))
+    }
 
-        config.validate()?;
-        Ok(config)
+    /// Lowest brightness on the given power source, including the legacy `ac_always_on`
+    pub fn brightness_floor(&self, power: PowerState, max_brightness: u32) -> Option<u32> {
+        let floor = self
+            .power_policy(power)
+            .and_then(|policy| policy.min_brightness)
+            .map(|value| value.to_level(max_brightness));
+        let legacy = (self.ac_always_on && power == PowerState::AC).then_some(1);
+        floor.max(legacy).map(|floor| floor.min(max_brightness))
     }
 
-    /// Load all profile files from the profiles directory
-    fn load_profiles(config_dir: &Path) -> Result<HashMap<String, LocationProfile>> {
-        let profiles_dir = config_dir.join("profiles");
+    /// Highest brightness on the given power source, including the legacy `ac_always_on`
+    pub fn brightness_cap(&self, power: PowerState, max_brightness: u32) -> Option<u32> {
+        let cap = self
+            .power_policy(power)
+            .and_then(|policy| policy.max_brightness)
+            .map(|value| value.to_level(max_brightness));
+        // Without an on_ac policy, ac_always_on keeps its old meaning of exactly 1 on AC
+        let legacy = (self.ac_always_on && power == PowerState::AC && self.on_ac.is_none())
+            .then_some(1.min(max_brightness));
+        cap.or(legacy)
+    }
 
-        // Create profiles directory if it doesn't exist
-        if !profiles_dir.exists() {
-            fs::create_dir_all(&profiles_dir).map_err(|e| {
-                Error::config_error(
-                    profiles_dir.display().to_string(),
-                    format!("Failed to create profiles directory: {}", e),
-                )
-            })?;
+    /// Enabled rules in the order they are tried
+    pub fn rule_order(&self) -> Vec<RuleLayer> {
+        let mut order: Vec<RuleLayer> = Vec::with_capacity(DEFAULT_RULE_ORDER.len());
+        for layer in self.rule_order.iter().chain(&DEFAULT_RULE_ORDER) {
+            if !order.contains(layer) {
+                order.push(*layer);
+            }
         }
+        order.retain(|layer| self.is_rule_enabled(*layer));
+        order
+    }
 
-        let mut profiles = HashMap::new();
-        let mut seen_names = HashSet::new();
+    /// Whether an enabled custom rule reads the given variable
+    pub fn uses_variable(&self, variable: Variable) -> bool {
+        self.is_rule_enabled(RuleLayer::Custom)
+            && self
+                .custom_rules
+                .iter()
+                .any(|rule| rule.when.uses(variable))
+    }
 
-        // Read all .toml files in profiles directory
-        let entries = fs::read_dir(&profiles_dir).map_err(|e| {
-            Error::config_error(
-                profiles_dir.display().to_string(),
-                format!("Failed to read profiles directory: {}", e),
+    /// Whether a rule is switched on (all are by default)
+    pub fn is_rule_enabled(&self, layer: RuleLayer) -> bool {
+        self.enabled_rules.get(&layer).copied().unwrap_or(true)
+    }
+
+    /// Whether any setting of this profile can turn the backlight on
+    ///
+    /// `global_default` is the `default_brightness` from config.toml.
+    pub fn can_turn_on(&self, global_default: Option<BrightnessValue>) -> bool {
+        let is_on = |value: Option<BrightnessValue>| {
+            matches!(
+                value,
+                Some(BrightnessValue::Level(1..) | BrightnessValue::Percent(1..))
             )
-        })?;
+        };
+        let policies = [&self.on_ac, &self.on_battery];
+        let schedules = std::iter::once(&self.time_schedules).chain(
+            policies
+                .iter()
+                .filter_map(|policy| policy.as_ref()?.time_schedules.as_ref()),
+        );
 
-        for entry in entries {
-            let entry = entry.map_err(|e| {
-                Error::config_error(
-                    profiles_dir.display().to_string(),
-                    format!("Failed to read directory entry: {}", e),
+        let enabled = |layer| self.is_rule_enabled(layer);
+
+        self.ac_always_on
+            || enabled(RuleLayer::Default) && is_on(self.default_brightness.or(global_default))
+            || enabled(RuleLayer::Meeting)
+                && is_on(
+                    self.meeting
+                        .as_ref()
+                        .filter(|m| m.enabled)
+                        .map(|m| m.brightness),
                 )
-            })?;
+            || policies
+                .iter()
+                .any(|policy| is_on(policy.as_ref().and_then(|p| p.min_brightness)))
+            || enabled(RuleLayer::Schedule)
+                && schedules
+                    .flatten()
+                    .any(|schedule| is_on(Some(schedule.brightness)))
+            || enabled(RuleLayer::Custom)
+                && self
+                    .custom_rules
+                    .iter()
+                    .any(|rule| is_on(Some(rule.then.brightness)))
+    }
+}
 
-            let path = entry.path();
+/// Profile settings that only apply on one power source (`[on_ac]` / `[on_battery]`)
+///
+/// Unset fields fall back to the profile's own settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PowerSourcePolicy {
+    /// Replaces the profile's time schedules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_schedules: Option<Vec<TimeSchedule>>,
+    /// Replaces the profile's idle timeout (seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout: Option<u64>,
+    /// Brightness never drops below this, even when idle; media suppression and
+    /// meetings still apply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_brightness: Option<BrightnessValue>,
+    /// Brightness never exceeds this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_brightness: Option<BrightnessValue>,
+}
 
-            // Skip non-files and non-.toml files
-            if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("toml") {
-                continue;
-            }
+/// Profile settings that apply in one power-profiles-daemon mode
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowerModeOverride {
+    /// Brightness never exceeds this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_brightness: Option<BrightnessValue>,
+    /// Shorter idle timeout (seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout: Option<u64>,
+}
 
-            // Load profile
-            let content = fs::read_to_string(&path).map_err(|e| {
-                Error::config_error(
-                    path.display().to_string(),
-                    format!("Failed to read profile file: {}", e),
-                )
-            })?;
+/// Brightness settings applied while a meeting (webcam or screen share) is detected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeetingRule {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub brightness: BrightnessValue,
+    /// Keep the backlight on even when idle during a meeting
+    #[serde(default = "default_true")]
+    pub suppress_idle: bool,
+    /// Treat an open /dev/video* handle as a meeting
+    #[serde(default = "default_true")]
+    pub detect_webcam: bool,
+    /// Treat an active desktop-portal screencast session as a meeting
+    #[serde(default = "default_true")]
+    pub detect_screen_sharing: bool,
+}
 
-            let profile: LocationProfile = toml::from_str(&content).map_err(|e| {
-                let error_msg = e.to_string();
-                if error_msg.contains("line") {
-                    Error::ConfigSyntax(format!(
-                        "TOML syntax error in {}: {}",
-                        path.display(),
-                        error_msg
-                    ))
-                } else {
-                    Error::ConfigSyntax(format!(
-                        "Failed to parse profile file {}: {}",
-                        path.display(),
-                        error_msg
-                    ))
-                }
-            })?;
+/// Limits brightness while discharging below a battery percentage
+///
+/// `{ below = 20, brightness = 0 }` forces the backlight off,
+/// `{ below = 40, max_brightness = "50%" }` caps it at half the device maximum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatteryRule {
+    /// Battery percentage under which the rule applies
+    pub below: u8,
+    /// Brightness to force
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<BrightnessValue>,
+    /// Upper bound for the brightness chosen by other rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_brightness: Option<BrightnessValue>,
+}
 
-            // Check for duplicate profile names
-            if !seen_names.insert(profile.name.clone()) {
-                return Err(Error::ConfigValidation(format!(
-                    "Duplicate profile name '{}' found in {}",
-                    profile.name,
-                    path.display()
-                )));
-            }
+/// A brightness given as a device level (`1`) or a share of the maximum (`"50%"`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawBrightnessValue", into = "RawBrightnessValue")]
+pub enum BrightnessValue {
+    Level(u32),
+    Percent(u8),
+}
 
-            profiles.insert(profile.name.clone(), profile);
-        }
+impl Default for BrightnessValue {
+    fn default() -> Self {
+        BrightnessValue::Level(0)
+    }
+}
 
-        // Ensure at least one profile exists
-        if profiles.is_empty() {
-            return Err(Error::ConfigValidation(
-                "No profiles found. At least one profile is required.".to_string(),
-            ));
+impl BrightnessValue {
+    /// Convert to a device level, rounding percentages down
+    pub fn to_level(self, max_brightness: u32) -> u32 {
+        match self {
+            BrightnessValue::Level(level) => level.min(max_brightness),
+            BrightnessValue::Percent(percent) => max_brightness * percent as u32 / 100,
         }
-
-        Ok(profiles)
     }
+}
 
-    /// Load active profile from state file
-    fn load_active_profile(config_dir: &Path) -> Result<String> {
-        let state_path = config_dir.join("state.toml");
-
-        if !state_path.exists() {
-            // Default to first available profile
-            return Ok("home".to_string());
+impl std::fmt::Display for BrightnessValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrightnessValue::Level(level) => write!(f, "{}", level),
+            BrightnessValue::Percent(percent) => write!(f, "{}%", percent),
         }
+    }
+}
 
-        let content = fs::read_to_string(&state_path).map_err(|e| {
-            Error::config_error(
-                state_path.display().to_string(),
-                format!("Failed to read state file: {}", e),
-            )
-        })?;
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawBrightnessValue {
+    Level(u32),
+    Text(String),
+}
 
-        #[derive(Deserialize)]
-        struct State {
-            active_profile: String,
+impl TryFrom<RawBrightnessValue> for BrightnessValue {
+    type Error = String;
+
+    fn try_from(raw: RawBrightnessValue) -> std::result::Result<Self, Self::Error> {
+        match raw {
+            RawBrightnessValue::Level(level) => Ok(BrightnessValue::Level(level)),
+            RawBrightnessValue::Text(text) => text
+                .trim()
+                .strip_suffix('%')
+                .and_then(|p| p.trim().parse::<u8>().ok())
+                .filter(|p| *p <= 100)
+                .map(BrightnessValue::Percent)
+                .ok_or_else(|| {
+                    format!(
+                        "invalid brightness '{}', expected a level or a percentage like \"50%\"",
+                        text
+                    )
+                }),
         }
+    }
+}
 
-        let state: State = toml::from_str(&content)
-            .map_err(|e| Error::ConfigSyntax(format!("Failed to parse state file: {}", e)))?;
-
-        Ok(state.active_profile)
+impl From<BrightnessValue> for RawBrightnessValue {
+    fn from(value: BrightnessValue) -> Self {
+        match value {
+            BrightnessValue::Level(level) => RawBrightnessValue::Level(level),
+            BrightnessValue::Percent(_) => RawBrightnessValue::Text(value.to_string()),
+        }
     }
+}
 
-    /// Save active profile to state file
-    pub fn save_active_profile(&self) -> Result<()> {
-        let config_dir = Self::get_config_dir();
-        let state_path = config_dir.join("state.toml");
+/// A rule like `{ when = "power == 'battery' && battery < 30", then = { brightness = 0 } }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomRule {
+    pub when: Condition,
+    pub then: CustomAction,
+}
 
-        #[derive(Serialize)]
-        struct State {
-            active_profile: String,
-        }
+/// What a custom rule does while its condition holds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomAction {
+    pub brightness: BrightnessValue,
+}
 
-        let state = State {
-            active_profile: self.active_profile.clone(),
+/// A rule of the brightness decision, tried in the profile's `rule_order`
+///
+/// The first rule that applies decides the brightness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuleLayer {
+    /// Brightness set with `kbd-backlight set`
+    Manual,
+    /// The profile's `[[custom_rules]]`
+    Custom,
+    /// The profile's meeting rule while a webcam or screen share is active
+    Meeting,
+    /// Video playback or fullscreen windows per the suppression policy (off)
+    Media,
+    /// No input for the idle timeout (off)
+    Idle,
+    /// The most recent time schedule entry
+    Schedule,
+    /// `default_brightness`, always applies
+    Default,
+}
+
+/// Rule order used when a profile doesn't set `rule_order`
+pub const DEFAULT_RULE_ORDER: [RuleLayer; 7] = [
+    RuleLayer::Manual,
+    RuleLayer::Custom,
+    RuleLayer::Meeting,
+    RuleLayer::Media,
+    RuleLayer::Idle,
+    RuleLayer::Schedule,
+    RuleLayer::Default,
+];
+
+impl std::fmt::Display for RuleLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RuleLayer::Manual => "manual",
+            RuleLayer::Custom => "custom",
+            RuleLayer::Meeting => "meeting",
+            RuleLayer::Media => "media",
+            RuleLayer::Idle => "idle",
+            RuleLayer::Schedule => "schedule",
+            RuleLayer::Default => "default",
         };
+        write!(f, "{}", name)
+    }
+}
 
-        let content = toml::to_string_pretty(&state).map_err(|e| {
-            Error::config_error(
-                state_path.display().to_string(),
-                format!("Failed to serialize state: {}", e),
-            )
-        })?;
+/// How video playback and fullscreen windows combine to suppress the backlight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SuppressionPolicy {
+    /// Only MPRIS video playback suppresses the backlight
+    Video,
+    /// Only fullscreen windows suppress the backlight
+    Fullscreen,
+    /// Either signal suppresses the backlight ("both" is accepted as an alias)
+    #[serde(alias = "both")]
+    Either,
+    /// Video must be playing while a window is fullscreen
+    FullscreenAndVideo,
+}
 
-        fs::write(&state_path, content).map_err(|e| {
-            Error::config_error(
-                state_path.display().to_string(),
-                format!("Failed to write state file: {}", e),
-            )
-        })?;
+impl SuppressionPolicy {
+    /// Whether this policy needs MPRIS video detection
+    pub fn uses_video(&self) -> bool {
+        !matches!(self, SuppressionPolicy::Fullscreen)
+    }
 
-        Ok(())
+    /// Whether this policy needs fullscreen detection
+    pub fn uses_fullscreen(&self) -> bool {
+        !matches!(self, SuppressionPolicy::Video)
     }
 
-    /// Save configuration to the XDG config directory
-    pub fn save(&self) -> Result<()> {
-        self.validate()?;
+    /// Decide whether the given signals suppress the backlight
+    pub fn suppresses(&self, is_video_playing: bool, is_fullscreen: bool) -> bool {
+        match self {
+            SuppressionPolicy::Video => is_video_playing,
+            SuppressionPolicy::Fullscreen => is_fullscreen,
+            SuppressionPolicy::Either => is_video_playing || is_fullscreen,
+            SuppressionPolicy::FullscreenAndVideo => is_video_playing && is_fullscreen,
+        }
+    }
+}
 
-        let config_dir = Self::get_config_dir();
-        let config_path = config_dir.join("config.toml");
-        let profiles_dir = config_dir.join("profiles");
+impl Default for LocationProfile {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            idle_timeout: 30,
+            time_schedules: vec![],
+            video_detection_enabled: true,
+            wifi_networks: vec![],
+            locations: vec![],
+            ac_always_on: false,
+            media_players: MediaPlayerFilter::default(),
+            suppression_policy: None,
+            meeting: None,
+            min_dwell_time: 0,
+            battery_rules: vec![],
+            on_ac: None,
+            on_battery: None,
+            power_modes: HashMap::new(),
+            default_brightness: None,
+            rule_order: vec![],
+            enabled_rules: HashMap::new(),
+            custom_rules: vec![],
+        }
+    }
+}
 
-        // Ensure the config and profiles directories exist
-        fs::create_dir_all(&config_dir).map_err(|e| {
-            Error::config_error(
-                config_dir.display().to_string(),
-                format!("Failed to create config directory: {}", e),
-            )
-        })?;
+/// Identifies a location by one or more network properties
+///
+/// Every property that is set must match. More specific properties score higher, so a
+/// BSSID or gateway matcher beats a plain SSID shared by several places (e.g. eduroam).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LocationMatcher {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssid: Option<SsidEntry>,
+    /// Access point MAC address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bssid: Option<String>,
+    /// MAC address of the default gateway, works for wired and wireless networks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway_mac: Option<String>,
+    /// NetworkManager connection name or UUID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<String>,
+    /// Wired interface that must have a carrier (e.g. a docking station NIC)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wired_interface: Option<String>,
+}
 
-        fs::create_dir_all(&profiles_dir).map_err(|e| {
-            Error::config_error(
-                profiles_dir.display().to_string(),
-                format!("Failed to create profiles directory: {}", e),
-            )
-        })?;
+impl LocationMatcher {
+    const BSSID_SCORE: u32 = 32;
+    const GATEWAY_SCORE: u32 = 16;
+    const CONNECTION_SCORE: u32 = 8;
+    const WIRED_SCORE: u32 = 4;
+    const SSID_SCORE: u32 = 2;
+    const SSID_PATTERN_SCORE: u32 = 1;
+
+    /// Whether no property is set (such a matcher would match everything)
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
 
-        let content = toml::to_string_pretty(self).map_err(|e| {
-            Error::config_error(
-                config_path.display().to_string(),
-                format!("Failed to serialize config: {}", e),
-            )
-        })?;
+    /// Score this matcher against the current network
+    /// Returns None if any configured property does not match
+    pub fn score(&self, fingerprint: &NetworkFingerprint) -> Option<u32> {
+        if self.is_empty() {
+            return None;
+        }
 
-        // Write to temporary file first, then rename for atomic operation
-        let temp_path = config_dir.join(".config.toml.tmp");
+        let mut score = 0;
+        if let Some(ref ssid) = self.ssid {
+            fingerprint
+                .ssid
+                .as_deref()
+                .is_some_and(|current| ssid.matches(current))
+                .then_some(())?;
+            // An exact SSID is more specific than a wildcard
+            score += match ssid.pattern() {
+                SsidPattern::Exact(_) => Self::SSID_SCORE,
+                _ => Self::SSID_PATTERN_SCORE,
+            };
+        }
+        if let Some(ref bssid) = self.bssid {
+            mac_matches(bssid, fingerprint.bssid.as_deref()).then_some(())?;
+            score += Self::BSSID_SCORE;
+        }
+        if let Some(ref gateway_mac) = self.gateway_mac {
+            mac_matches(gateway_mac, fingerprint.gateway_mac.as_deref()).then_some(())?;
+            score += Self::GATEWAY_SCORE;
+        }
+        if let Some(ref connection) = self.connection {
+            fingerprint
+                .connections
+                .iter()
+                .any(|c| c.id == *connection || c.uuid.eq_ignore_ascii_case(connection))
+                .then_some(())?;
+            score += Self::CONNECTION_SCORE;
+        }
+        if let Some(ref interface) = self.wired_interface {
+            fingerprint
+                .wired_interfaces
+                .contains(interface)
+                .then_some(())?;
+            score += Self::WIRED_SCORE;
+        }
 
-        fs::write(&temp_path, &content).map_err(|e| {
-            Error::config_error(
-                temp_path.display().to_string(),
-                format!("Failed to write temporary config file: {}", e),
-            )
-        })?;
+        Some(score)
+    }
 
-        fs::rename(&temp_path, &config_path).map_err(|e| {
-            // Clean up temp file on error
-            let _ = fs::remove_file(&temp_path);
-            Error::config_error(
-                config_path.display().to_string(),
-                format!("Failed to save config file: {}", e),
-            )
-        })?;
+    /// Canonical form used to detect identical matchers across profiles
+    fn normalized(&self) -> Self {
+        Self {
+            bssid: self.bssid.as_ref().map(|m| m.to_lowercase()),
+            gateway_mac: self.gateway_mac.as_ref().map(|m| m.to_lowercase()),
+            ..self.clone()
+        }
+    }
+}
 
-        // Save all profiles
-        for profile_name in self.profiles.keys() {
-            self.save_profile(profile_name)?;
+impl std::fmt::Display for LocationMatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = [
+            ("ssid", &self.ssid.as_ref().map(SsidEntry::to_string)),
+            ("bssid", &self.bssid),
+            ("gateway_mac", &self.gateway_mac),
+            ("connection", &self.connection),
+            ("wired_interface", &self.wired_interface),
+        ];
+        let parts: Vec<_> = fields
+            .iter()
+            .filter_map(|(key, value)| value.as_ref().map(|v| format!("{} = '{}'", key, v)))
+            .collect();
+        write!(f, "{{ {} }}", parts.join(", "))
+    }
+}
+
+/// An SSID entry: exact name, glob (`Starbucks*`, `Cafe-??`) or regex (`/^eduroam(-5G)?$/`)
+#[derive(Debug, Clone)]
+pub enum SsidPattern {
+    Exact(String),
+    Glob(Regex),
+    Regex(Regex),
+}
+
+impl SsidPattern {
+    /// Parse an SSID entry from a profile
+    pub fn parse(entry: &str) -> Result<Self> {
+        if let Some(re) = entry
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+            .filter(|re| !re.is_empty())
+        {
+            return Regex::new(re)
+                .map(SsidPattern::Regex)
+                .map_err(|e| Error::Parse(format!("Invalid SSID regex '{}': {}", entry, e)));
         }
 
-        // Save active profile state
-        self.save_active_profile()?;
+        if entry.contains(['*', '?']) {
+            let mut re = String::from("^");
+            for c in entry.chars() {
+                match c {
+                    '*' => re.push_str(".*"),
+                    '?' => re.push('.'),
+                    c => re.push_str(&regex::escape(&c.to_string())),
+                }
+            }
+            re.push('$');
+            return Regex::new(&re)
+                .map(SsidPattern::Glob)
+                .map_err(|e| Error::Parse(format!("Invalid SSID pattern '{}': {}", entry, e)));
+        }
 
-        Ok(())
+        Ok(SsidPattern::Exact(entry.to_string()))
     }
 
-    /// Save a single profile to its own file
-    pub fn save_profile(&self, profile_name: &str) -> Result<()> {
-        let profile = self.profiles.get(profile_name).ok_or_else(|| {
-            Error::ConfigValidation(format!("Profile '{}' not found", profile_name))
-        })?;
+    /// Check an SSID against this pattern
+    pub fn matches(&self, ssid: &str) -> bool {
+        match self {
+            SsidPattern::Exact(exact) => exact == ssid,
+            SsidPattern::Glob(re) | SsidPattern::Regex(re) => re.is_match(ssid),
+        }
+    }
+}
 
-        let config_dir = Self::get_config_dir();
-        let profiles_dir = config_dir.join("profiles");
+/// An SSID entry as written in a profile, compiled once when the profile is parsed
+///
+/// Compares, hashes and serializes as the original text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SsidEntry {
+    entry: String,
+    pattern: SsidPattern,
+}
 
-        // Ensure profiles directory exists
-        fs::create_dir_all(&profiles_dir).map_err(|e| {
-            Error::config_error(
-                profiles_dir.display().to_string(),
-                format!("Failed to create profiles directory: {}", e),
-            )
-        })?;
+impl SsidEntry {
+    /// The entry as written in the profile
+    pub fn as_str(&self) -> &str {
+        &self.entry
+    }
 
-        let profile_path = profiles_dir.join(format!("{}.toml", profile_name));
+    /// The compiled pattern
+    pub fn pattern(&self) -> &SsidPattern {
+        &self.pattern
+    }
 
-        let content = toml::to_string_pretty(profile).map_err(|e| {
-            Error::config_error(
-                profile_path.display().to_string(),
-                format!("Failed to serialize profile: {}", e),
-            )
-        })?;
+    /// Check an SSID against this entry
+    pub fn matches(&self, ssid: &str) -> bool {
+        self.pattern.matches(ssid)
+    }
+}
 
-        // Write to temporary file first
-        let temp_path = profiles_dir.join(format!(".{}.toml.tmp", profile_name));
+impl TryFrom<String> for SsidEntry {
+    type Error = Error;
 
-        fs::write(&temp_path, &content).map_err(|e| {
-            Error::config_error(
-                temp_path.display().to_string(),
-                format!("Failed to write temporary profile file: {}", e),
-            )
-        })?;
+    fn try_from(entry: String) -> Result<Self> {
+        let pattern = SsidPattern::parse(&entry)?;
+        Ok(Self { entry, pattern })
+    }
+}
 
-        fs::rename(&temp_path, &profile_path).map_err(|e| {
-            let _ = fs::remove_file(&temp_path);
-            Error::config_error(
-                profile_path.display().to_string(),
-                format!("Failed to save profile file: {}", e),
-            )
-        })?;
+impl std::str::FromStr for SsidEntry {
+    type Err = Error;
 
-        Ok(())
+    fn from_str(entry: &str) -> Result<Self> {
+        Self::try_from(entry.to_string())
     }
+}
 
-    /// Validate configuration for correctness
-    pub fn validate(&self) -> Result<()> {
-        // Check that profiles is not empty
-        if self.profiles.is_empty() {
-            return Err(Error::ConfigValidation(
-                "Configuration must have at least one profile".to_string(),
-            ));
-        }
+impl From<SsidEntry> for String {
+    fn from(entry: SsidEntry) -> Self {
+        entry.entry
+    }
+}
 
-        // Check that active_profile exists in profiles
-        if !self.profiles.contains_key(&self.active_profile) {
-            let available: Vec<_> = self.profiles.keys().collect();
+impl PartialEq for SsidEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.entry == other.entry
+    }
+}
+
+impl Eq for SsidEntry {}
+
+impl std::hash::Hash for SsidEntry {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.entry.hash(state);
+    }
+}
+
+impl std::fmt::Display for SsidEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.entry)
+    }
+}
+
+fn mac_matches(expected: &str, actual: Option<&str>) -> bool {
+    actual.is_some_and(|actual| actual.eq_ignore_ascii_case(expected))
+}
+
+/// Check the hour and minute of every schedule in a list
+fn validate_time_schedules(
+    config: &Config,
+    profile: &str,
+    label: &str,
+    schedules: &[TimeSchedule],
+) -> Result<()> {
+    for (idx, schedule) in schedules.iter().enumerate() {
+        if schedule.at.is_some() && (schedule.hour.is_some() || schedule.minute.is_some()) {
             return Err(Error::ConfigValidation(format!(
-                "Active profile '{}' not found in profiles. Available profiles: {:?}",
-                self.active_profile, available
+                "Profile '{}', {} #{}: Set either 'at' or 'hour'/'minute', not both",
+                profile,
+                label,
+                idx + 1
             )));
         }
-
-        // Check for duplicate WiFi networks across profiles
-        let mut wifi_to_profile: HashMap<String, String> = HashMap::new();
-        for (profile_name, profile) in &self.profiles {
-            for wifi in &profile.wifi_networks {
-                if let Some(existing_profile) = wifi_to_profile.get(wifi) {
-                    return Err(Error::ConfigValidation(format!(
-                        "WiFi network '{}' is assigned to multiple profiles: '{}' and '{}'",
-                        wifi, existing_profile, profile_name
-                    )));
-                }
-                wifi_to_profile.insert(wifi.clone(), profile_name.clone());
+        if let (Some(from), Some(until)) = (schedule.from, schedule.until) {
+            if from > until {
+                return Err(Error::ConfigValidation(format!(
+                    "Profile '{}', {} #{}: from ({}) is after until ({})",
+                    profile,
+                    label,
+                    idx + 1,
+                    from.0,
+                    until.0
+                )));
             }
         }
-
-        // Validate each profile
-        for (name, profile) in &self.profiles {
-            // Validate profile name matches key
-            if profile.name != *name {
+        if schedule.days.contains(&ScheduleDay::Holidays) && config.holidays.is_none() {
+            return Err(Error::ConfigValidation(format!(
+                "Profile '{}', {} #{}: 'holidays' needs a holidays file in config.toml",
+                profile,
+                label,
+                idx + 1
+            )));
+        }
+        if let Some(at) = schedule.at {
+            if config.coordinates().is_none() {
                 return Err(Error::ConfigValidation(format!(
-                    "Profile name '{}' does not match filename (expected '{}.toml')",
-                    profile.name, name
+                    "Profile '{}', {} #{}: '{}' needs latitude and longitude in config.toml",
+                    profile,
+                    label,
+                    idx + 1,
+                    at
                 )));
             }
+            continue;
+        }
+        let Some(hour) = schedule.hour else {
+            return Err(Error::ConfigValidation(format!(
+                "Profile '{}', {} #{}: Set 'hour' (and optionally 'minute') or 'at'",
+                profile,
+                label,
+                idx + 1
+            )));
+        };
+        if hour > 23 {
+            return Err(Error::ConfigValidation(format!(
+                "Profile '{}', {} #{}: Invalid hour {} (must be 0-23)",
+                profile,
+                label,
+                idx + 1,
+                hour
+            )));
+        }
+        let minute = schedule.minute.unwrap_or_default();
+        if minute > 59 {
+            return Err(Error::ConfigValidation(format!(
+                "Profile '{}', {} #{}: Invalid minute {} (must be 0-59)",
+                profile,
+                label,
+                idx + 1,
+                minute
+            )));
+        }
+        // Note: brightness validation is hardware-specific, so we don't validate it here
+    }
+    Ok(())
+}
 
-            // Validate idle timeout is reasonable (not too small)
-            if profile.idle_timeout == 0 {
-                return Err(Error::ConfigValidation(format!(
-                    "Profile '{}' has idle_timeout of 0. Use a positive value (recommended: 5-60 seconds)",
-                    name
-                )));
+fn is_valid_mac(mac: &str) -> bool {
+    let octets: Vec<_> = mac.split(':').collect();
+    octets.len() == 6
+        && octets
+            .iter()
+            .all(|o| o.len() == 2 && o.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Selects which MPRIS players may suppress the backlight
+///
+/// Entries match a player's bus-name suffix (`mpv` matches `org.mpris.MediaPlayer2.mpv`
+/// and `org.mpris.MediaPlayer2.mpv.instance42`) or its `Identity` (case-insensitive).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaPlayerFilter {
+    /// If non-empty, only these players are honoured
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Players that are always ignored
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+impl MediaPlayerFilter {
+    /// Check whether a player with the given bus name and identity is honoured
+    pub fn allows(&self, bus_name: &str, identity: Option<&str>) -> bool {
+        let matches = |pattern: &String| Self::matches(pattern, bus_name, identity);
+
+        if !self.include.is_empty() && !self.include.iter().any(matches) {
+            return false;
+        }
+
+        !self.exclude.iter().any(matches)
+    }
+
+    fn matches(pattern: &str, bus_name: &str, identity: Option<&str>) -> bool {
+        if identity.is_some_and(|id| id.eq_ignore_ascii_case(pattern)) {
+            return true;
+        }
+
+        // Compare against the bus name with the MPRIS prefix stripped, honouring
+        // per-instance suffixes such as "firefox.instance_1_42"
+        let short_name = bus_name.strip_prefix(MPRIS_BUS_PREFIX).unwrap_or(bus_name);
+        short_name == pattern
+            || short_name
+                .strip_prefix(pattern)
+                .is_some_and(|rest| rest.starts_with('.'))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeSchedule {
+    /// Clock time of the entry; `minute` defaults to 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hour: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minute: Option<u8>,
+    /// Device level or percentage, mapped onto the device's steps
+    pub brightness: BrightnessValue,
+    /// Time relative to sunrise or sunset, used instead of `hour`/`minute`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<SolarTime>,
+    /// Days the entry applies on; empty means every day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<ScheduleDay>,
+    /// First date the entry applies on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<ScheduleDate>,
+    /// Last date the entry applies on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<ScheduleDate>,
+    /// Ramp gradually to the next entry's brightness instead of holding this level
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interpolate: bool,
+}
+
+impl TimeSchedule {
+    /// Wall-clock time of this schedule on the given date in the given timezone
+    ///
+    /// Sunrise/sunset schedules need coordinates and don't occur on polar days or nights.
+    pub fn time_on<Tz: TimeZone>(
+        &self,
+        date: NaiveDate,
+        coordinates: Option<(f64, f64)>,
+        timezone: &Tz,
+    ) -> Option<NaiveTime> {
+        match self.at {
+            Some(at) => {
+                let (latitude, longitude) = coordinates?;
+                at.resolve(date, latitude, longitude, timezone)
             }
+            None => NaiveTime::from_hms_opt(
+                self.hour? as u32,
+                self.minute.unwrap_or_default() as u32,
+                0,
+            ),
+        }
+    }
 
-            // Validate time schedules
-            for (idx, schedule) in profile.time_schedules.iter().enumerate() {
-                if schedule.hour > 23 {
-                    return Err(Error::ConfigValidation(format!(
-                        "Profile '{}', schedule #{}: Invalid hour {} (must be 0-23)",
-                        name,
-                        idx + 1,
-                        schedule.hour
-                    )));
-                }
-                if schedule.minute > 59 {
-                    return Err(Error::ConfigValidation(format!(
-                        "Profile '{}', schedule #{}: Invalid minute {} (must be 0-59)",
-                        name,
-                        idx + 1,
-                        schedule.minute
-                    )));
-                }
-                // Note: brightness validation is hardware-specific, so we don't validate it here
+    /// Whether the entry applies on the given date
+    ///
+    /// On holidays, entries with a `days` filter only apply when it lists `holidays`.
+    pub fn applies_on(&self, date: NaiveDate, holidays: &Holidays) -> bool {
+        if self.from.is_some_and(|from| date < from.0)
+            || self.until.is_some_and(|until| date > until.0)
+        {
+            return false;
+        }
+        if self.days.is_empty() {
+            return true;
+        }
+        if holidays.contains(date) {
+            return self.days.contains(&ScheduleDay::Holidays);
+        }
+        self.days.iter().any(|day| day.matches(date.weekday()))
+    }
+}
+
+/// Day filter for schedule entries: a weekday (`"mon"`), `"weekdays"`, `"weekends"`
+/// or `"holidays"` from the holidays file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ScheduleDay {
+    Day(Weekday),
+    Weekdays,
+    Weekends,
+    Holidays,
+}
+
+impl ScheduleDay {
+    fn matches(self, weekday: Weekday) -> bool {
+        let weekend = matches!(weekday, Weekday::Sat | Weekday::Sun);
+        match self {
+            ScheduleDay::Day(day) => day == weekday,
+            ScheduleDay::Weekdays => !weekend,
+            ScheduleDay::Weekends => weekend,
+            ScheduleDay::Holidays => false,
+        }
+    }
+}
+
+impl TryFrom<String> for ScheduleDay {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "weekdays" => Ok(ScheduleDay::Weekdays),
+            "weekends" => Ok(ScheduleDay::Weekends),
+            "holidays" => Ok(ScheduleDay::Holidays),
+            day => day.parse().map(ScheduleDay::Day).map_err(|_| {
+                format!(
+                    "invalid day '{}', expected e.g. \"mon\", \"weekdays\", \"weekends\" or \"holidays\"",
+                    value
+                )
+            }),
+        }
+    }
+}
+
+impl From<ScheduleDay> for String {
+    fn from(day: ScheduleDay) -> Self {
+        day.to_string()
+    }
+}
+
+impl std::fmt::Display for ScheduleDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleDay::Day(day) => write!(f, "{}", day.to_string().to_ascii_lowercase()),
+            ScheduleDay::Weekdays => write!(f, "weekdays"),
+            ScheduleDay::Weekends => write!(f, "weekends"),
+            ScheduleDay::Holidays => write!(f, "holidays"),
+        }
+    }
+}
+
+/// A calendar date in schedules, written as `"2024-12-24"` or a TOML date
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "RawScheduleDate", into = "RawScheduleDate")]
+pub struct ScheduleDate(pub NaiveDate);
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawScheduleDate {
+    Date(toml::value::Datetime),
+    Text(String),
+}
+
+impl TryFrom<RawScheduleDate> for ScheduleDate {
+    type Error = String;
+
+    fn try_from(raw: RawScheduleDate) -> std::result::Result<Self, Self::Error> {
+        let text = match raw {
+            RawScheduleDate::Date(datetime) => datetime.to_string(),
+            RawScheduleDate::Text(text) => text,
+        };
+        NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+            .map(ScheduleDate)
+            .map_err(|_| format!("invalid date '{}', expected YYYY-MM-DD", text))
+    }
+}
+
+impl From<ScheduleDate> for RawScheduleDate {
+    fn from(date: ScheduleDate) -> Self {
+        RawScheduleDate::Text(date.0.format("%Y-%m-%d").to_string())
+    }
+}
+
+/// Sunrise or sunset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolarEvent {
+    Sunrise,
+    Sunset,
+}
+
+/// A time relative to sunrise or sunset, written as `"sunset"`, `"sunset-30m"` or
+/// `"sunrise+1h15m"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SolarTime {
+    pub event: SolarEvent,
+    /// Minutes after (positive) or before (negative) the event
+    pub offset_minutes: i32,
+}
+
+impl SolarTime {
+    /// Time of the event plus offset on the given date, in the given timezone
+    /// None when the sun doesn't rise or set that day
+    pub fn resolve<Tz: TimeZone>(
+        &self,
+        date: NaiveDate,
+        latitude: f64,
+        longitude: f64,
+        timezone: &Tz,
+    ) -> Option<NaiveTime> {
+        let SunTimes::Normal { sunrise, sunset } = solar::sun_times(date, latitude, longitude)
+        else {
+            return None;
+        };
+        let event = match self.event {
+            SolarEvent::Sunrise => sunrise,
+            SolarEvent::Sunset => sunset,
+        };
+        let time = event + chrono::Duration::minutes(self.offset_minutes as i64);
+        Some(time.with_timezone(timezone).time())
+    }
+}
+
+impl TryFrom<String> for SolarTime {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        let invalid = || {
+            format!(
+                "invalid solar time '{}', expected e.g. \"sunset\", \"sunset-30m\" or \"sunrise+1h\"",
+                value
+            )
+        };
+        let text = value.trim().to_ascii_lowercase();
+        let (event, rest) = if let Some(rest) = text.strip_prefix("sunrise") {
+            (SolarEvent::Sunrise, rest)
+        } else if let Some(rest) = text.strip_prefix("sunset") {
+            (SolarEvent::Sunset, rest)
+        } else {
+            return Err(invalid());
+        };
+
+        let offset_minutes = match rest.trim_start() {
+            "" => 0,
+            rest => {
+                let (sign, duration) = match rest.split_at(1) {
+                    ("+", duration) => (1, duration),
+                    ("-", duration) => (-1, duration),
+                    _ => return Err(invalid()),
+                };
+                sign * parse_offset_minutes(duration.trim()).ok_or_else(invalid)?
             }
+        };
+
+        Ok(Self {
+            event,
+            offset_minutes,
+        })
+    }
+}
+
+impl From<SolarTime> for String {
+    fn from(value: SolarTime) -> Self {
+        value.to_string()
+    }
+}
+
+impl std::fmt::Display for SolarTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let event = match self.event {
+            SolarEvent::Sunrise => "sunrise",
+            SolarEvent::Sunset => "sunset",
+        };
+        if self.offset_minutes == 0 {
+            return f.pad(event);
         }
+        let sign = if self.offset_minutes < 0 { '-' } else { '+' };
+        let minutes = self.offset_minutes.unsigned_abs();
+        let offset = match (minutes / 60, minutes % 60) {
+            (0, m) => format!("{}m", m),
+            (h, 0) => format!("{}h", h),
+            (h, m) => format!("{}h{}m", h, m),
+        };
+        f.pad(&format!("{}{}{}", event, sign, offset))
+    }
+}
+
+/// Parse `30m`, `1h` or `1h30m` into minutes, at most 12 hours
+fn parse_offset_minutes(text: &str) -> Option<i32> {
+    let (hours, minutes) = match text.split_once('h') {
+        Some((hours, minutes)) => (hours.parse::<i32>().ok()?, minutes),
+        None => (0, text),
+    };
+    let minutes = match minutes.strip_suffix('m') {
+        Some(minutes) => minutes.parse::<i32>().ok()?,
+        None if minutes.is_empty() && text.contains('h') => 0,
+        None => return None,
+    };
+    let total = hours * 60 + minutes;
+    (hours >= 0 && minutes >= 0 && total > 0 && total <= 12 * 60).then_some(total)
+}
+
+impl Config {
+    /// Coordinates for sunrise/sunset schedules, if configured
+    pub fn coordinates(&self) -> Option<(f64, f64)> {
+        self.latitude.zip(self.longitude)
+    }
+
+    /// Load configuration from the XDG config directory
+    /// If the file doesn't exist, create a default configuration
+    pub fn load() -> Result<Self> {
+        let config_dir = Self::get_config_dir();
+
+        // Create default configuration if it doesn't exist
+        if !config_dir.join("config.toml").exists() {
+            let default_config = Self::default();
+            default_config.save()?;
+            return Ok(default_config);
+        }
+
+        // Create profiles directory if it doesn't exist
+        let profiles_dir = config_dir.join("profiles");
+        if !profiles_dir.exists() {
+            fs::create_dir_all(&profiles_dir).map_err(|e| {
+                Error::config_error(
+                    profiles_dir.display().to_string(),
+                    format!("Failed to create profiles directory: {}", e),
+                )
+            })?;
+        }
+
+        Self::load_from(&config_dir)
+    }
+
+    /// Load an existing configuration without writing anything
+    /// Fails if config.toml is missing, e.g. when reloading after it was deleted.
+    pub fn load_from(config_dir: &Path) -> Result<Self> {
+        let config_path = config_dir.join("config.toml");
+
+        // Load main config.toml
+        let content = fs::read_to_string(&config_path).map_err(|e| {
+            Error::config_error(
+                config_path.display().to_string(),
+                format!("Failed to read config file: {}", e),
+            )
+        })?;
+
+        let mut config: Config = toml::from_str(&content).map_err(|e| {
+            let error_msg = e.to_string();
+            if error_msg.contains("line") {
+                Error::ConfigSyntax(format!(
+                    "TOML syntax error in {}: {}",
+                    config_path.display(),
+                    error_msg
+                ))
+            } else {
+                Error::ConfigSyntax(format!(
+                    "Failed to parse config file {}: {}",
+                    config_path.display(),
+                    error_msg
+                ))
+            }
+        })?;
+
+        if let Some(holidays) = &config.holidays {
+            config.holiday_calendar = Holidays::load(&config_dir.join(holidays))?;
+        }
+
+        // Load all profile files from profiles/ directory
+        config.profiles = Self::load_profiles(config_dir)?;
+
+        // Load active profile, pin and manual override from state file
+        let state = Self::load_state(config_dir)?;
+        config.active_profile = state.active_profile;
+        config.profile_pin = state.profile_pin;
+        config.manual_override = state.manual_override;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load all profile files from the profiles directory
+    fn load_profiles(config_dir: &Path) -> Result<HashMap<String, LocationProfile>> {
+        let profiles_dir = config_dir.join("profiles");
+        let mut profiles = HashMap::new();
+        let mut seen_names = HashSet::new();
+
+        // Read all .toml files in profiles directory
+        let entries = fs::read_dir(&profiles_dir).map_err(|e| {
+            Error::config_error(
+                profiles_dir.display().to_string(),
+                format!("Failed to read profiles directory: {}", e),
+            )
+        })?;
+
+        for entry in entries {
+            let entry = entry.map_err(|e| {
+                Error::config_error(
+                    profiles_dir.display().to_string(),
+                    format!("Failed to read directory entry: {}", e),
+                )
+            })?;
+
+            let path = entry.path();
+
+            // Skip non-files and non-.toml files
+            if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("toml") {
+                continue;
+            }
+
+            // Load profile
+            let content = fs::read_to_string(&path).map_err(|e| {
+                Error::config_error(
+                    path.display().to_string(),
+                    format!("Failed to read profile file: {}", e),
+                )
+            })?;
+
+            let profile: LocationProfile = toml::from_str(&content).map_err(|e| {
+                let error_msg = e.to_string();
+                if error_msg.contains("line") {
+                    Error::ConfigSyntax(format!(
+                        "TOML syntax error in {}: {}",
+                        path.display(),
+                        error_msg
+                    ))
+                } else {
+                    Error::ConfigSyntax(format!(
+                        "Failed to parse profile file {}: {}",
+                        path.display(),
+                        error_msg
+                    ))
+                }
+            })?;
+
+            // Check for duplicate profile names
+            if !seen_names.insert(profile.name.clone()) {
+                return Err(Error::ConfigValidation(format!(
+                    "Duplicate profile name '{}' found in {}",
+                    profile.name,
+                    path.display()
+                )));
+            }
+
+            profiles.insert(profile.name.clone(), profile);
+        }
+
+        // Ensure at least one profile exists
+        if profiles.is_empty() {
+            return Err(Error::ConfigValidation(
+                "No profiles found. At least one profile is required.".to_string(),
+            ));
+        }
+
+        Ok(profiles)
+    }
+
+    /// Load active profile, pin and manual override from state file
+    fn load_state(config_dir: &Path) -> Result<State> {
+        let state_path = config_dir.join("state.toml");
+
+        if !state_path.exists() {
+            // Default to first available profile
+            return Ok(State {
+                active_profile: "home".to_string(),
+                profile_pin: None,
+                manual_override: None,
+            });
+        }
+
+        let content = fs::read_to_string(&state_path).map_err(|e| {
+            Error::config_error(
+                state_path.display().to_string(),
+                format!("Failed to read state file: {}", e),
+            )
+        })?;
+
+        let state: State = toml::from_str(&content)
+            .map_err(|e| Error::ConfigSyntax(format!("Failed to parse state file: {}", e)))?;
+
+        Ok(state)
+    }
+
+    /// Save the active profile, its pin and the manual override to state.toml
+    pub fn save_active_profile(&self) -> Result<()> {
+        let config_dir = Self::get_config_dir();
+        let state_path = config_dir.join("state.toml");
+
+        let state = State {
+            active_profile: self.active_profile.clone(),
+            profile_pin: self.profile_pin.clone(),
+            manual_override: self.manual_override,
+        };
+
+        let content = toml::to_string_pretty(&state).map_err(|e| {
+            Error::config_error(
+                state_path.display().to_string(),
+                format!("Failed to serialize state: {}", e),
+            )
+        })?;
+
+        fs::write(&state_path, content).map_err(|e| {
+            Error::config_error(
+                state_path.display().to_string(),
+                format!("Failed to write state file: {}", e),
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Save configuration to the XDG config directory
+    pub fn save(&self) -> Result<()> {
+        self.validate()?;
+
+        let config_dir = Self::get_config_dir();
+        let config_path = config_dir.join("config.toml");
+        let profiles_dir = config_dir.join("profiles");
+
+        // Ensure the config and profiles directories exist
+        fs::create_dir_all(&config_dir).map_err(|e| {
+            Error::config_error(
+                config_dir.display().to_string(),
+                format!("Failed to create config directory: {}", e),
+            )
+        })?;
+
+        fs::create_dir_all(&profiles_dir).map_err(|e| {
+            Error::config_error(
+                profiles_dir.display().to_string(),
+                format!("Failed to create profiles directory: {}", e),
+            )
+        })?;
+
+        let content = toml::to_string_pretty(self).map_err(|e| {
+            Error::config_error(
+                config_path.display().to_string(),
+                format!("Failed to serialize config: {}", e),
+            )
+        })?;
+
+        // Write to temporary file first, then rename for atomic operation
+        let temp_path = config_dir.join(".config.toml.tmp");
+
+        fs::write(&temp_path, &content).map_err(|e| {
+            Error::config_error(
+                temp_path.display().to_string(),
+                format!("Failed to write temporary config file: {}", e),
+            )
+        })?;
+
+        fs::rename(&temp_path, &config_path).map_err(|e| {
+            // Clean up temp file on error
+            let _ = fs::remove_file(&temp_path);
+            Error::config_error(
+                config_path.display().to_string(),
+                format!("Failed to save config file: {}", e),
+            )
+        })?;
+
+        // Save all profiles
+        for profile_name in self.profiles.keys() {
+            self.save_profile(profile_name)?;
+        }
+
+        // Save active profile state
+        self.save_active_profile()?;
+
+        Ok(())
+    }
+
+    /// Save a single profile to its own file
+    pub fn save_profile(&self, profile_name: &str) -> Result<()> {
+        let profile = self.profiles.get(profile_name).ok_or_else(|| {
+            Error::ConfigValidation(format!("Profile '{}' not found", profile_name))
+        })?;
+
+        let config_dir = Self::get_config_dir();
+        let profiles_dir = config_dir.join("profiles");
+
+        // Ensure profiles directory exists
+        fs::create_dir_all(&profiles_dir).map_err(|e| {
+            Error::config_error(
+                profiles_dir.display().to_string(),
+                format!("Failed to create profiles directory: {}", e),
+            )
+        })?;
+
+        let profile_path = profiles_dir.join(format!("{}.toml", profile_name));
+
+        let content = toml::to_string_pretty(profile).map_err(|e| {
+            Error::config_error(
+                profile_path.display().to_string(),
+                format!("Failed to serialize profile: {}", e),
+            )
+        })?;
+
+        // Write to temporary file first
+        let temp_path = profiles_dir.join(format!(".{}.toml.tmp", profile_name));
+
+        fs::write(&temp_path, &content).map_err(|e| {
+            Error::config_error(
+                temp_path.display().to_string(),
+                format!("Failed to write temporary profile file: {}", e),
+            )
+        })?;
+
+        fs::rename(&temp_path, &profile_path).map_err(|e| {
+            let _ = fs::remove_file(&temp_path);
+            Error::config_error(
+                profile_path.display().to_string(),
+                format!("Failed to save profile file: {}", e),
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Validate configuration for correctness
+    pub fn validate(&self) -> Result<()> {
+        // Check that profiles is not empty
+        if self.profiles.is_empty() {
+            return Err(Error::ConfigValidation(
+                "Configuration must have at least one profile".to_string(),
+            ));
+        }
+
+        // Check that active_profile exists in profiles
+        if !self.profiles.contains_key(&self.active_profile) {
+            let available: Vec<_> = self.profiles.keys().collect();
+            return Err(Error::ConfigValidation(format!(
+                "Active profile '{}' not found in profiles. Available profiles: {:?}",
+                self.active_profile, available
+            )));
+        }
+
+        // Check the coordinates used for sunrise/sunset schedules
+        match (self.latitude, self.longitude) {
+            (Some(latitude), Some(longitude)) => {
+                if !(-90.0..=90.0).contains(&latitude) {
+                    return Err(Error::ConfigValidation(format!(
+                        "Invalid latitude {} (must be -90 to 90)",
+                        latitude
+                    )));
+                }
+                if !(-180.0..=180.0).contains(&longitude) {
+                    return Err(Error::ConfigValidation(format!(
+                        "Invalid longitude {} (must be -180 to 180)",
+                        longitude
+                    )));
+                }
+            }
+            (None, None) => {}
+            _ => {
+                return Err(Error::ConfigValidation(
+                    "latitude and longitude must be set together".to_string(),
+                ))
+            }
+        }
+
+        // Check that fallback profiles exist
+        for (key, profile) in [
+            ("fallback_profile", &self.fallback_profile),
+            ("offline_profile", &self.offline_profile),
+        ] {
+            if let Some(profile) = profile {
+                if !self.profiles.contains_key(profile) {
+                    return Err(Error::ConfigValidation(format!(
+                        "{} '{}' not found in profiles",
+                        key, profile
+                    )));
+                }
+            }
+        }
+
+        // Check for identical location matchers across profiles
+        // The same SSID may appear in several profiles if other properties tell them apart
+        // SsidEntry hashes its source text only, so the compiled regex cache is not part of the key
+        #[allow(clippy::mutable_key_type)]
+        let mut matcher_to_profile: HashMap<LocationMatcher, &str> = HashMap::new();
+        for (profile_name, profile) in &self.profiles {
+            for matcher in profile.location_matchers() {
+                let matcher = matcher.normalized();
+                if let Some(existing_profile) = matcher_to_profile.get(&matcher) {
+                    if existing_profile != profile_name {
+                        return Err(Error::ConfigValidation(format!(
+                            "Location {} is assigned to multiple profiles: '{}' and '{}'",
+                            matcher, existing_profile, profile_name
+                        )));
+                    }
+                }
+                matcher_to_profile.insert(matcher, profile_name);
+            }
+        }
+
+        // Validate each profile
+        for (name, profile) in &self.profiles {
+            // Validate profile name matches key
+            if profile.name != *name {
+                return Err(Error::ConfigValidation(format!(
+                    "Profile name '{}' does not match filename (expected '{}.toml')",
+                    profile.name, name
+                )));
+            }
+
+            // Validate idle timeout is reasonable (not too small)
+            if profile.idle_timeout == 0 {
+                return Err(Error::ConfigValidation(format!(
+                    "Profile '{}' has idle_timeout of 0. Use a positive value (recommended: 5-60 seconds)",
+                    name
+                )));
+            }
+
+            // Validate battery rules
+            for (idx, rule) in profile.battery_rules.iter().enumerate() {
+                if rule.below == 0 || rule.below > 100 {
+                    return Err(Error::ConfigValidation(format!(
+                        "Profile '{}', battery rule #{}: Invalid threshold {} (must be 1-100)",
+                        name,
+                        idx + 1,
+                        rule.below
+                    )));
+                }
+                if rule.brightness.is_none() && rule.max_brightness.is_none() {
+                    return Err(Error::ConfigValidation(format!(
+                        "Profile '{}', battery rule #{}: Set brightness or max_brightness",
+                        name,
+                        idx + 1
+                    )));
+                }
+            }
+
+            // Validate location matchers
+            for (idx, matcher) in profile.locations.iter().enumerate() {
+                if matcher.is_empty() {
+                    return Err(Error::ConfigValidation(format!(
+                        "Profile '{}', location #{}: Set at least one of ssid, bssid, gateway_mac, connection or wired_interface",
+                        name,
+                        idx + 1
+                    )));
+                }
+                for mac in [&matcher.bssid, &matcher.gateway_mac].into_iter().flatten() {
+                    if !is_valid_mac(mac) {
+                        return Err(Error::ConfigValidation(format!(
+                            "Profile '{}', location #{}: Invalid MAC address '{}' (expected aa:bb:cc:dd:ee:ff)",
+                            name,
+                            idx + 1,
+                            mac
+                        )));
+                    }
+                }
+            }
+
+            // Validate time schedules
+            validate_time_schedules(self, name, "schedule", &profile.time_schedules)?;
+
+            // Validate power source policies
+            for (label, policy) in [
+                ("on_ac", &profile.on_ac),
+                ("on_battery", &profile.on_battery),
+            ] {
+                let Some(policy) = policy else { continue };
+                if let Some(schedules) = &policy.time_schedules {
+                    validate_time_schedules(self, name, &format!("{} schedule", label), schedules)?;
+                }
+                if policy.idle_timeout == Some(0) {
+                    return Err(Error::ConfigValidation(format!(
+                        "Profile '{}', {}: idle_timeout must be greater than 0",
+                        name, label
+                    )));
+                }
+                let inverted = match (policy.min_brightness, policy.max_brightness) {
+                    (Some(BrightnessValue::Level(min)), Some(BrightnessValue::Level(max))) => {
+                        min > max
+                    }
+                    (Some(BrightnessValue::Percent(min)), Some(BrightnessValue::Percent(max))) => {
+                        min > max
+                    }
+                    _ => false,
+                };
+                if inverted {
+                    return Err(Error::ConfigValidation(format!(
+                        "Profile '{}', {}: min_brightness is above max_brightness",
+                        name, label
+                    )));
+                }
+            }
+
+            // Validate custom rule conditions
+            for (idx, rule) in profile.custom_rules.iter().enumerate() {
+                if let Some(error) = rule.when.error() {
+                    return Err(Error::ConfigValidation(format!(
+                        "Profile '{}', custom rule #{}: invalid condition at {}",
+                        name,
+                        idx + 1,
+                        error.describe(rule.when.source())
+                    )));
+                }
+            }
+
+            // Validate the rule order
+            for (idx, layer) in profile.rule_order.iter().enumerate() {
+                if profile.rule_order[..idx].contains(layer) {
+                    return Err(Error::ConfigValidation(format!(
+                        "Profile '{}': rule '{}' is listed twice in rule_order",
+                        name, layer
+                    )));
+                }
+            }
+
+            // Validate power mode overrides
+            for (mode, mode_override) in &profile.power_modes {
+                if mode_override.idle_timeout == Some(0) {
+                    return Err(Error::ConfigValidation(format!(
+                        "Profile '{}', power mode '{}': idle_timeout must be greater than 0",
+                        name, mode
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Settings that are valid but probably not what the user wants
+    pub fn warnings(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.profiles.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .filter(|name| !self.profiles[*name].can_turn_on(self.default_brightness))
+            .map(|name| {
+                format!(
+                    "Profile '{}' never turns the backlight on; add a time schedule or set default_brightness",
+                    name
+                )
+            })
+            .collect()
+    }
+
+    /// Get the configuration directory using XDG config directory
+    pub fn get_config_dir() -> PathBuf {
+        // Use XDG_CONFIG_HOME if set, otherwise use ~/.config
+        let config_dir = std::env::var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| {
+                let home = std::env::var("HOME").expect("HOME environment variable not set");
+                PathBuf::from(home).join(".config")
+            });
+
+        config_dir.join("kbd-backlight")
+    }
+
+    /// Find the profile to use on the current network
+    ///
+    /// The highest-scoring matcher wins. Ties prefer the active profile, then the
+    /// alphabetically first profile name, so the result is stable. Without a match,
+    /// `offline_profile` applies while WiFi is disconnected and `fallback_profile`
+    /// otherwise; None keeps the current profile.
+    pub fn resolve_location(&self, fingerprint: &NetworkFingerprint) -> Option<&str> {
+        self.resolve_location_source(fingerprint)
+            .map(|(name, _)| name)
+    }
+
+    /// Like `resolve_location`, but also reports why the profile was chosen
+    pub fn resolve_location_source(
+        &self,
+        fingerprint: &NetworkFingerprint,
+    ) -> Option<(&str, LocationSource)> {
+        if let Some(name) = self.match_location(fingerprint) {
+            return Some((name, LocationSource::Matched));
+        }
+
+        if fingerprint.ssid.is_none() {
+            if let Some(ref name) = self.offline_profile {
+                return Some((name, LocationSource::Offline));
+            }
+        }
+        self.fallback_profile
+            .as_deref()
+            .map(|name| (name, LocationSource::Fallback))
+    }
+
+    /// Find the profile whose location matchers best fit the current network
+    fn match_location(&self, fingerprint: &NetworkFingerprint) -> Option<&str> {
+        self.profiles
+            .iter()
+            .filter_map(|(name, profile)| {
+                let score = profile.location_score(fingerprint)?;
+                Some((score, *name == self.active_profile, Reverse(name.as_str())))
+            })
+            .max()
+            .map(|(_, _, Reverse(name))| name)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut profiles = HashMap::new();
+
+        // Create a default "home" profile
+        profiles.insert(
+            "home".to_string(),
+            LocationProfile {
+                name: "home".to_string(),
+                idle_timeout: 30,
+                video_detection_enabled: true,
+                wifi_networks: vec![],
+                locations: vec![],
+                ac_always_on: false,
+                media_players: MediaPlayerFilter::default(),
+                suppression_policy: None,
+                meeting: None,
+                min_dwell_time: 0,
+                battery_rules: vec![],
+                on_ac: None,
+                on_battery: None,
+                power_modes: HashMap::new(),
+                default_brightness: None,
+                rule_order: vec![],
+                enabled_rules: HashMap::new(),
+                custom_rules: vec![],
+                time_schedules: vec![
+                    TimeSchedule {
+                        hour: Some(9),
+                        minute: Some(0),
+                        brightness: BrightnessValue::Level(1),
+                        ..Default::default()
+                    },
+                    TimeSchedule {
+                        hour: Some(22),
+                        minute: Some(0),
+                        brightness: BrightnessValue::Level(0),
+                        ..Default::default()
+                    },
+                ],
+            },
+        );
+
+        Config {
+            profiles,
+            active_profile: "home".to_string(),
+            auto_switch_location: false,
+            fallback_profile: None,
+            offline_profile: None,
+            location_settle_time: 5,
+            profile_pin: None,
+            manual_override: None,
+            latitude: None,
+            longitude: None,
+            default_brightness: None,
+            holidays: None,
+            holiday_calendar: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    // Mutex to serialize tests that modify environment variables
+    static ENV_MUTEX: Mutex<()> = Mutex::new(());
+
+    fn setup_test_env() -> (TempDir, std::sync::MutexGuard<'static, ()>) {
+        let guard = ENV_MUTEX.lock().unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        env::set_var("XDG_CONFIG_HOME", temp_dir.path());
+        (temp_dir, guard)
+    }
+
+    #[test]
+    fn test_default_config_creation() {
+        let config = Config::default();
+        assert_eq!(config.active_profile, "home");
+        assert!(config.profiles.contains_key("home"));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_config_save_and_load() {
+        let (_temp_dir, _guard) = setup_test_env();
+
+        let config = Config::default();
+        assert!(config.save().is_ok());
+
+        let loaded_config = Config::load().unwrap();
+        assert_eq!(loaded_config.active_profile, config.active_profile);
+        assert_eq!(loaded_config.profiles.len(), config.profiles.len());
+    }
+
+    #[test]
+    fn test_config_validation_invalid_profile() {
+        let config = Config {
+            active_profile: "nonexistent".to_string(),
+            ..Default::default()
+        };
+
+        let result = config.validate();
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("not found in profiles"));
+    }
+
+    #[test]
+    fn test_config_validation_invalid_hour() {
+        let mut config = Config::default();
+        if let Some(profile) = config.profiles.get_mut("home") {
+            profile.time_schedules.push(TimeSchedule {
+                hour: Some(25),
+                minute: Some(0),
+                brightness: BrightnessValue::Level(2),
+                ..Default::default()
+            });
+        }
+
+        let result = config.validate();
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("Invalid hour"));
+    }
+
+    #[test]
+    fn test_config_validation_invalid_minute() {
+        let mut config = Config::default();
+        if let Some(profile) = config.profiles.get_mut("home") {
+            profile.time_schedules.push(TimeSchedule {
+                hour: Some(12),
+                minute: Some(60),
+                brightness: BrightnessValue::Level(2),
+                ..Default::default()
+            });
+        }
+
+        let result = config.validate();
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("Invalid minute"));
+    }
+
+    #[test]
+    fn test_toml_serialization() {
+        let (_temp_dir, _guard) = setup_test_env();
+        let config = Config::default();
+
+        // Save and reload to test serialization
+        assert!(config.save().is_ok());
+        let loaded = Config::load().unwrap();
+
+        assert_eq!(config.active_profile, loaded.active_profile);
+        assert_eq!(config.auto_switch_location, loaded.auto_switch_location);
+    }
+
+    #[test]
+    fn test_multiple_profiles() {
+        let mut config = Config::default();
+
+        config.profiles.insert(
+            "office".to_string(),
+            LocationProfile {
+                name: "office".to_string(),
+                idle_timeout: 5,
+                video_detection_enabled: true,
+                wifi_networks: vec![],
+                ac_always_on: false,
+                time_schedules: vec![TimeSchedule {
+                    hour: Some(8),
+                    minute: Some(0),
+                    brightness: BrightnessValue::Level(3),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        );
+
+        assert!(config.validate().is_ok());
+        assert_eq!(config.profiles.len(), 2);
+    }
+
+    #[test]
+    fn test_profile_independence() {
+        let mut config = Config::default();
+
+        // Add a second profile with different settings
+        config.profiles.insert(
+            "office".to_string(),
+            LocationProfile {
+                name: "office".to_string(),
+                idle_timeout: 5,
+                video_detection_enabled: true,
+                wifi_networks: vec![],
+                ac_always_on: false,
+                time_schedules: vec![TimeSchedule {
+                    hour: Some(8),
+                    minute: Some(0),
+                    brightness: BrightnessValue::Level(3),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        );
+
+        // Verify home profile is unchanged
+        let home = config.profiles.get("home").unwrap();
+        assert_eq!(home.idle_timeout, 30);
+        assert_eq!(home.time_schedules.len(), 2);
+
+        // Verify office profile has its own settings
+        let office = config.profiles.get("office").unwrap();
+        assert_eq!(office.idle_timeout, 5);
+        assert_eq!(office.time_schedules.len(), 1);
+        assert_eq!(
+            office.time_schedules[0].brightness,
+            BrightnessValue::Level(3)
+        );
+    }
+
+    #[test]
+    fn test_profile_switching() {
+        let mut config = Config::default();
+
+        // Add office profile
+        config.profiles.insert(
+            "office".to_string(),
+            LocationProfile {
+                name: "office".to_string(),
+                idle_timeout: 5,
+                video_detection_enabled: true,
+                wifi_networks: vec![],
+                ac_always_on: false,
+                time_schedules: vec![],
+                ..Default::default()
+            },
+        );
+
+        // Initially on home profile
+        assert_eq!(config.active_profile, "home");
+
+        // Switch to office profile
+        config.active_profile = "office".to_string();
+        assert_eq!(config.active_profile, "office");
+        assert!(config.validate().is_ok());
+
+        // Verify we can access the active profile's settings
+        let active = config.profiles.get(&config.active_profile).unwrap();
+        assert_eq!(active.idle_timeout, 5);
+    }
+
+    #[test]
+    fn test_profile_persistence() {
+        let (_temp_dir, _guard) = setup_test_env();
+
+        let mut config = Config::default();
+
+        // Add office profile
+        config.profiles.insert(
+            "office".to_string(),
+            LocationProfile {
+                name: "office".to_string(),
+                idle_timeout: 5,
+                video_detection_enabled: true,
+                wifi_networks: vec![],
+                ac_always_on: false,
+                time_schedules: vec![],
+                ..Default::default()
+            },
+        );
+
+        // Switch to office profile
+        config.active_profile = "office".to_string();
+
+        // Save everything (profiles and state)
+        assert!(config.save().is_ok());
+
+        // Load config and verify active profile persisted
+        let loaded = Config::load().unwrap();
+        assert_eq!(loaded.active_profile, "office");
+        assert_eq!(loaded.profiles.len(), 2);
+    }
+
+    #[test]
+    fn test_profile_pin_persistence() {
+        let (temp_dir, _guard) = setup_test_env();
+
+        let mut config = Config::default();
+        assert!(config.save().is_ok());
+
+        let pins = [
+            ProfilePin::UntilNetworkChange {
+                network: NetworkFingerprint {
+                    ssid: Some("HomeWiFi".to_string()),
+                    gateway_mac: Some("00:11:22:33:44:55".to_string()),
+                    ..Default::default()
+                },
+            },
+            ProfilePin::UntilTime {
+                until: 1_736_182_800,
+            },
+            ProfilePin::Indefinite,
+        ];
+        for pin in pins {
+            config.profile_pin = Some(pin.clone());
+            assert!(config.save_active_profile().is_ok());
+            let loaded = Config::load().unwrap();
+            assert_eq!(loaded.profile_pin, Some(pin));
+        }
+
+        // State files written before pins existed still load
+        let state_path = temp_dir.path().join("kbd-backlight/state.toml");
+        fs::write(&state_path, "active_profile = \"home\"\n").unwrap();
+        assert_eq!(Config::load().unwrap().profile_pin, None);
+        assert_eq!(Config::load().unwrap().manual_override, None);
+    }
+
+    #[test]
+    fn test_manual_override_persistence() {
+        let (temp_dir, _guard) = setup_test_env();
+
+        let mut config = Config::default();
+        assert!(config.save().is_ok());
+
+        let expiries = [
+            OverrideExpiry::Indefinite,
+            OverrideExpiry::UntilTime {
+                until: 1_736_182_800,
+            },
+            OverrideExpiry::UntilNextSchedule {
+                since: 1_736_179_200,
+            },
+            OverrideExpiry::UntilIdle,
+        ];
+        for expiry in expiries {
+            let manual = ManualOverride {
+                brightness: 2,
+                expiry,
+            };
+            config.manual_override = Some(manual);
+            assert!(config.save_active_profile().is_ok());
+            assert_eq!(Config::load().unwrap().manual_override, Some(manual));
+        }
+
+        let state_path = temp_dir.path().join("kbd-backlight/state.toml");
+        let state = fs::read_to_string(&state_path).unwrap();
+        assert!(state.contains("[manual_override]"), "{}", state);
+        assert!(state.contains("mode = \"until-idle\""), "{}", state);
+
+        config.manual_override = None;
+        assert!(config.save_active_profile().is_ok());
+        assert_eq!(Config::load().unwrap().manual_override, None);
+    }
+
+    #[test]
+    fn test_profile_pin_release() {
+        let home = NetworkFingerprint {
+            ssid: Some("HomeWiFi".to_string()),
+            bssid: Some("aa:bb:cc:dd:ee:01".to_string()),
+            ..Default::default()
+        };
+        let pin = ProfilePin::UntilNetworkChange {
+            network: home.clone(),
+        };
+        assert!(pin.holds_on(&home));
+
+        // Roaming to another access point is the same network
+        let roamed = NetworkFingerprint {
+            bssid: Some("aa:bb:cc:dd:ee:02".to_string()),
+            ..home.clone()
+        };
+        assert!(pin.holds_on(&roamed));
+        assert!(!pin.holds_on(&NetworkFingerprint::default()));
+        assert!(!pin.is_expired(Local::now()));
+
+        let pin = ProfilePin::UntilTime { until: 1_000 };
+        assert!(pin.holds_on(&NetworkFingerprint::default()));
+        assert!(pin.is_expired(Local.timestamp_opt(1_000, 0).unwrap()));
+        assert!(!pin.is_expired(Local.timestamp_opt(999, 0).unwrap()));
+
+        assert!(!ProfilePin::Indefinite.is_expired(Local::now()));
+        assert!(ProfilePin::Indefinite.holds_on(&NetworkFingerprint::default()));
+    }
+
+    #[test]
+    fn test_media_player_filter() {
+        let default = MediaPlayerFilter::default();
+        assert!(default.allows("org.mpris.MediaPlayer2.mpv", None));
+
+        let filter = MediaPlayerFilter {
+            include: vec![],
+            exclude: vec!["firefox".to_string(), "Chromium".to_string()],
+        };
+        assert!(!filter.allows(
+            "org.mpris.MediaPlayer2.firefox.instance_1_42",
+            Some("Mozilla Firefox")
+        ));
+        assert!(!filter.allows(
+            "org.mpris.MediaPlayer2.chromium.instance7",
+            Some("Chromium")
+        ));
+        assert!(filter.allows("org.mpris.MediaPlayer2.mpv", Some("mpv Media Player")));
+        // Partial names must not match
+        assert!(filter.allows("org.mpris.MediaPlayer2.firefoxish", None));
+
+        // Patterns only match the player name, not trailing bus name components
+        let filter = MediaPlayerFilter {
+            include: vec![],
+            exclude: vec!["instance_1".to_string(), "spotify".to_string()],
+        };
+        assert!(filter.allows("org.mpris.MediaPlayer2.firefox.instance_1", None));
+        assert!(filter.allows("com.example.spotify", None));
+        assert!(!filter.allows("org.mpris.MediaPlayer2.spotify", None));
+
+        let filter = MediaPlayerFilter {
+            include: vec!["mpv".to_string(), "vlc media player".to_string()],
+            exclude: vec![],
+        };
+        assert!(filter.allows("org.mpris.MediaPlayer2.mpv", None));
+        assert!(filter.allows("org.mpris.MediaPlayer2.vlc", Some("VLC media player")));
+        assert!(!filter.allows("org.mpris.MediaPlayer2.spotify", Some("Spotify")));
+    }
+
+    #[test]
+    fn test_media_player_filter_parsing() {
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "home"
+            idle_timeout = 30
+            time_schedules = []
+
+            [media_players]
+            exclude = ["firefox"]
+            "#,
+        )
+        .unwrap();
+        assert!(profile.media_players.include.is_empty());
+        assert_eq!(profile.media_players.exclude, vec!["firefox".to_string()]);
+
+        // Profiles without the section keep honouring every player
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "home"
+            idle_timeout = 30
+            time_schedules = []
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profile.media_players, MediaPlayerFilter::default());
+    }
+
+    #[test]
+    fn test_suppression_policy_defaults() {
+        let mut profile = LocationProfile::default();
+        assert_eq!(profile.suppression_policy(), SuppressionPolicy::Video);
+
+        profile.video_detection_enabled = false;
+        assert_eq!(profile.suppression_policy(), SuppressionPolicy::Fullscreen);
+
+        profile.suppression_policy = Some(SuppressionPolicy::Either);
+        assert_eq!(profile.suppression_policy(), SuppressionPolicy::Either);
+    }
+
+    #[test]
+    fn test_suppression_policy_parsing() {
+        for (value, expected) in [
+            ("video", SuppressionPolicy::Video),
+            ("fullscreen", SuppressionPolicy::Fullscreen),
+            ("either", SuppressionPolicy::Either),
+            ("both", SuppressionPolicy::Either),
+            (
+                "fullscreen-and-video",
+                SuppressionPolicy::FullscreenAndVideo,
+            ),
+        ] {
+            let profile: LocationProfile = toml::from_str(&format!(
+                "name = \"home\"\nidle_timeout = 30\ntime_schedules = []\nsuppression_policy = \"{}\"",
+                value
+            ))
+            .unwrap();
+            assert_eq!(profile.suppression_policy, Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_suppression_policy_signals() {
+        let cases = [
+            (SuppressionPolicy::Video, [false, true, false, true]),
+            (SuppressionPolicy::Fullscreen, [false, false, true, true]),
+            (SuppressionPolicy::Either, [false, true, true, true]),
+            (
+                SuppressionPolicy::FullscreenAndVideo,
+                [false, false, false, true],
+            ),
+        ];
+        for (policy, expected) in cases {
+            // (video, fullscreen) in order: (f,f), (t,f), (f,t), (t,t)
+            assert_eq!(policy.suppresses(false, false), expected[0]);
+            assert_eq!(policy.suppresses(true, false), expected[1]);
+            assert_eq!(policy.suppresses(false, true), expected[2]);
+            assert_eq!(policy.suppresses(true, true), expected[3]);
+        }
+    }
+
+    #[test]
+    fn test_meeting_rule_parsing() {
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "office"
+            idle_timeout = 15
+            time_schedules = []
+
+            [meeting]
+            brightness = 1
+            detect_screen_sharing = false
+            "#,
+        )
+        .unwrap();
+        let meeting = profile.meeting.unwrap();
+        assert!(meeting.enabled);
+        assert_eq!(meeting.brightness, BrightnessValue::Level(1));
+        assert!(meeting.suppress_idle);
+        assert!(meeting.detect_webcam);
+        assert!(!meeting.detect_screen_sharing);
+
+        let meeting: MeetingRule = toml::from_str("brightness = \"50%\"").unwrap();
+        assert_eq!(meeting.brightness, BrightnessValue::Percent(50));
+    }
+
+    fn eduroam_config() -> Config {
+        let mut config = Config::default();
+        config.profiles.insert(
+            "office".to_string(),
+            LocationProfile {
+                name: "office".to_string(),
+                wifi_networks: vec!["eduroam".parse().unwrap()],
+                locations: vec![LocationMatcher {
+                    ssid: Some("eduroam".parse().unwrap()),
+                    gateway_mac: Some("00:11:22:33:44:55".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        );
+        config.profiles.insert(
+            "home".to_string(),
+            LocationProfile {
+                name: "home".to_string(),
+                locations: vec![
+                    LocationMatcher {
+                        ssid: Some("eduroam".parse().unwrap()),
+                        bssid: Some("AA:BB:CC:DD:EE:FF".to_string()),
+                        ..Default::default()
+                    },
+                    LocationMatcher {
+                        wired_interface: Some("enp0s31f6".to_string()),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            },
+        );
+        config
+    }
+
+    #[test]
+    fn test_location_matcher_score() {
+        let matcher = LocationMatcher {
+            ssid: Some("eduroam".parse().unwrap()),
+            bssid: Some("AA:BB:CC:DD:EE:FF".to_string()),
+            ..Default::default()
+        };
+        let mut fingerprint = NetworkFingerprint {
+            ssid: Some("eduroam".to_string()),
+            bssid: Some("aa:bb:cc:dd:ee:ff".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(matcher.score(&fingerprint), Some(34));
+
+        // Every configured property must match
+        fingerprint.bssid = Some("aa:bb:cc:dd:ee:00".to_string());
+        assert_eq!(matcher.score(&fingerprint), None);
+
+        // Connections match by name or UUID
+        let matcher = LocationMatcher {
+            connection: Some("8A2F3C4E-0000-4000-8000-000000000001".to_string()),
+            ..Default::default()
+        };
+        fingerprint.connections = vec![crate::location::ActiveNetworkConnection {
+            id: "Office LAN".to_string(),
+            uuid: "8a2f3c4e-0000-4000-8000-000000000001".to_string(),
+        }];
+        assert_eq!(matcher.score(&fingerprint), Some(8));
+
+        assert_eq!(LocationMatcher::default().score(&fingerprint), None);
+    }
+
+    #[test]
+    fn test_resolve_location_shared_ssid() {
+        let config = eduroam_config();
+        assert!(config.validate().is_ok());
+
+        // The gateway identifies the office
+        let office = NetworkFingerprint {
+            ssid: Some("eduroam".to_string()),
+            gateway_mac: Some("00:11:22:33:44:55".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.resolve_location(&office), Some("office"));
+
+        // The access point identifies home
+        let home = NetworkFingerprint {
+            ssid: Some("eduroam".to_string()),
+            bssid: Some("aa:bb:cc:dd:ee:ff".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.resolve_location(&home), Some("home"));
+
+        // Only the plain SSID matches
+        let elsewhere = NetworkFingerprint {
+            ssid: Some("eduroam".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.resolve_location(&elsewhere), Some("office"));
+
+        // Docking station without WiFi
+        let docked = NetworkFingerprint {
+            wired_interfaces: vec!["enp0s31f6".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(config.resolve_location(&docked), Some("home"));
+
+        assert_eq!(
+            config.resolve_location(&NetworkFingerprint::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_location_tie_prefers_active_profile() {
+        let mut config = eduroam_config();
+        config.profiles.get_mut("office").unwrap().locations = vec![LocationMatcher {
+            wired_interface: Some("eth1".to_string()),
+            ..Default::default()
+        }];
+        assert!(config.validate().is_ok());
+
+        // Both wired matchers score the same
+        let fingerprint = NetworkFingerprint {
+            wired_interfaces: vec!["enp0s31f6".to_string(), "eth1".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(config.resolve_location(&fingerprint), Some("home"));
+
+        config.active_profile = "office".to_string();
+        assert_eq!(config.resolve_location(&fingerprint), Some("office"));
+    }
+
+    #[test]
+    fn test_validation_duplicate_location() {
+        let mut config = eduroam_config();
+        config
+            .profiles
+            .get_mut("home")
+            .unwrap()
+            .wifi_networks
+            .push("eduroam".parse().unwrap());
+        assert!(config.validate().is_err());
+
+        // MAC case does not make matchers distinct
+        let mut config = eduroam_config();
+        config
+            .profiles
+            .get_mut("office")
+            .unwrap()
+            .locations
+            .push(LocationMatcher {
+                ssid: Some("eduroam".parse().unwrap()),
+                bssid: Some("aa:bb:cc:dd:ee:ff".to_string()),
+                ..Default::default()
+            });
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validation_invalid_location() {
+        let mut config = eduroam_config();
+        config.profiles.get_mut("home").unwrap().locations[0].bssid = Some("aa:bb:cc".to_string());
+        assert!(config.validate().is_err());
+
+        let mut config = eduroam_config();
+        config
+            .profiles
+            .get_mut("home")
+            .unwrap()
+            .locations
+            .push(LocationMatcher::default());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_location_matcher_parsing() {
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "office"
+            idle_timeout = 15
+            time_schedules = []
+
+            [[locations]]
+            ssid = "eduroam"
+            gateway_mac = "00:11:22:33:44:55"
+
+            [[locations]]
+            connection = "Office LAN"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profile.locations.len(), 2);
+        assert_eq!(
+            profile.locations[1].connection.as_deref(),
+            Some("Office LAN")
+        );
+        assert_eq!(profile.location_matchers().count(), 2);
+    }
+
+    #[test]
+    fn test_ssid_patterns() {
+        let glob = SsidPattern::parse("Starbucks*").unwrap();
+        assert!(glob.matches("Starbucks"));
+        assert!(glob.matches("Starbucks Guest WiFi"));
+        assert!(!glob.matches("My Starbucks"));
+
+        let single = SsidPattern::parse("Cafe-??").unwrap();
+        assert!(single.matches("Cafe-5G"));
+        assert!(!single.matches("Cafe-5GHz"));
+
+        // Regex metacharacters in globs are literal
+        assert!(SsidPattern::parse("a.b*").unwrap().matches("a.bc"));
+        assert!(!SsidPattern::parse("a.b*").unwrap().matches("axbc"));
+
+        let regex = SsidPattern::parse("/^eduroam(-5G)?$/").unwrap();
+        assert!(regex.matches("eduroam-5G"));
+        assert!(!regex.matches("eduroam-guest"));
+
+        assert!(matches!(
+            SsidPattern::parse("Home").unwrap(),
+            SsidPattern::Exact(_)
+        ));
+        assert!(SsidPattern::parse("/(unclosed/").is_err());
+    }
+
+    #[test]
+    fn test_exact_ssid_beats_pattern() {
+        let mut config = eduroam_config();
+        config
+            .profiles
+            .get_mut("home")
+            .unwrap()
+            .wifi_networks
+            .push("Starbucks*".parse().unwrap());
+        config
+            .profiles
+            .get_mut("office")
+            .unwrap()
+            .wifi_networks
+            .push("Starbucks Reserve".parse().unwrap());
+        assert!(config.validate().is_ok());
+
+        let fingerprint = |ssid: &str| NetworkFingerprint {
+            ssid: Some(ssid.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.resolve_location(&fingerprint("Starbucks Guest")),
+            Some("home")
+        );
+        assert_eq!(
+            config.resolve_location(&fingerprint("Starbucks Reserve")),
+            Some("office")
+        );
+    }
+
+    #[test]
+    fn test_fallback_and_offline_profiles() {
+        let mut config = eduroam_config();
+        let cafe = NetworkFingerprint {
+            ssid: Some("Corner Cafe".to_string()),
+            ..Default::default()
+        };
+        let offline = NetworkFingerprint::default();
+
+        // Without fallbacks the current profile is kept
+        assert_eq!(config.resolve_location(&cafe), None);
+        assert_eq!(config.resolve_location(&offline), None);
+
+        config.profiles.insert(
+            "mobile".to_string(),
+            LocationProfile {
+                name: "mobile".to_string(),
+                ..Default::default()
+            },
+        );
+        config.fallback_profile = Some("mobile".to_string());
+        assert!(config.validate().is_ok());
+        assert_eq!(config.resolve_location(&cafe), Some("mobile"));
+        assert_eq!(config.resolve_location(&offline), Some("mobile"));
 
-        Ok(())
+        config.offline_profile = Some("home".to_string());
+        assert_eq!(config.resolve_location(&cafe), Some("mobile"));
+        assert_eq!(config.resolve_location(&offline), Some("home"));
+
+        // A matching wired location still wins over the offline profile
+        let docked = NetworkFingerprint {
+            wired_interfaces: vec!["enp0s31f6".to_string()],
+            ..Default::default()
+        };
+        config.offline_profile = Some("office".to_string());
+        assert_eq!(config.resolve_location(&docked), Some("home"));
     }
 
-    /// Get the configuration directory using XDG config directory
-    pub fn get_config_dir() -> PathBuf {
-        // Use XDG_CONFIG_HOME if set, otherwise use ~/.config
-        let config_dir = std::env::var("XDG_CONFIG_HOME")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| {
-                let home = std::env::var("HOME").expect("HOME environment variable not set");
-                PathBuf::from(home).join(".config")
-            });
+    #[test]
+    fn test_validation_fallback_profiles() {
+        let mut config = eduroam_config();
+        config.fallback_profile = Some("missing".to_string());
+        assert!(config.validate().is_err());
+
+        let mut config = eduroam_config();
+        config.offline_profile = Some("missing".to_string());
+        assert!(config.validate().is_err());
+
+        // Invalid SSID patterns are rejected when the profile is parsed
+        assert!("/[invalid/".parse::<SsidEntry>().is_err());
+        assert!(toml::from_str::<LocationProfile>(
+            r#"
+            name = "cafe"
+            idle_timeout = 30
+            time_schedules = []
+            wifi_networks = ["/[invalid/"]
+            "#
+        )
+        .is_err());
+    }
 
-        config_dir.join("kbd-backlight")
+    #[test]
+    fn test_fallback_profiles_parsing() {
+        let config: Config = toml::from_str(
+            r#"
+            auto_switch_location = true
+            fallback_profile = "mobile"
+            offline_profile = "home"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.fallback_profile.as_deref(), Some("mobile"));
+        assert_eq!(config.offline_profile.as_deref(), Some("home"));
+
+        let config: Config = toml::from_str("auto_switch_location = true").unwrap();
+        assert_eq!(config.fallback_profile, None);
+        assert_eq!(config.offline_profile, None);
+        assert_eq!(config.location_settle_time, 5);
     }
 
-    /// Build WiFi SSID to profile name mapping from all profiles
-    pub fn build_location_mappings(&self) -> HashMap<String, String> {
-        let mut mappings = HashMap::new();
-        for (profile_name, profile) in &self.profiles {
-            for wifi in &profile.wifi_networks {
-                mappings.insert(wifi.clone(), profile_name.clone());
-            }
-        }
-        mappings
+    #[test]
+    fn test_resolve_location_source() {
+        let mut config = eduroam_config();
+        config.fallback_profile = Some("home".to_string());
+        config.offline_profile = Some("office".to_string());
+
+        let docked = NetworkFingerprint {
+            wired_interfaces: vec!["enp0s31f6".to_string()],
+            ..Default::default()
+        };
+        let cafe = NetworkFingerprint {
+            ssid: Some("Corner Cafe".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.resolve_location_source(&docked),
+            Some(("home", LocationSource::Matched))
+        );
+        assert_eq!(
+            config.resolve_location_source(&cafe),
+            Some(("home", LocationSource::Fallback))
+        );
+        assert_eq!(
+            config.resolve_location_source(&NetworkFingerprint::default()),
+            Some(("office", LocationSource::Offline))
+        );
     }
-}
 
-impl Default for Config {
-    fn default() -> Self {
-        let mut profiles = HashMap::new();
+    #[test]
+    fn test_min_dwell_time_parsing() {
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "office"
+            idle_timeout = 15
+            time_schedules = []
+            min_dwell_time = 300
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profile.min_dwell_time, 300);
+        assert_eq!(LocationProfile::default().min_dwell_time, 0);
+    }
 
-        // Create a default "home" profile
-        profiles.insert(
-            "home".to_string(),
-            LocationProfile {
-                name: "home".to_string(),
-                idle_timeout: 30,
-                video_detection_enabled: true,
-                wifi_networks: vec![],
-                ac_always_on: false,
-                time_schedules: vec![
-                    TimeSchedule {
-                        hour: 9,
-                        minute: 0,
-                        brightness: 1,
-                    },
-                    TimeSchedule {
-                        hour: 22,
-                        minute: 0,
-                        brightness: 0,
-                    },
-                ],
-            },
+    #[test]
+    fn test_battery_rules_parsing() {
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "mobile"
+            idle_timeout = 5
+            time_schedules = []
+            battery_rules = [
+                { below = 20, brightness = 0 },
+                { below = 40, max_brightness = "50%" },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile.battery_rules,
+            vec![
+                BatteryRule {
+                    below: 20,
+                    brightness: Some(BrightnessValue::Level(0)),
+                    max_brightness: None,
+                },
+                BatteryRule {
+                    below: 40,
+                    brightness: None,
+                    max_brightness: Some(BrightnessValue::Percent(50)),
+                },
+            ]
         );
 
-        Config {
-            profiles,
-            active_profile: "home".to_string(),
-            auto_switch_location: false,
-        }
+        // Round-trips through TOML
+        let serialized = toml::to_string(&profile).unwrap();
+        let reparsed: LocationProfile = toml::from_str(&serialized).unwrap();
+        assert_eq!(reparsed.battery_rules, profile.battery_rules);
+
+        let invalid = toml::from_str::<LocationProfile>(
+            r#"
+            name = "mobile"
+            idle_timeout = 5
+            time_schedules = []
+            battery_rules = [{ below = 40, max_brightness = "150%" }]
+            "#,
+        );
+        assert!(invalid.is_err());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::env;
-    use std::sync::Mutex;
-    use tempfile::TempDir;
+    #[test]
+    fn test_brightness_value_levels() {
+        assert_eq!(BrightnessValue::Percent(50).to_level(3), 1);
+        assert_eq!(BrightnessValue::Percent(100).to_level(3), 3);
+        assert_eq!(BrightnessValue::Percent(50).to_level(255), 127);
+        assert_eq!(BrightnessValue::Level(5).to_level(3), 3);
+    }
 
-    // Mutex to serialize tests that modify environment variables
-    static ENV_MUTEX: Mutex<()> = Mutex::new(());
+    #[test]
+    fn test_validation_battery_rules() {
+        let mut config = Config::default();
+        config
+            .profiles
+            .get_mut("home")
+            .unwrap()
+            .battery_rules
+            .push(BatteryRule {
+                below: 30,
+                brightness: None,
+                max_brightness: None,
+            });
+        assert!(config.validate().is_err());
 
-    fn setup_test_env() -> (TempDir, std::sync::MutexGuard<'static, ()>) {
-        let guard = ENV_MUTEX.lock().unwrap();
-        let temp_dir = TempDir::new().unwrap();
-        env::set_var("XDG_CONFIG_HOME", temp_dir.path());
-        (temp_dir, guard)
+        let rule = &mut config.profiles.get_mut("home").unwrap().battery_rules[0];
+        rule.brightness = Some(BrightnessValue::Level(0));
+        assert!(config.validate().is_ok());
+
+        let rule = &mut config.profiles.get_mut("home").unwrap().battery_rules[0];
+        rule.below = 0;
+        assert!(config.validate().is_err());
     }
 
     #[test]
-    fn test_default_config_creation() {
-        let config = Config::default();
-        assert_eq!(config.active_profile, "home");
-        assert!(config.profiles.contains_key("home"));
+    fn test_power_source_policy_parsing() {
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "office"
+            idle_timeout = 30
+            time_schedules = []
+
+            [on_ac]
+            min_brightness = 1
+
+            [on_battery]
+            idle_timeout = 10
+            max_brightness = "50%"
+            time_schedules = [{ hour = 18, minute = 0, brightness = 1 }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile.on_ac,
+            Some(PowerSourcePolicy {
+                min_brightness: Some(BrightnessValue::Level(1)),
+                ..Default::default()
+            })
+        );
+        assert_eq!(profile.idle_timeout_for(PowerState::Battery, None), 10);
+        assert_eq!(profile.brightness_cap(PowerState::Battery, 3), Some(1));
+        assert_eq!(profile.brightness_floor(PowerState::AC, 3), Some(1));
+        assert_eq!(profile.brightness_floor(PowerState::Battery, 3), None);
+        assert_eq!(profile.time_schedules_for(PowerState::Battery).len(), 1);
+        assert!(profile.time_schedules_for(PowerState::AC).is_empty());
+
+        let serialized = toml::to_string(&profile).unwrap();
+        let reparsed: LocationProfile = toml::from_str(&serialized).unwrap();
+        assert_eq!(reparsed.on_battery, profile.on_battery);
+    }
+
+    #[test]
+    fn test_validation_power_source_policies() {
+        let mut config = Config::default();
+        let home = config.profiles.get_mut("home").unwrap();
+        home.on_battery = Some(PowerSourcePolicy {
+            idle_timeout: Some(0),
+            ..Default::default()
+        });
+        assert!(config.validate().is_err());
+
+        let home = config.profiles.get_mut("home").unwrap();
+        home.on_battery = Some(PowerSourcePolicy {
+            min_brightness: Some(BrightnessValue::Percent(60)),
+            max_brightness: Some(BrightnessValue::Percent(30)),
+            ..Default::default()
+        });
+        assert!(config.validate().is_err());
+
+        let home = config.profiles.get_mut("home").unwrap();
+        home.on_battery = Some(PowerSourcePolicy {
+            time_schedules: Some(vec![TimeSchedule {
+                hour: Some(24),
+                minute: Some(0),
+                brightness: BrightnessValue::Level(1),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        });
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("on_battery schedule #1"), "{}", err);
+
+        config.profiles.get_mut("home").unwrap().on_battery = Some(PowerSourcePolicy {
+            max_brightness: Some(BrightnessValue::Level(1)),
+            ..Default::default()
+        });
         assert!(config.validate().is_ok());
     }
 
     #[test]
-    fn test_config_save_and_load() {
-        let (_temp_dir, _guard) = setup_test_env();
+    fn test_power_mode_overrides_parsing() {
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "mobile"
+            idle_timeout = 30
+            time_schedules = []
+
+            [power_modes.power-saver]
+            max_brightness = "50%"
+            idle_timeout = 10
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile.power_modes[&PowerMode::PowerSaver],
+            PowerModeOverride {
+                max_brightness: Some(BrightnessValue::Percent(50)),
+                idle_timeout: Some(10),
+            }
+        );
 
-        let config = Config::default();
-        assert!(config.save().is_ok());
+        let serialized = toml::to_string(&profile).unwrap();
+        let reparsed: LocationProfile = toml::from_str(&serialized).unwrap();
+        assert_eq!(reparsed.power_modes, profile.power_modes);
 
-        let loaded_config = Config::load().unwrap();
-        assert_eq!(loaded_config.active_profile, config.active_profile);
-        assert_eq!(loaded_config.profiles.len(), config.profiles.len());
+        let mut config = Config::default();
+        config.profiles.get_mut("home").unwrap().power_modes.insert(
+            PowerMode::PowerSaver,
+            PowerModeOverride {
+                idle_timeout: Some(0),
+                ..Default::default()
+            },
+        );
+        assert!(config.validate().is_err());
+
+        // Unknown modes are rejected
+        let invalid = toml::from_str::<LocationProfile>(
+            r#"
+            name = "mobile"
+            idle_timeout = 30
+            time_schedules = []
+            [power_modes.turbo]
+            max_brightness = 1
+            "#,
+        );
+        assert!(invalid.is_err());
     }
 
     #[test]
-    fn test_config_validation_invalid_profile() {
-        let config = Config {
-            active_profile: "nonexistent".to_string(),
-            ..Default::default()
-        };
+    fn test_solar_time_parsing() {
+        let parse = |text: &str| SolarTime::try_from(text.to_string());
+        assert_eq!(
+            parse("sunset-30m"),
+            Ok(SolarTime {
+                event: SolarEvent::Sunset,
+                offset_minutes: -30,
+            })
+        );
+        assert_eq!(parse("Sunrise").unwrap().offset_minutes, 0);
+        assert_eq!(parse("sunrise + 1h15m").unwrap().offset_minutes, 75);
+        assert_eq!(parse("sunset+2h").unwrap().to_string(), "sunset+2h");
+        assert_eq!(parse("sunrise-90m").unwrap().to_string(), "sunrise-1h30m");
+        for invalid in [
+            "noon",
+            "sunset30m",
+            "sunset-",
+            "sunset+13h",
+            "sunset-1x",
+            "sunset+0m",
+        ] {
+            assert!(parse(invalid).is_err(), "{} should be rejected", invalid);
+        }
 
-        let result = config.validate();
-        assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
-            .to_string()
-            .contains("not found in profiles"));
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "home"
+            idle_timeout = 30
+            time_schedules = [
+                { at = "sunset-30m", brightness = 2 },
+                { hour = 23, minute = 0, brightness = 0 },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profile.time_schedules[0].at, parse("sunset-30m").ok());
+        assert_eq!(profile.time_schedules[1].at, None);
+
+        let serialized = toml::to_string(&profile).unwrap();
+        let reparsed: LocationProfile = toml::from_str(&serialized).unwrap();
+        assert_eq!(reparsed.time_schedules, profile.time_schedules);
     }
 
     #[test]
-    fn test_config_validation_invalid_hour() {
+    fn test_validation_solar_schedules() {
         let mut config = Config::default();
-        if let Some(profile) = config.profiles.get_mut("home") {
-            profile.time_schedules.push(TimeSchedule {
-                hour: 25,
-                minute: 0,
-                brightness: 2,
+        config
+            .profiles
+            .get_mut("home")
+            .unwrap()
+            .time_schedules
+            .push(TimeSchedule {
+                brightness: BrightnessValue::Level(2),
+                at: SolarTime::try_from("sunset".to_string()).ok(),
+                ..Default::default()
             });
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("latitude"), "{}", err);
+
+        config.latitude = Some(48.1);
+        assert!(config.validate().is_err());
+        config.longitude = Some(11.6);
+        assert!(config.validate().is_ok());
+        config.latitude = Some(91.0);
+        assert!(config.validate().is_err());
+        config.latitude = Some(48.1);
+
+        // An entry needs exactly one of `at` and `hour`
+        fn schedule(config: &mut Config) -> &mut TimeSchedule {
+            config
+                .profiles
+                .get_mut("home")
+                .unwrap()
+                .time_schedules
+                .last_mut()
+                .unwrap()
         }
+        schedule(&mut config).hour = Some(20);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("not both"), "{}", err);
 
-        let result = config.validate();
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Invalid hour"));
+        schedule(&mut config).at = None;
+        assert!(config.validate().is_ok());
+
+        schedule(&mut config).hour = None;
+        schedule(&mut config).minute = Some(30);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("Set 'hour'"), "{}", err);
     }
 
     #[test]
-    fn test_config_validation_invalid_minute() {
-        let mut config = Config::default();
-        if let Some(profile) = config.profiles.get_mut("home") {
-            profile.time_schedules.push(TimeSchedule {
-                hour: 12,
-                minute: 60,
-                brightness: 2,
-            });
+    fn test_schedule_day_and_date_filters() {
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "office"
+            idle_timeout = 30
+            time_schedules = [
+                { hour = 8, minute = 0, brightness = 2, days = ["mon", "Tue", "wednesday", "weekdays"] },
+                { hour = 11, minute = 0, brightness = 1, days = ["weekends", "holidays"] },
+                { hour = 7, minute = 0, brightness = 3, from = 2024-12-20, until = "2024-12-31" },
+            ]
+            "#,
+        )
+        .unwrap();
+        let schedules = &profile.time_schedules;
+        assert_eq!(
+            schedules[0].days,
+            vec![
+                ScheduleDay::Day(Weekday::Mon),
+                ScheduleDay::Day(Weekday::Tue),
+                ScheduleDay::Day(Weekday::Wed),
+                ScheduleDay::Weekdays,
+            ]
+        );
+        let date = |text| NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap();
+        assert_eq!(schedules[2].from, Some(ScheduleDate(date("2024-12-20"))));
+        assert_eq!(schedules[2].until, Some(ScheduleDate(date("2024-12-31"))));
+
+        let serialized = toml::to_string(&profile).unwrap();
+        let reparsed: LocationProfile = toml::from_str(&serialized).unwrap();
+        assert_eq!(reparsed.time_schedules, profile.time_schedules);
+
+        let holidays = Holidays::parse("2024-12-25").unwrap();
+        // Wednesday
+        assert!(schedules[0].applies_on(date("2024-12-18"), &holidays));
+        assert!(!schedules[1].applies_on(date("2024-12-18"), &holidays));
+        // Saturday
+        assert!(!schedules[0].applies_on(date("2024-12-21"), &holidays));
+        assert!(schedules[1].applies_on(date("2024-12-21"), &holidays));
+        // Holiday on a Wednesday
+        assert!(!schedules[0].applies_on(date("2024-12-25"), &holidays));
+        assert!(schedules[1].applies_on(date("2024-12-25"), &holidays));
+        // Date range
+        assert!(!schedules[2].applies_on(date("2024-12-19"), &holidays));
+        assert!(schedules[2].applies_on(date("2024-12-25"), &holidays));
+        assert!(!schedules[2].applies_on(date("2025-01-01"), &holidays));
+
+        for invalid in [
+            r#"time_schedules = [{ brightness = 1, days = ["someday"] }]"#,
+            r#"time_schedules = [{ brightness = 1, from = "24.12.2024" }]"#,
+        ] {
+            let text = format!("name = \"office\"\nidle_timeout = 30\n{}", invalid);
+            assert!(
+                toml::from_str::<LocationProfile>(&text).is_err(),
+                "{}",
+                invalid
+            );
         }
+    }
 
-        let result = config.validate();
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Invalid minute"));
+    #[test]
+    fn test_validation_schedule_filters() {
+        let date = |text| {
+            Some(ScheduleDate(
+                NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap(),
+            ))
+        };
+        let mut config = Config::default();
+        let schedules = &mut config.profiles.get_mut("home").unwrap().time_schedules;
+        schedules[0].from = date("2025-01-06");
+        schedules[0].until = date("2024-12-20");
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("schedule #1"), "{}", err);
+
+        let schedules = &mut config.profiles.get_mut("home").unwrap().time_schedules;
+        schedules[0].until = None;
+        schedules[1].days = vec![ScheduleDay::Holidays];
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("holidays file"), "{}", err);
+
+        config.holidays = Some("holidays.ics".into());
+        assert!(config.validate().is_ok());
     }
 
     #[test]
-    fn test_toml_serialization() {
+    fn test_load_holidays_file() {
         let (_temp_dir, _guard) = setup_test_env();
-        let config = Config::default();
 
-        // Save and reload to test serialization
-        assert!(config.save().is_ok());
-        let loaded = Config::load().unwrap();
+        let config = Config {
+            holidays: Some("holidays.txt".into()),
+            ..Default::default()
+        };
+        config.save().unwrap();
+        assert!(Config::load().is_err());
 
-        assert_eq!(config.active_profile, loaded.active_profile);
-        assert_eq!(config.auto_switch_location, loaded.auto_switch_location);
+        let config_dir = Config::get_config_dir();
+        fs::write(config_dir.join("holidays.txt"), "2024-12-25\n").unwrap();
+        let loaded = Config::load().unwrap();
+        let christmas = NaiveDate::from_ymd_opt(2024, 12, 25).unwrap();
+        assert!(loaded.holiday_calendar.contains(christmas));
     }
 
     #[test]
-    fn test_multiple_profiles() {
-        let mut config = Config::default();
-
-        config.profiles.insert(
-            "office".to_string(),
-            LocationProfile {
-                name: "office".to_string(),
-                idle_timeout: 5,
-                video_detection_enabled: true,
-                wifi_networks: vec![],
-                ac_always_on: false,
-                time_schedules: vec![TimeSchedule {
-                    hour: 8,
-                    minute: 0,
-                    brightness: 3,
-                }],
-            },
+    fn test_default_brightness_warnings() {
+        let mut config: Config = toml::from_str("default_brightness = \"50%\"").unwrap();
+        assert_eq!(
+            config.default_brightness,
+            Some(BrightnessValue::Percent(50))
         );
-
+        let profile: LocationProfile = toml::from_str(
+            "name = \"dark\"\nidle_timeout = 30\ntime_schedules = []\ndefault_brightness = 0\n",
+        )
+        .unwrap();
+        assert_eq!(profile.default_brightness, Some(BrightnessValue::Level(0)));
+
+        config.profiles.insert("dark".to_string(), profile);
+        config.active_profile = "dark".to_string();
+        // The profile's own 0 wins over the global default
+        assert_eq!(config.warnings().len(), 1);
+        assert!(config.warnings()[0].contains("'dark'"));
         assert!(config.validate().is_ok());
-        assert_eq!(config.profiles.len(), 2);
+
+        let dark = config.profiles.get_mut("dark").unwrap();
+        dark.default_brightness = None;
+        assert!(config.warnings().is_empty());
+
+        config.default_brightness = None;
+        assert_eq!(config.warnings().len(), 1);
+        let dark = config.profiles.get_mut("dark").unwrap();
+        dark.on_ac = Some(PowerSourcePolicy {
+            min_brightness: Some(BrightnessValue::Level(1)),
+            ..Default::default()
+        });
+        assert!(config.warnings().is_empty());
+        let dark = config.profiles.get_mut("dark").unwrap();
+        dark.on_ac = None;
+        dark.time_schedules.push(TimeSchedule {
+            hour: Some(20),
+            brightness: BrightnessValue::Level(1),
+            ..Default::default()
+        });
+        assert!(config.warnings().is_empty());
+
+        assert!(Config::default().warnings().is_empty());
     }
 
     #[test]
-    fn test_profile_independence() {
-        let mut config = Config::default();
-
-        // Add a second profile with different settings
-        config.profiles.insert(
-            "office".to_string(),
-            LocationProfile {
-                name: "office".to_string(),
-                idle_timeout: 5,
-                video_detection_enabled: true,
-                wifi_networks: vec![],
-                ac_always_on: false,
-                time_schedules: vec![TimeSchedule {
-                    hour: 8,
-                    minute: 0,
-                    brightness: 3,
-                }],
-            },
+    fn test_rule_order_parsing() {
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "night"
+            idle_timeout = 30
+            time_schedules = []
+            rule_order = ["idle", "manual"]
+
+            [enabled_rules]
+            media = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile.rule_order(),
+            vec![
+                RuleLayer::Idle,
+                RuleLayer::Manual,
+                RuleLayer::Custom,
+                RuleLayer::Meeting,
+                RuleLayer::Schedule,
+                RuleLayer::Default,
+            ]
+        );
+        assert!(!profile.is_rule_enabled(RuleLayer::Media));
+        assert!(profile.is_rule_enabled(RuleLayer::Idle));
+        assert_eq!(
+            LocationProfile::default().rule_order(),
+            DEFAULT_RULE_ORDER.to_vec()
         );
 
-        // Verify home profile is unchanged
-        let home = config.profiles.get("home").unwrap();
-        assert_eq!(home.idle_timeout, 30);
-        assert_eq!(home.time_schedules.len(), 2);
+        let serialized = toml::to_string(&profile).unwrap();
+        let reparsed: LocationProfile = toml::from_str(&serialized).unwrap();
+        assert_eq!(reparsed.rule_order, profile.rule_order);
+        assert_eq!(reparsed.enabled_rules, profile.enabled_rules);
 
-        // Verify office profile has its own settings
-        let office = config.profiles.get("office").unwrap();
-        assert_eq!(office.idle_timeout, 5);
-        assert_eq!(office.time_schedules.len(), 1);
-        assert_eq!(office.time_schedules[0].brightness, 3);
+        assert!(toml::from_str::<LocationProfile>(
+            "name = \"x\"\nidle_timeout = 30\ntime_schedules = []\nrule_order = [\"video\"]\n"
+        )
+        .is_err());
     }
 
     #[test]
-    fn test_profile_switching() {
+    fn test_validation_rule_order() {
         let mut config = Config::default();
+        let home = config.profiles.get_mut("home").unwrap();
+        home.rule_order = vec![RuleLayer::Idle, RuleLayer::Manual, RuleLayer::Idle];
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("'idle' is listed twice"), "{}", err);
 
-        // Add office profile
-        config.profiles.insert(
-            "office".to_string(),
-            LocationProfile {
-                name: "office".to_string(),
-                idle_timeout: 5,
-                video_detection_enabled: true,
-                wifi_networks: vec![],
-                ac_always_on: false,
-                time_schedules: vec![],
-            },
-        );
-
-        // Initially on home profile
-        assert_eq!(config.active_profile, "home");
-
-        // Switch to office profile
-        config.active_profile = "office".to_string();
-        assert_eq!(config.active_profile, "office");
+        let home = config.profiles.get_mut("home").unwrap();
+        home.rule_order.pop();
         assert!(config.validate().is_ok());
+        assert!(config.warnings().is_empty());
 
-        // Verify we can access the active profile's settings
-        let active = config.profiles.get(&config.active_profile).unwrap();
-        assert_eq!(active.idle_timeout, 5);
+        // Without schedules the profile can't turn the backlight on anymore
+        let home = config.profiles.get_mut("home").unwrap();
+        home.enabled_rules.insert(RuleLayer::Schedule, false);
+        assert_eq!(config.warnings().len(), 1);
     }
 
     #[test]
-    fn test_profile_persistence() {
-        let (_temp_dir, _guard) = setup_test_env();
-
-        let mut config = Config::default();
-
-        // Add office profile
-        config.profiles.insert(
-            "office".to_string(),
-            LocationProfile {
-                name: "office".to_string(),
-                idle_timeout: 5,
-                video_detection_enabled: true,
-                wifi_networks: vec![],
-                ac_always_on: false,
-                time_schedules: vec![],
-            },
+    fn test_custom_rules_parsing_and_validation() {
+        let profile: LocationProfile = toml::from_str(
+            r#"
+            name = "mobile"
+            idle_timeout = 30
+            time_schedules = []
+
+            [[custom_rules]]
+            when = "power == 'battery' && battery < 30 && hour >= 22"
+            then = { brightness = 0 }
+
+            [[custom_rules]]
+            when = "ssid == 'Library'"
+            then = { brightness = "50%" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profile.custom_rules.len(), 2);
+        assert_eq!(
+            profile.custom_rules[1].then.brightness,
+            BrightnessValue::Percent(50)
         );
+        assert!(profile.uses_variable(Variable::Ssid));
+        assert!(!profile.uses_variable(Variable::AppId));
+        assert!(profile.can_turn_on(None));
 
-        // Switch to office profile
-        config.active_profile = "office".to_string();
+        let serialized = toml::to_string(&profile).unwrap();
+        let reparsed: LocationProfile = toml::from_str(&serialized).unwrap();
+        assert_eq!(reparsed.custom_rules, profile.custom_rules);
 
-        // Save everything (profiles and state)
-        assert!(config.save().is_ok());
+        let mut config = Config::default();
+        config.profiles.insert("mobile".to_string(), profile);
+        assert!(config.validate().is_ok());
 
-        // Load config and verify active profile persisted
-        let loaded = Config::load().unwrap();
-        assert_eq!(loaded.active_profile, "office");
-        assert_eq!(loaded.profiles.len(), 2);
+        // Invalid conditions load, but fail validation with their position
+        let mobile = config.profiles.get_mut("mobile").unwrap();
+        mobile.custom_rules[1].when =
+            Condition::from("ssid == 'Library' && battery < low".to_string());
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("Profile 'mobile', custom rule #2"), "{}", err);
+        assert!(err.contains("column 32: unknown variable 'low'"), "{}", err);
+        assert!(
+            err.contains("ssid == 'Library' && battery < low"),
+            "{}",
+            err
+        );
     }
 }
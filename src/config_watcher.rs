@@ -0,0 +1,222 @@
+// Configuration file watcher
+// Uses inotify on the config and profiles directories to trigger hot reloads
+use crate::{Error, Result};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+/// Quiet period after the last change before a reload, so editors can finish saving
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Inotify instance usable with tokio's AsyncFd
+struct InotifyFd(Inotify);
+
+impl AsRawFd for InotifyFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_fd().as_raw_fd()
+    }
+}
+
+/// Watch descriptors and which files under them matter
+struct Watches {
+    config_dir: WatchDescriptor,
+    profiles_dir: WatchDescriptor,
+}
+
+impl Watches {
+    /// `config.toml` and `profiles/*.toml` are reloaded; `state.toml` is written by
+    /// the daemon itself and editor temp files are ignored
+    fn is_relevant(&self, event: &InotifyEvent) -> bool {
+        let Some(name) = event.name.as_ref().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        if name.starts_with('.') {
+            return false;
+        }
+        if event.wd == self.config_dir {
+            name == "config.toml"
+        } else if event.wd == self.profiles_dir {
+            name.ends_with(".toml")
+        } else {
+            false
+        }
+    }
+}
+
+pub struct ConfigWatcher {
+    changed: Arc<Notify>,
+    watcher: JoinHandle<()>,
+}
+
+impl ConfigWatcher {
+    /// Watch `config.toml` and `profiles/` in the given directory
+    /// A change is reported once no further changes arrived for `debounce`
+    pub fn new(config_dir: &Path, debounce: Duration) -> Result<Self> {
+        let unavailable = |e: nix::Error| {
+            Error::monitor_unavailable(
+                "ConfigWatcher",
+                format!("inotify failed: {}", e),
+                "Configuration changes need `kbd-backlight reload` or SIGHUP",
+            )
+        };
+        let flags = AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_MOVED_TO
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_DELETE;
+
+        let inotify =
+            Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC).map_err(unavailable)?;
+        let watches = Watches {
+            config_dir: inotify.add_watch(config_dir, flags).map_err(unavailable)?,
+            profiles_dir: inotify
+                .add_watch(&config_dir.join("profiles"), flags)
+                .map_err(unavailable)?,
+        };
+        let fd = AsyncFd::new(InotifyFd(inotify)).map_err(Error::Io)?;
+
+        let changed = Arc::new(Notify::new());
+        let notify = Arc::clone(&changed);
+        let watcher = tokio::spawn(async move {
+            loop {
+                if let Err(e) = next_change(&fd, &watches).await {
+                    eprintln!("Config watcher stopped: {}", e);
+                    break;
+                }
+                // Wait for the burst of writes to settle
+                while let Ok(result) =
+                    tokio::time::timeout(debounce, next_change(&fd, &watches)).await
+                {
+                    if let Err(e) = result {
+                        eprintln!("Config watcher stopped: {}", e);
+                        return;
+                    }
+                }
+                notify.notify_one();
+            }
+        });
+
+        Ok(Self { changed, watcher })
+    }
+
+    /// Wait until the configuration files changed
+    pub async fn wait_for_change(&self) {
+        self.changed.notified().await;
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.watcher.abort();
+    }
+}
+
+/// Wait for an inotify event on a relevant file
+async fn next_change(fd: &AsyncFd<InotifyFd>, watches: &Watches) -> io::Result<()> {
+    loop {
+        let mut guard = fd.readable().await?;
+        match guard.try_io(|fd| fd.get_ref().0.read_events().map_err(io::Error::from)) {
+            Ok(Ok(events)) => {
+                if events.iter().any(|event| watches.is_relevant(event)) {
+                    return Ok(());
+                }
+            }
+            Ok(Err(e)) => return Err(e),
+            Err(_would_block) => continue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::fs;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_config_watcher() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("profiles")).unwrap();
+        let watcher = ConfigWatcher::new(dir.path(), Duration::from_millis(50)).unwrap();
+
+        // Several writes are reported once
+        fs::write(dir.path().join("profiles/home.toml"), "name = \"home\"").unwrap();
+        fs::write(dir.path().join("config.toml"), "active_profile = \"home\"").unwrap();
+        timeout(Duration::from_secs(5), watcher.wait_for_change())
+            .await
+            .expect("No change reported");
+        assert!(
+            timeout(Duration::from_millis(200), watcher.wait_for_change())
+                .await
+                .is_err()
+        );
+
+        // The daemon's own state file and temp files are ignored
+        fs::write(dir.path().join("state.toml"), "active_profile = \"home\"").unwrap();
+        fs::write(dir.path().join(".config.toml.tmp"), "").unwrap();
+        assert!(
+            timeout(Duration::from_millis(200), watcher.wait_for_change())
+                .await
+                .is_err()
+        );
+
+        // Atomic saves (write to temp file, rename over) are picked up
+        fs::write(dir.path().join(".config.toml.tmp"), "").unwrap();
+        fs::rename(
+            dir.path().join(".config.toml.tmp"),
+            dir.path().join("config.toml"),
+        )
+        .unwrap();
+        timeout(Duration::from_secs(5), watcher.wait_for_change())
+            .await
+            .expect("No change reported for rename");
+    }
+
+    #[tokio::test]
+    async fn test_deleted_config_is_not_recreated() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let profile = "name = \"home\"\nidle_timeout = 30\ntime_schedules = []\n";
+        fs::create_dir(dir.path().join("profiles")).unwrap();
+        fs::write(dir.path().join("profiles/home.toml"), profile).unwrap();
+        fs::write(
+            dir.path().join("config.toml"),
+            "auto_switch_location = false\n",
+        )
+        .unwrap();
+        assert!(Config::load_from(dir.path()).is_ok());
+
+        let watcher = ConfigWatcher::new(dir.path(), Duration::from_millis(50)).unwrap();
+        fs::remove_file(dir.path().join("config.toml")).unwrap();
+        timeout(Duration::from_secs(5), watcher.wait_for_change())
+            .await
+            .expect("No change reported for deletion");
+
+        // Reloading fails and leaves every file alone, so nothing triggers another reload
+        assert!(Config::load_from(dir.path()).is_err());
+        assert!(!dir.path().join("config.toml").exists());
+        assert!(!dir.path().join("state.toml").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("profiles/home.toml")).unwrap(),
+            profile
+        );
+        assert!(
+            timeout(Duration::from_millis(200), watcher.wait_for_change())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_config_watcher_missing_dir() {
+        assert!(
+            ConfigWatcher::new(Path::new("/nonexistent/kbd-backlight"), DEFAULT_DEBOUNCE).is_err()
+        );
+    }
+}
@@ -4,20 +4,32 @@
 use chrono::Local;
 use kbd_backlight::{
     brightness::BrightnessController,
-    config::Config,
-    ipc::{IpcMessage, IpcResponse, IpcServer, StatusInfo, DEFAULT_SOCKET_PATH},
-    location::LocationDetector,
+    config::{Config, ManualOverride, OverrideExpiry, ProfilePin},
+    config_watcher::{ConfigWatcher, DEFAULT_DEBOUNCE},
+    ipc::{
+        IpcMessage, IpcResponse, IpcServer, LocationChange, MediaPlayerStatus, OverrideMode,
+        PinMode, StatusInfo, DEFAULT_SOCKET_PATH,
+    },
+    location::{LocationDebouncer, LocationDetector, ProfileDwell},
+    meeting::MeetingDetector,
     monitors::{FullscreenMonitor, IdleMonitor},
-    power::{PowerDetector, PowerState},
-    rules::{RuleEngine, SystemContext},
+    power::{PowerDetector, PowerMode, PowerProfilesMonitor, PowerState},
+    rules::{RuleEngine, SystemContext, Variable},
+    sleep::{SleepEvent, SleepMonitor},
     video_detector::VideoDetector,
     Result,
 };
+use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
+use std::time::Instant;
 use tokio::signal;
+use tokio::signal::unix::{Signal, SignalKind};
 use tokio::time::{interval, Duration};
 
+/// Number of automatic profile switches kept for `kbd-backlight history`
+const LOCATION_HISTORY_LEN: usize = 50;
+
 /// Main daemon struct that orchestrates all components
 struct Daemon {
     brightness_controller: BrightnessController,
@@ -25,13 +37,26 @@ struct Daemon {
     current_idle_timeout: Arc<RwLock<u64>>,
     fullscreen_monitor: Option<Arc<FullscreenMonitor>>,
     video_detector: Option<VideoDetector>,
+    meeting_detector: MeetingDetector,
     location_detector: LocationDetector,
     power_detector: PowerDetector,
+    power_profiles: Option<PowerProfilesMonitor>,
+    sleep_monitor: Option<SleepMonitor>,
+    config_watcher: Option<ConfigWatcher>,
+    /// Error of the last failed configuration reload, cleared by a successful one
+    config_error: Option<String>,
+    /// Between logind's PrepareForSleep(true) and the resume
+    sleeping: bool,
     rule_engine: Arc<RwLock<RuleEngine>>,
     config: Arc<RwLock<Config>>,
     ipc_server: IpcServer,
     current_brightness: Arc<RwLock<u32>>,
-    last_ssid: Arc<RwLock<Option<String>>>,
+    max_brightness: u32,
+    location_debouncer: LocationDebouncer,
+    location_history: VecDeque<LocationChange>,
+    /// How long the active profile has been in use
+    profile_dwell: ProfileDwell,
+    media_players: Arc<RwLock<Vec<MediaPlayerStatus>>>,
 }
 
 impl Daemon {
@@ -45,6 +70,9 @@ impl Daemon {
             }
             e
         })?;
+        for warning in config.warnings() {
+            eprintln!("Warning: {}", warning);
+        }
         let config = Arc::new(RwLock::new(config));
 
         // Initialize brightness controller
@@ -54,8 +82,9 @@ impl Daemon {
             e
         })?;
 
-        // Get initial brightness
+        // Get initial and maximum brightness
         let current_brightness = brightness_controller.get_brightness()?;
+        let max_brightness = brightness_controller.get_max_brightness()?;
         let current_brightness = Arc::new(RwLock::new(current_brightness));
 
         // Initialize idle monitor with timeout from active profile
@@ -97,13 +126,61 @@ impl Daemon {
             }
         };
 
-        // Initialize location detector
-        let location_detector = LocationDetector::new();
-        println!("Location detector initialized");
+        // Initialize meeting detector
+        let meeting_detector = MeetingDetector::new().await;
+        println!("Meeting detector initialized");
+
+        // Initialize location detector (NetworkManager over D-Bus, CLI tools as fallback)
+        let location_detector = LocationDetector::with_network_manager().await;
+        if location_detector.is_event_driven() {
+            println!("Location detector initialized (NetworkManager)");
+        } else {
+            println!("Location detector initialized (polling)");
+        }
+
+        // Initialize power detector (UPower over D-Bus, sysfs as fallback)
+        let power_detector = PowerDetector::with_upower().await;
+        if power_detector.is_event_driven() {
+            println!("Power detector initialized (UPower)");
+        } else {
+            println!("Power detector initialized (sysfs)");
+        }
 
-        // Initialize power detector
-        let power_detector = PowerDetector::new();
-        println!("Power detector initialized");
+        // Initialize power-profiles-daemon monitor
+        let power_profiles = match PowerProfilesMonitor::with_system_bus().await {
+            Ok(monitor) => {
+                println!("Power profiles monitor initialized");
+                Some(monitor)
+            }
+            Err(e) => {
+                eprintln!("Warning: {}", e);
+                None
+            }
+        };
+
+        // Initialize suspend/resume monitor
+        let sleep_monitor = match SleepMonitor::with_system_bus().await {
+            Ok(monitor) => {
+                println!("Sleep monitor initialized");
+                Some(monitor)
+            }
+            Err(e) => {
+                eprintln!("Warning: {}", e);
+                None
+            }
+        };
+
+        // Watch the configuration files for hot reload
+        let config_watcher = match ConfigWatcher::new(&Config::get_config_dir(), DEFAULT_DEBOUNCE) {
+            Ok(watcher) => {
+                println!("Config watcher initialized");
+                Some(watcher)
+            }
+            Err(e) => {
+                eprintln!("Warning: {}", e);
+                None
+            }
+        };
 
         // Initialize rule engine
         let rule_engine = Arc::new(RwLock::new(RuleEngine::new(Arc::clone(&config))));
@@ -120,13 +197,23 @@ impl Daemon {
             current_idle_timeout,
             fullscreen_monitor,
             video_detector,
+            meeting_detector,
             location_detector,
             power_detector,
+            power_profiles,
+            sleep_monitor,
+            sleeping: false,
+            config_watcher,
+            config_error: None,
             rule_engine,
             config,
             ipc_server,
             current_brightness,
-            last_ssid: Arc::new(RwLock::new(None)),
+            max_brightness,
+            location_debouncer: LocationDebouncer::new(),
+            location_history: VecDeque::new(),
+            profile_dwell: ProfileDwell::new(Instant::now()),
+            media_players: Arc::new(RwLock::new(Vec::new())),
         })
     }
 
@@ -139,6 +226,21 @@ impl Daemon {
         let mut monitor_interval = interval(Duration::from_secs(1)); // Poll monitors every second
         let mut schedule_interval = interval(Duration::from_secs(60)); // Evaluate time schedules every minute
 
+        // Event-driven location detection needs an initial check
+        if self.location_detector.is_event_driven() {
+            self.check_location_profile_switch().await;
+        }
+
+        // Registered once, so a SIGHUP arriving while another event is handled is kept
+        let mut sighup = signal::unix::signal(SignalKind::hangup())
+            .map_err(|e| {
+                eprintln!(
+                    "Failed to set up SIGHUP handler, use `kbd-backlight reload` instead: {}",
+                    e
+                )
+            })
+            .ok();
+
         loop {
             tokio::select! {
                 // Handle shutdown signals
@@ -150,13 +252,23 @@ impl Daemon {
                     println!("Received SIGTERM, shutting down...");
                     break;
                 }
+                _ = Self::wait_for_sighup(&mut sighup) => {
+                    println!("Received SIGHUP, reloading configuration...");
+                    let _ = self.reload_config().await;
+                }
+
+                // Configuration files changed on disk
+                _ = self.wait_for_config_change() => {
+                    println!("Configuration files changed, reloading...");
+                    let _ = self.reload_config().await;
+                }
 
                 // Handle IPC connections
                 Ok(mut stream) = self.ipc_server.accept() => {
                     // Receive message
                     match IpcMessage::receive(&mut stream).await {
                         Ok(message) => {
-                            let response = self.handle_ipc_message(message);
+                            let response = self.handle_ipc_message(message).await;
                             if let Err(e) = response.send(&mut stream).await {
                                 eprintln!("Error sending IPC response: {}", e);
                             }
@@ -167,6 +279,33 @@ impl Daemon {
                     }
                 }
 
+                // Network changes reported by NetworkManager
+                _ = self.location_detector.wait_for_change() => {
+                    self.check_location_profile_switch().await;
+                    if let Err(e) = self.evaluate_and_apply_rules().await {
+                        eprintln!("Error evaluating rules: {}", e);
+                    }
+                }
+
+                // Power source or battery changes reported by UPower
+                _ = self.power_detector.wait_for_change() => {
+                    if let Err(e) = self.evaluate_and_apply_rules().await {
+                        eprintln!("Error evaluating rules: {}", e);
+                    }
+                }
+
+                // Suspend and resume reported by logind
+                event = self.wait_for_sleep_event() => {
+                    self.handle_sleep_event(event).await;
+                }
+
+                // Power mode changes reported by power-profiles-daemon
+                _ = self.wait_for_power_mode_change() => {
+                    if let Err(e) = self.evaluate_and_apply_rules().await {
+                        eprintln!("Error evaluating rules: {}", e);
+                    }
+                }
+
                 // Periodic monitor polling (every second)
                 _ = monitor_interval.tick() => {
                     if let Err(e) = self.evaluate_and_apply_rules().await {
@@ -189,24 +328,25 @@ impl Daemon {
 
     /// Evaluate rules and apply brightness changes
     async fn evaluate_and_apply_rules(&mut self) -> Result<()> {
-        // Check for location-based profile switching
-        self.check_location_profile_switch();
+        // Keep the backlight off while the system is going to sleep
+        if self.sleeping {
+            return Ok(());
+        }
 
-        // Get current power state
-        let power_state = self
-            .power_detector
-            .get_power_state()
-            .unwrap_or(PowerState::Unknown);
+        self.expire_profile_pin().await;
+
+        // Poll for location-based profile switching unless changes are event-driven,
+        // and keep polling while a network change is settling
+        if !self.location_detector.is_event_driven() || self.location_debouncer.is_pending() {
+            self.check_location_profile_switch().await;
+        }
 
-        // Get profile idle timeout and video detection settings
-        let (idle_timeout, video_detection_enabled, ac_always_on) = {
+        // Get the profile's idle timeout for the current power source
+        let idle_timeout = {
+            let (power_state, power_mode) = (self.power_state(), self.power_mode());
             let config = self.config.read().unwrap();
             let profile = config.profiles.get(&config.active_profile).unwrap();
-            (
-                profile.idle_timeout,
-                profile.video_detection_enabled,
-                profile.ac_always_on,
-            )
+            profile.idle_timeout_for(power_state, power_mode)
         };
 
         // Update idle monitor timeout only if changed (to avoid file descriptor leak)
@@ -216,6 +356,69 @@ impl Daemon {
             *self.current_idle_timeout.write().unwrap() = idle_timeout;
         }
 
+        let context = self.build_context().await?;
+        let previous_brightness = context.previous_brightness;
+
+        // End a timed manual override once it runs out
+        let expired = self
+            .rule_engine
+            .write()
+            .unwrap()
+            .expire_manual_override(&context);
+        if let Some(manual) = expired {
+            println!(
+                "Manual brightness override {} ended ({}), resuming automatic control",
+                manual.brightness, manual.expiry
+            );
+            self.save_state();
+        }
+
+        // Evaluate rules
+        let decision = self.rule_engine.read().unwrap().evaluate(&context);
+
+        // Apply brightness decision
+        if let kbd_backlight::rules::BrightnessDecision::SetBrightness(brightness) = decision.action
+        {
+            if brightness != previous_brightness {
+                self.brightness_controller.set_brightness(brightness)?;
+                *self.current_brightness.write().unwrap() = brightness;
+                println!(
+                    "Brightness changed: {} -> {} (rule: {}, idle: {}, video: {}, fullscreen: {}, meeting: {}, power: {:?})",
+                    previous_brightness,
+                    brightness,
+                    decision
+                        .trace
+                        .winner
+                        .map_or_else(|| "none".to_string(), |rule| rule.to_string()),
+                    context.is_idle,
+                    context.is_video_playing,
+                    context.is_fullscreen,
+                    context.is_in_meeting,
+                    context.power_state
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gather the current idle, video and fullscreen signals into a rule context
+    ///
+    /// Only the signals the active profile's suppression policy needs are polled.
+    async fn build_context(&self) -> Result<SystemContext> {
+        let (policy, media_filter, meeting, uses) = {
+            let config = self.config.read().unwrap();
+            let profile = config.profiles.get(&config.active_profile).unwrap();
+            (
+                profile.suppression_policy(),
+                profile.media_players.clone(),
+                profile.meeting.clone().filter(|m| m.enabled),
+                [Variable::Ssid, Variable::IdleSeconds, Variable::AppId]
+                    .map(|variable| profile.uses_variable(variable)),
+            )
+        };
+        let [uses_ssid, uses_idle_seconds, uses_app_id] = uses;
+
         // Check idle state with error handling
         let is_idle = match self.idle_monitor.read().unwrap().is_idle() {
             Ok(idle) => idle,
@@ -229,158 +432,351 @@ impl Daemon {
             }
         };
 
-        // Check video playback state (replaces fullscreen for video detection)
-        let is_video_playing = if video_detection_enabled {
-            if let Some(ref detector) = self.video_detector {
-                detector.is_video_playing().await.unwrap_or(false)
-            } else {
-                false
+        // Check video playback state
+        let is_video_playing = match self.video_detector {
+            Some(ref detector) if policy.uses_video() => {
+                let players: Vec<MediaPlayerStatus> = detector
+                    .list_media_players()
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|player| MediaPlayerStatus {
+                        suppressing: player.is_suppressing(&media_filter),
+                        bus_name: player.bus_name,
+                        identity: player.identity,
+                        status: player.status,
+                    })
+                    .collect();
+                let playing = players.iter().any(|player| player.suppressing);
+                *self.media_players.write().unwrap() = players;
+                playing
             }
-        } else {
-            // Fall back to fullscreen detection if video detection is disabled
-            if let Some(ref monitor) = self.fullscreen_monitor {
-                monitor.is_fullscreen_active().unwrap_or(false)
-            } else {
+            _ => {
+                self.media_players.write().unwrap().clear();
                 false
             }
         };
 
-        // Get current time
-        let current_time = Local::now();
+        // Check fullscreen state with graceful degradation
+        let is_fullscreen = match self.fullscreen_monitor {
+            Some(ref monitor) if policy.uses_fullscreen() => {
+                monitor.is_fullscreen_active().unwrap_or_else(|e| {
+                    eprintln!(
+                        "Warning: Fullscreen detection failed: {}. Assuming not fullscreen.",
+                        e
+                    );
+                    false
+                })
+            }
+            _ => false,
+        };
 
-        // Get previous brightness
-        let previous_brightness = *self.current_brightness.read().unwrap();
+        // Check for webcam use or screen sharing, only if the profile has a meeting rule
+        let is_in_meeting = match meeting {
+            Some(meeting) => self
+                .meeting_detector
+                .is_meeting_active(meeting.detect_webcam, meeting.detect_screen_sharing),
+            None => false,
+        };
 
-        // Create system context
-        let context = SystemContext {
-            is_idle,
-            is_fullscreen: is_video_playing, // Use video playing as "fullscreen" for now
-            current_time,
-            previous_brightness,
+        // Read battery level for battery rules
+        let battery = self.power_detector.get_battery_level().unwrap_or_else(|e| {
+            eprintln!("Warning: Battery detection failed: {}", e);
+            None
+        });
+
+        // Signals only custom rules read
+        let ssid = if uses_ssid {
+            self.location_detector
+                .get_current_ssid()
+                .await
+                .unwrap_or_default()
+        } else {
+            None
+        };
+        let idle_seconds = if uses_idle_seconds {
+            self.idle_monitor
+                .read()
+                .unwrap()
+                .get_idle_time()
+                .map_or(0, |idle| idle.as_secs())
+        } else {
+            0
+        };
+        let app_id = match self.fullscreen_monitor {
+            Some(ref monitor) if uses_app_id => monitor.active_app_id().unwrap_or_else(|e| {
+                eprintln!("Warning: Focused window detection failed: {}", e);
+                None
+            }),
+            _ => None,
         };
 
-        // Evaluate rules
-        let decision = self.rule_engine.read().unwrap().evaluate(&context);
-        let has_manual_override = self.rule_engine.read().unwrap().manual_override.is_some();
+        Ok(SystemContext {
+            is_idle,
+            is_video_playing,
+            is_fullscreen,
+            is_in_meeting,
+            current_time: Local::now(),
+            previous_brightness: *self.current_brightness.read().unwrap(),
+            power_state: self.power_state(),
+            power_mode: self.power_mode(),
+            battery,
+            max_brightness: self.max_brightness,
+            ssid,
+            idle_seconds,
+            app_id,
+        })
+    }
 
-        // Apply brightness decision with optional AC power handling
-        if let kbd_backlight::rules::BrightnessDecision::SetBrightness(mut brightness) = decision {
-            // Optional AC power handling (disabled by default):
-            // - If ac_always_on is enabled: Keep brightness at 1 when on AC (except during video or manual override)
-            // - If disabled: Respect all rules regardless of power state
-            if ac_always_on
-                && power_state == PowerState::AC
-                && !is_video_playing
-                && !has_manual_override
-            {
-                brightness = 1;
-            }
+    /// Current power source, Unknown if it can't be read
+    fn power_state(&self) -> PowerState {
+        self.power_detector
+            .get_power_state()
+            .unwrap_or(PowerState::Unknown)
+    }
 
-            if brightness != previous_brightness {
-                self.brightness_controller.set_brightness(brightness)?;
-                *self.current_brightness.write().unwrap() = brightness;
-                println!(
-                    "Brightness changed: {} -> {} (idle: {}, video: {}, power: {:?})",
-                    previous_brightness, brightness, is_idle, is_video_playing, power_state
+    /// Active power-profiles-daemon mode, None if unavailable
+    fn power_mode(&self) -> Option<PowerMode> {
+        self.power_profiles
+            .as_ref()
+            .and_then(|monitor| monitor.active_mode())
+    }
+
+    /// Persist the active profile, pin and manual override, keeping them in memory if
+    /// state.toml can't be written
+    fn save_state(&self) {
+        if let Err(e) = self.config.read().unwrap().save_active_profile() {
+            eprintln!("Warning: Failed to save state: {}", e);
+        }
+    }
+
+    /// Wait until the configuration files change, never resolves without a watcher
+    async fn wait_for_config_change(&self) {
+        match self.config_watcher {
+            Some(ref watcher) => watcher.wait_for_change().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Load and validate the configuration from disk and swap it in
+    ///
+    /// On failure the previous configuration stays active and the error is kept
+    /// for `kbd-backlight status`.
+    async fn reload_config(&mut self) -> Result<()> {
+        // Never fall back to defaults here: a missing config.toml must not overwrite
+        // the user's profiles and state
+        let config = match Config::load_from(&Config::get_config_dir()) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!(
+                    "Configuration reload failed, keeping the previous configuration: {}",
+                    e
                 );
+                self.config_error = Some(e.to_string());
+                return Err(e);
             }
-        }
+        };
 
+        for warning in config.warnings() {
+            eprintln!("Warning: {}", warning);
+        }
+        let profile_changed = {
+            let mut current = self.config.write().unwrap();
+            let changed = current.active_profile != config.active_profile;
+            *current = config;
+            changed
+        };
+        if profile_changed {
+            self.profile_dwell.reset(Instant::now());
+        }
+        self.config_error = None;
+        println!("Configuration reloaded");
+
+        // Location matchers may have changed
+        self.location_debouncer.reset();
+        self.check_location_profile_switch().await;
+        if let Err(e) = self.force_rule_evaluation().await {
+            eprintln!("Error applying brightness after reload: {}", e);
+        }
         Ok(())
     }
 
-    /// Check if we should switch profiles based on WiFi location
-    fn check_location_profile_switch(&mut self) {
-        let config = self.config.read().unwrap();
-        if !config.auto_switch_location {
-            return;
+    /// Wait for suspend or resume, never resolves without logind
+    async fn wait_for_sleep_event(&self) -> SleepEvent {
+        match self.sleep_monitor {
+            Some(ref monitor) => monitor.next_event().await,
+            None => std::future::pending().await,
         }
+    }
 
-        // Get current SSID
-        if let Ok(Some(ssid)) = self.location_detector.get_current_ssid() {
-            let mut last_ssid = self.last_ssid.write().unwrap();
-
-            // Only switch if SSID changed
-            if last_ssid.as_ref() != Some(&ssid) {
-                // Build location mappings from profiles
-                let location_mappings = config.build_location_mappings();
-
-                // Check if we have a mapping for this SSID
-                if let Some(profile_name) = location_mappings.get(&ssid).cloned() {
-                    if profile_name != config.active_profile {
-                        let old_profile = config.active_profile.clone();
-                        drop(config);
-                        drop(last_ssid);
-
-                        // Switch profile
-                        let mut config = self.config.write().unwrap();
-                        config.active_profile = profile_name.clone();
-
-                        println!(
-                            "Location changed: {} -> Switching profile: {} -> {}",
-                            ssid, old_profile, profile_name
-                        );
-
-                        // Save active profile state
-                        let _ = config.save_active_profile();
-                        drop(config);
-
-                        // Update last SSID
-                        *self.last_ssid.write().unwrap() = Some(ssid);
-                        return;
+    /// Turn the backlight off before sleep, and re-apply the rules after resume
+    ///
+    /// Firmware often resets the backlight on resume, so the hardware is read again
+    /// and the decision is written even if it matches the last known brightness.
+    async fn handle_sleep_event(&mut self, event: SleepEvent) {
+        match event {
+            SleepEvent::Suspending => {
+                self.sleeping = true;
+                println!("Preparing for sleep, turning backlight off");
+                if let Err(e) = self.brightness_controller.set_brightness(0) {
+                    eprintln!("Error turning backlight off before sleep: {}", e);
+                } else {
+                    *self.current_brightness.write().unwrap() = 0;
+                }
+                if let Some(ref monitor) = self.sleep_monitor {
+                    monitor.release_inhibitor();
+                }
+            }
+            SleepEvent::Resumed => {
+                self.sleeping = false;
+                if let Some(ref monitor) = self.sleep_monitor {
+                    monitor.inhibit().await;
+                }
+                match self.brightness_controller.get_brightness() {
+                    Ok(brightness) => {
+                        println!("Resumed from sleep, hardware brightness is {}", brightness);
+                        *self.current_brightness.write().unwrap() = brightness;
                     }
+                    Err(e) => eprintln!("Error reading brightness after resume: {}", e),
+                }
+                if let Err(e) = self.force_rule_evaluation().await {
+                    eprintln!("Error applying brightness after resume: {}", e);
                 }
+            }
+        }
+    }
+
+    /// Wait until the power mode changes, never resolves without power-profiles-daemon
+    async fn wait_for_power_mode_change(&self) {
+        match self.power_profiles {
+            Some(ref monitor) => monitor.wait_for_change().await,
+            None => std::future::pending().await,
+        }
+    }
 
-                *last_ssid = Some(ssid);
+    /// Release a time-limited profile pin once it has run out
+    async fn expire_profile_pin(&mut self) {
+        let expired = {
+            let mut config = self.config.write().unwrap();
+            let expired = config
+                .profile_pin
+                .as_ref()
+                .is_some_and(|pin| pin.is_expired(Local::now()));
+            if expired {
+                config.profile_pin = None;
+                let _ = config.save_active_profile();
             }
+            expired
+        };
+
+        if expired {
+            println!("Profile pin expired, resuming automatic location switching");
+            self.location_debouncer.reset();
+            self.check_location_profile_switch().await;
         }
     }
 
-    /// Force immediate rule evaluation and brightness application
-    fn force_rule_evaluation(&mut self) -> Result<()> {
-        // Check idle state with error handling
-        let is_idle = self
-            .idle_monitor
-            .read()
-            .unwrap()
-            .is_idle()
-            .unwrap_or_else(|e| {
-                eprintln!("Warning: Idle detection failed: {}. Assuming not idle.", e);
-                false
-            });
+    /// Check if we should switch profiles based on WiFi location
+    async fn check_location_profile_switch(&mut self) {
+        if !self.config.read().unwrap().auto_switch_location {
+            return;
+        }
 
-        // Check fullscreen state with graceful degradation
-        let is_fullscreen = if let Some(ref monitor) = self.fullscreen_monitor {
-            monitor.is_fullscreen_active().unwrap_or_else(|e| {
-                eprintln!(
-                    "Warning: Fullscreen detection failed: {}. Assuming not fullscreen.",
-                    e
-                );
-                false
-            })
-        } else {
-            false
+        // Get current network fingerprint
+        let Ok(location) = self.location_detector.get_fingerprint().await else {
+            return;
         };
 
-        // Get current time
-        let current_time = Local::now();
+        // Only act once the network has been stable for the settle time
+        let settle = Duration::from_secs(self.config.read().unwrap().location_settle_time);
+        let Some(location) = self
+            .location_debouncer
+            .observe(location, Instant::now(), settle)
+        else {
+            return;
+        };
 
-        // Get previous brightness
-        let previous_brightness = *self.current_brightness.read().unwrap();
+        let mut config = self.config.write().unwrap();
 
-        // Create system context
-        let context = SystemContext {
-            is_idle,
-            is_fullscreen,
-            current_time,
-            previous_brightness,
+        // A manually pinned profile holds until its pin is released
+        if let Some(pin) = config.profile_pin.clone() {
+            if pin.holds_on(&location) {
+                self.location_debouncer.accept();
+                return;
+            }
+            println!("Network changed ({}), releasing profile pin", location);
+            config.profile_pin = None;
+            let _ = config.save_active_profile();
+        }
+
+        // Find the profile whose location matchers best fit the current network
+        let Some((profile_name, source)) = config
+            .resolve_location_source(&location)
+            .map(|(name, source)| (name.to_string(), source))
+        else {
+            self.location_debouncer.accept();
+            return;
         };
+        if profile_name == config.active_profile {
+            self.location_debouncer.accept();
+            return;
+        }
+
+        // Stay on the current profile until its minimum dwell time has passed;
+        // the change stays pending and is retried on the next poll
+        let min_dwell = config
+            .profiles
+            .get(&config.active_profile)
+            .map_or(0, |profile| profile.min_dwell_time);
+        if self
+            .profile_dwell
+            .holds(Duration::from_secs(min_dwell), Instant::now())
+        {
+            return;
+        }
+        self.location_debouncer.accept();
+
+        let old_profile = config.active_profile.clone();
+
+        // Switch profile
+        config.active_profile = profile_name.clone();
+        self.profile_dwell.reset(Instant::now());
+
+        println!(
+            "Location changed: {} -> Switching profile: {} -> {} ({})",
+            location, old_profile, profile_name, source
+        );
+
+        // Save active profile state
+        let _ = config.save_active_profile();
+
+        if self.location_history.len() == LOCATION_HISTORY_LEN {
+            self.location_history.pop_front();
+        }
+        self.location_history.push_back(LocationChange {
+            timestamp: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            network: location.to_string(),
+            from_profile: old_profile,
+            to_profile: profile_name,
+            reason: source.to_string(),
+        });
+    }
+
+    /// Force immediate rule evaluation and brightness application
+    async fn force_rule_evaluation(&mut self) -> Result<()> {
+        // The evaluation on resume applies whatever changed while asleep
+        if self.sleeping {
+            return Ok(());
+        }
+
+        let context = self.build_context().await?;
 
         // Evaluate rules
         let decision = self.rule_engine.read().unwrap().evaluate(&context);
 
         // Apply brightness decision
-        if let kbd_backlight::rules::BrightnessDecision::SetBrightness(brightness) = decision {
+        if let kbd_backlight::rules::BrightnessDecision::SetBrightness(brightness) = decision.action
+        {
             self.brightness_controller.set_brightness(brightness)?;
             *self.current_brightness.write().unwrap() = brightness;
             println!("Brightness applied: {}", brightness);
@@ -390,73 +786,156 @@ impl Daemon {
     }
 
     /// Handle a single IPC message
-    fn handle_ipc_message(&mut self, message: IpcMessage) -> IpcResponse {
+    async fn handle_ipc_message(&mut self, message: IpcMessage) -> IpcResponse {
         match message {
             IpcMessage::GetStatus => {
-                let config = self.config.read().unwrap();
-                let current_brightness = *self.current_brightness.read().unwrap();
-                let is_idle = self.idle_monitor.read().unwrap().is_idle().unwrap_or(false);
-                let is_fullscreen = if let Some(ref monitor) = self.fullscreen_monitor {
-                    monitor.is_fullscreen_active().unwrap_or(false)
-                } else {
-                    false
+                let context = match self.build_context().await {
+                    Ok(context) => context,
+                    Err(e) => return IpcResponse::Error(format!("Failed to read status: {}", e)),
+                };
+                let (manual_override, override_ends) = {
+                    let engine = self.rule_engine.read().unwrap();
+                    (
+                        engine.manual_override(),
+                        engine.manual_override_ends(&context),
+                    )
                 };
-                let manual_override = self.rule_engine.read().unwrap().manual_override;
+                let manual_override_remaining = override_ends.map(|ends| {
+                    (ends - context.current_time)
+                        .num_seconds()
+                        .try_into()
+                        .unwrap_or(0)
+                });
+                let config = self.config.read().unwrap();
+                let media_players = self.media_players.read().unwrap().clone();
 
                 IpcResponse::Status(StatusInfo {
                     active_profile: config.active_profile.clone(),
-                    current_brightness,
-                    is_idle,
-                    is_fullscreen,
-                    manual_override,
+                    current_brightness: context.previous_brightness,
+                    is_idle: context.is_idle,
+                    is_fullscreen: context.is_fullscreen,
+                    is_video_playing: context.is_video_playing,
+                    in_meeting: context.is_in_meeting,
+                    manual_override: manual_override.map(|manual| manual.brightness),
+                    manual_override_expiry: manual_override.map(|manual| manual.expiry.to_string()),
+                    manual_override_remaining,
+                    media_players,
+                    profile_pin: config.profile_pin.as_ref().map(ToString::to_string),
+                    battery: context.battery,
+                    config_error: self.config_error.clone(),
+                    config_warnings: config.warnings(),
                 })
             }
 
-            IpcMessage::SetProfile(profile_name) => {
-                let mut config = self.config.write().unwrap();
-
-                // Check if profile exists
-                if !config.profiles.contains_key(&profile_name) {
-                    let available: Vec<_> = config.profiles.keys().cloned().collect();
-                    return IpcResponse::Error(format!(
-                        "Profile '{}' not found. Available profiles: {}",
-                        profile_name,
-                        available.join(", ")
-                    ));
+            IpcMessage::Explain => match self.build_context().await {
+                Ok(context) => {
+                    let decision = self.rule_engine.read().unwrap().evaluate(&context);
+                    IpcResponse::Explanation(decision.trace)
                 }
+                Err(e) => IpcResponse::Error(format!("Failed to read system state: {}", e)),
+            },
 
-                // Store old profile for logging
-                let old_profile = config.active_profile.clone();
+            IpcMessage::SetProfile {
+                name: profile_name,
+                pin,
+            } => {
+                let pin = match pin {
+                    PinMode::UntilNetworkChange => ProfilePin::UntilNetworkChange {
+                        network: self
+                            .location_detector
+                            .get_fingerprint()
+                            .await
+                            .unwrap_or_default(),
+                    },
+                    PinMode::Until(until) => ProfilePin::UntilTime { until },
+                    PinMode::Indefinite => ProfilePin::Indefinite,
+                };
 
-                // Update active profile
-                config.active_profile = profile_name.clone();
+                // Scope the config lock so it is released before forcing rule evaluation
+                let old_profile = {
+                    let mut config = self.config.write().unwrap();
+
+                    // Check if profile exists
+                    if !config.profiles.contains_key(&profile_name) {
+                        let available: Vec<_> = config.profiles.keys().cloned().collect();
+                        return IpcResponse::Error(format!(
+                            "Profile '{}' not found. Available profiles: {}",
+                            profile_name,
+                            available.join(", ")
+                        ));
+                    }
 
-                // Update idle monitor timeout from new profile
-                if let Some(profile) = config.profiles.get(&profile_name) {
-                    *self.idle_monitor.write().unwrap() = IdleMonitor::new(profile.idle_timeout);
-                }
+                    // Store old profile and pin for logging and rollback
+                    let old_profile = config.active_profile.clone();
+                    let old_pin = config.profile_pin.take();
 
-                // Save active profile state to persist the profile change
-                if let Err(e) = config.save_active_profile() {
-                    // Rollback on save failure
-                    config.active_profile = old_profile;
-                    return IpcResponse::Error(format!("Failed to save active profile: {}", e));
-                }
+                    // Update active profile and pin it against location switching
+                    config.active_profile = profile_name.clone();
+                    config.profile_pin = Some(pin.clone());
 
-                // Release the config lock before forcing rule evaluation
-                drop(config);
+                    // Update idle monitor timeout from new profile
+                    if let Some(profile) = config.profiles.get(&profile_name) {
+                        let idle_timeout =
+                            profile.idle_timeout_for(self.power_state(), self.power_mode());
+                        *self.idle_monitor.write().unwrap() = IdleMonitor::new(idle_timeout);
+                        *self.current_idle_timeout.write().unwrap() = idle_timeout;
+                    }
 
-                println!("Profile switched: {} -> {}", old_profile, profile_name);
+                    // Save active profile state to persist the profile change
+                    if let Err(e) = config.save_active_profile() {
+                        // Rollback on save failure
+                        config.active_profile = old_profile;
+                        config.profile_pin = old_pin;
+                        return IpcResponse::Error(format!("Failed to save active profile: {}", e));
+                    }
+
+                    old_profile
+                };
+
+                println!(
+                    "Profile switched: {} -> {} (pinned {})",
+                    old_profile, profile_name, pin
+                );
+                self.profile_dwell.reset(Instant::now());
 
                 // Immediately apply rules from the new profile
-                if let Err(e) = self.force_rule_evaluation() {
+                if let Err(e) = self.force_rule_evaluation().await {
                     eprintln!("Warning: Failed to apply new profile rules: {}", e);
                 }
 
                 IpcResponse::ProfileChanged
             }
 
-            IpcMessage::SetManualBrightness(brightness) => {
+            IpcMessage::ReleaseProfilePin => {
+                let released = {
+                    let mut config = self.config.write().unwrap();
+                    let released = config.profile_pin.take().is_some();
+                    if released {
+                        if let Err(e) = config.save_active_profile() {
+                            return IpcResponse::Error(format!(
+                                "Failed to save active profile: {}",
+                                e
+                            ));
+                        }
+                    }
+                    released
+                };
+
+                if released {
+                    println!("Profile pin released, resuming automatic location switching");
+                }
+
+                // Re-resolve the current network right away
+                self.location_debouncer.reset();
+                self.check_location_profile_switch().await;
+                if let Err(e) = self.force_rule_evaluation().await {
+                    eprintln!("Warning: Failed to apply profile rules: {}", e);
+                }
+
+                IpcResponse::Ok
+            }
+
+            IpcMessage::SetManualBrightness { brightness, until } => {
                 // Validate brightness range
                 let max_brightness = match self.brightness_controller.get_max_brightness() {
                     Ok(max) => max,
@@ -472,25 +951,74 @@ impl Daemon {
                     ));
                 }
 
-                // Set manual override
+                let expiry = match until {
+                    OverrideMode::Indefinite => OverrideExpiry::Indefinite,
+                    OverrideMode::Until(until) => OverrideExpiry::UntilTime { until },
+                    OverrideMode::UntilNextSchedule => {
+                        // Otherwise the override would never end
+                        let context = match self.build_context().await {
+                            Ok(context) => context,
+                            Err(e) => {
+                                return IpcResponse::Error(format!(
+                                    "Failed to read system state: {}",
+                                    e
+                                ))
+                            }
+                        };
+                        if self
+                            .rule_engine
+                            .read()
+                            .unwrap()
+                            .next_schedule_change(&context)
+                            .is_none()
+                        {
+                            let config = self.config.read().unwrap();
+                            return IpcResponse::Error(format!(
+                                "Profile '{}' has no time schedules to wait for (power: {})",
+                                config.active_profile, context.power_state
+                            ));
+                        }
+                        OverrideExpiry::UntilNextSchedule {
+                            since: Local::now().timestamp(),
+                        }
+                    }
+                    OverrideMode::UntilIdle => OverrideExpiry::UntilIdle,
+                };
+
+                // Set manual override and keep it across restarts
                 self.rule_engine
                     .write()
                     .unwrap()
-                    .set_manual_override(Some(brightness));
+                    .set_timed_override(Some(ManualOverride { brightness, expiry }));
+                self.save_state();
 
-                // Apply immediately
-                if let Err(e) = self.brightness_controller.set_brightness(brightness) {
-                    return IpcResponse::Error(format!("Failed to set brightness: {}", e));
+                // Apply immediately, or on resume if the system is going to sleep
+                if !self.sleeping {
+                    if let Err(e) = self.brightness_controller.set_brightness(brightness) {
+                        return IpcResponse::Error(format!("Failed to set brightness: {}", e));
+                    }
+                    *self.current_brightness.write().unwrap() = brightness;
                 }
 
-                *self.current_brightness.write().unwrap() = brightness;
-                println!("Manual brightness override set to: {}", brightness);
+                println!(
+                    "Manual brightness override set to: {} ({})",
+                    brightness, expiry
+                );
                 IpcResponse::BrightnessSet
             }
 
             IpcMessage::ClearManualOverride => {
                 self.rule_engine.write().unwrap().set_manual_override(None);
+                self.save_state();
                 println!("Manual brightness override cleared");
+
+                // Hand control back to the rules right away
+                if let Err(e) = self.force_rule_evaluation().await {
+                    eprintln!(
+                        "Warning: Failed to apply rules after clearing override: {}",
+                        e
+                    );
+                }
                 IpcResponse::Ok
             }
 
@@ -520,9 +1048,10 @@ impl Daemon {
                 if let Some(prof) = config.profiles.get_mut(&profile) {
                     prof.time_schedules
                         .push(kbd_backlight::config::TimeSchedule {
-                            hour,
-                            minute,
-                            brightness,
+                            hour: Some(hour),
+                            minute: Some(minute),
+                            brightness: kbd_backlight::config::BrightnessValue::Level(brightness),
+                            ..Default::default()
                         });
 
                     // Save profile to its file
@@ -540,6 +1069,18 @@ impl Daemon {
                 }
             }
 
+            IpcMessage::GetLocationHistory => {
+                IpcResponse::LocationHistory(self.location_history.iter().cloned().collect())
+            }
+
+            IpcMessage::Reload => match self.reload_config().await {
+                Ok(()) => IpcResponse::Ok,
+                Err(e) => IpcResponse::Error(format!(
+                    "Failed to reload configuration, keeping the previous one: {}",
+                    e
+                )),
+            },
+
             IpcMessage::Shutdown => {
                 println!("Shutdown requested via IPC");
                 std::process::exit(0);
@@ -547,6 +1088,16 @@ impl Daemon {
         }
     }
 
+    /// Wait for SIGHUP signal, never resolves without a handler
+    async fn wait_for_sighup(sighup: &mut Option<Signal>) {
+        if let Some(sighup) = sighup {
+            if sighup.recv().await.is_some() {
+                return;
+            }
+        }
+        std::future::pending().await
+    }
+
     /// Wait for SIGTERM signal
     async fn wait_for_sigterm() {
         #[cfg(unix)]
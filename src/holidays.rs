@@ -0,0 +1,227 @@
+// Holiday calendars for time schedules
+// Reads either an iCalendar (.ics) file or a plain list of dates, one per line
+use crate::{Error, Result};
+use chrono::{Datelike, NaiveDate};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// Longest event taken from a calendar, so a broken DTEND can't mark years as holidays
+const MAX_EVENT_DAYS: i64 = 366;
+
+/// Set of holiday dates
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Holidays {
+    dates: BTreeSet<NaiveDate>,
+    /// (month, day) of holidays repeating every year
+    yearly: BTreeSet<(u32, u32)>,
+}
+
+impl Holidays {
+    /// Load a holidays file; `.ics` content is detected by its `BEGIN:VCALENDAR` header
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            Error::config_error(
+                path.display().to_string(),
+                format!("Failed to read holidays file: {}", e),
+            )
+        })?;
+        Self::parse(&content).map_err(|e| Error::config_error(path.display().to_string(), e))
+    }
+
+    /// Parse iCalendar or plain date list content
+    pub fn parse(content: &str) -> std::result::Result<Self, String> {
+        if content.trim_start().starts_with("BEGIN:VCALENDAR") {
+            Self::parse_ics(content)
+        } else {
+            Self::parse_date_list(content)
+        }
+    }
+
+    /// Whether the date is a holiday
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.dates.contains(&date) || self.yearly.contains(&(date.month(), date.day()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dates.is_empty() && self.yearly.is_empty()
+    }
+
+    /// One `YYYY-MM-DD` date per line; `#` starts a comment
+    fn parse_date_list(content: &str) -> std::result::Result<Self, String> {
+        let mut holidays = Self::default();
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let date = NaiveDate::parse_from_str(line, "%Y-%m-%d").map_err(|_| {
+                format!(
+                    "line {}: invalid date '{}', expected YYYY-MM-DD",
+                    idx + 1,
+                    line
+                )
+            })?;
+            holidays.dates.insert(date);
+        }
+        Ok(holidays)
+    }
+
+    /// All-day and timed VEVENTs; `RRULE:FREQ=YEARLY` events repeat every year,
+    /// other recurrence rules only count their first occurrence
+    fn parse_ics(content: &str) -> std::result::Result<Self, String> {
+        let mut holidays = Self::default();
+        let mut event: Option<IcsEvent> = None;
+
+        for line in unfold_ics_lines(content) {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let property = name.split_once(';').map_or(name, |(property, _)| property);
+            match (property.to_ascii_uppercase().as_str(), event.as_mut()) {
+                ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                    event = Some(IcsEvent::default());
+                }
+                ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                    if let Some(event) = event.take() {
+                        event.add_to(&mut holidays)?;
+                    }
+                }
+                ("DTSTART", Some(event)) => event.start = Some(parse_ics_date(value)?),
+                ("DTEND", Some(event)) => {
+                    let date = parse_ics_date(value)?;
+                    // All-day events end on the following day, timed events end on their date
+                    let all_day = !value.contains('T');
+                    event.end = Some(if all_day {
+                        date.pred_opt().unwrap_or(date)
+                    } else {
+                        date
+                    });
+                }
+                ("RRULE", Some(event)) => {
+                    event.yearly = value
+                        .split(';')
+                        .any(|part| part.eq_ignore_ascii_case("FREQ=YEARLY"));
+                }
+                _ => {}
+            }
+        }
+        Ok(holidays)
+    }
+}
+
+#[derive(Default)]
+struct IcsEvent {
+    start: Option<NaiveDate>,
+    /// Last day of the event (inclusive)
+    end: Option<NaiveDate>,
+    yearly: bool,
+}
+
+impl IcsEvent {
+    fn add_to(self, holidays: &mut Holidays) -> std::result::Result<(), String> {
+        let start = self
+            .start
+            .ok_or_else(|| "VEVENT without DTSTART".to_string())?;
+        let end = self.end.unwrap_or(start).max(start);
+        if (end - start).num_days() >= MAX_EVENT_DAYS {
+            return Err(format!("event starting {} is longer than a year", start));
+        }
+        for date in start.iter_days().take_while(|date| *date <= end) {
+            if self.yearly {
+                holidays.yearly.insert((date.month(), date.day()));
+            } else {
+                holidays.dates.insert(date);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Join continuation lines (starting with a space or tab) as described in RFC 5545
+fn unfold_ics_lines(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(previous)) => previous.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Date part of a `YYYYMMDD` or `YYYYMMDDTHHMMSS[Z]` value
+fn parse_ics_date(value: &str) -> std::result::Result<NaiveDate, String> {
+    value
+        .trim()
+        .get(..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+        .ok_or_else(|| format!("invalid calendar date '{}'", value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_date_list() {
+        let holidays = Holidays::parse(
+            "# Public holidays\n2024-12-25\n2024-12-26  # Boxing Day\n\n2025-01-01\n",
+        )
+        .unwrap();
+        assert!(holidays.contains(date(2024, 12, 25)));
+        assert!(holidays.contains(date(2024, 12, 26)));
+        assert!(holidays.contains(date(2025, 1, 1)));
+        assert!(!holidays.contains(date(2025, 12, 25)));
+        assert!(!holidays.contains(date(2024, 12, 24)));
+
+        let err = Holidays::parse("2024-12-25\n25.12.2024\n").unwrap_err();
+        assert!(err.contains("line 2"), "{}", err);
+        assert!(Holidays::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_ics() {
+        let holidays = Holidays::parse(
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             BEGIN:VEVENT\r\n\
+             DTSTART;VALUE=DATE:20241225\r\n\
+             DTEND;VALUE=DATE:20241227\r\n\
+             SUMMARY:Christmas\r\n\
+             END:VEVENT\r\n\
+             BEGIN:VEVENT\r\n\
+             DTSTART;VALUE=DATE:20200101\r\n\
+             RRULE:FREQ=YEARLY\r\n\
+             SUMMARY:New Year's\r\n\
+             \x20Day\r\n\
+             END:VEVENT\r\n\
+             BEGIN:VEVENT\r\n\
+             DTSTART:20240501T090000Z\r\n\
+             DTEND:20240501T170000Z\r\n\
+             END:VEVENT\r\n\
+             END:VCALENDAR\r\n",
+        )
+        .unwrap();
+        // DTEND of all-day events is exclusive
+        assert!(holidays.contains(date(2024, 12, 25)));
+        assert!(holidays.contains(date(2024, 12, 26)));
+        assert!(!holidays.contains(date(2024, 12, 27)));
+        // Yearly events repeat
+        assert!(holidays.contains(date(2020, 1, 1)));
+        assert!(holidays.contains(date(2031, 1, 1)));
+        // Timed events cover their day
+        assert!(holidays.contains(date(2024, 5, 1)));
+        assert!(!holidays.contains(date(2024, 5, 2)));
+
+        assert!(Holidays::parse("BEGIN:VCALENDAR\nBEGIN:VEVENT\nEND:VEVENT\n").is_err());
+        assert!(
+            Holidays::parse("BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:2024\nEND:VEVENT\n").is_err()
+        );
+    }
+}
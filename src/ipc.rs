@@ -1,6 +1,9 @@
 // IPC protocol module
 // This module handles communication between CLI and daemon
 
+use crate::power::BatteryLevel;
+use crate::rules::DecisionTrace;
+use crate::video_detector::PlaybackStatus;
 use crate::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
@@ -13,8 +16,20 @@ pub const DEFAULT_SOCKET_PATH: &str = "/tmp/kbd-backlight-daemon.sock";
 #[derive(Debug, Serialize, Deserialize)]
 pub enum IpcMessage {
     GetStatus,
-    SetProfile(String),
-    SetManualBrightness(u32),
+    /// Evaluate the rules and report how they decide the brightness
+    Explain,
+    SetProfile {
+        name: String,
+        #[serde(default)]
+        pin: PinMode,
+    },
+    /// Release a profile pin and resume automatic location switching
+    ReleaseProfilePin,
+    SetManualBrightness {
+        brightness: u32,
+        #[serde(default)]
+        until: OverrideMode,
+    },
     ClearManualOverride,
     ListProfiles,
     AddTimeSchedule {
@@ -23,16 +38,21 @@ pub enum IpcMessage {
         minute: u8,
         brightness: u32,
     },
+    GetLocationHistory,
+    /// Reload config.toml and the profiles from disk
+    Reload,
     Shutdown,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum IpcResponse {
     Status(StatusInfo),
+    Explanation(DecisionTrace),
     ProfileChanged,
     BrightnessSet,
     ProfileList(Vec<String>),
     ScheduleAdded,
+    LocationHistory(Vec<LocationChange>),
     Error(String),
     Ok,
 }
@@ -43,7 +63,80 @@ pub struct StatusInfo {
     pub current_brightness: u32,
     pub is_idle: bool,
     pub is_fullscreen: bool,
+    #[serde(default)]
+    pub is_video_playing: bool,
+    #[serde(default)]
+    pub in_meeting: bool,
     pub manual_override: Option<u32>,
+    /// When the manual override ends, e.g. "until idle"
+    #[serde(default)]
+    pub manual_override_expiry: Option<String>,
+    /// Seconds until the manual override ends, if it ends at a known time
+    #[serde(default)]
+    pub manual_override_remaining: Option<u64>,
+    #[serde(default)]
+    pub media_players: Vec<MediaPlayerStatus>,
+    /// Description of the active profile pin, if any
+    #[serde(default)]
+    pub profile_pin: Option<String>,
+    /// Combined battery level, None on machines without a battery
+    #[serde(default)]
+    pub battery: Option<BatteryLevel>,
+    /// Why the last configuration reload failed; the previous configuration stays active
+    #[serde(default)]
+    pub config_error: Option<String>,
+    /// Settings of the active configuration that are valid but probably unintended
+    #[serde(default)]
+    pub config_warnings: Vec<String>,
+}
+
+/// How long a manually selected profile holds against automatic location switching
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PinMode {
+    /// Until the network changes
+    #[default]
+    UntilNetworkChange,
+    /// Until the given Unix timestamp
+    Until(i64),
+    /// Until released with `kbd-backlight profile --auto`
+    Indefinite,
+}
+
+/// When a manual brightness override ends
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverrideMode {
+    /// Until `kbd-backlight auto`
+    #[default]
+    Indefinite,
+    /// Until the given Unix timestamp
+    Until(i64),
+    /// Until the next time schedule entry takes effect
+    UntilNextSchedule,
+    /// Until the idle timeout is reached
+    UntilIdle,
+}
+
+/// A media player seen by the daemon, as reported in status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaPlayerStatus {
+    pub bus_name: String,
+    pub identity: Option<String>,
+    pub status: PlaybackStatus,
+    /// Whether this player is currently keeping the backlight off
+    pub suppressing: bool,
+}
+
+/// An automatic profile switch caused by a network change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocationChange {
+    /// Local time of the switch, formatted as YYYY-MM-DD HH:MM:SS
+    pub timestamp: String,
+    /// The network that triggered the switch
+    pub network: String,
+    pub from_profile: String,
+    pub to_profile: String,
+    /// Why the new profile was chosen
+    pub reason: String,
 }
 
 impl IpcMessage {
@@ -361,13 +454,40 @@ impl IpcClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::RuleLayer;
+    use crate::power::{Battery, BatteryStatus, PowerMode, PowerState};
+    use crate::rules::{DecisionInputs, LimitTrace, RuleStatus, RuleTrace};
 
     #[test]
     fn test_ipc_message_serialization() {
         let messages = vec![
             IpcMessage::GetStatus,
-            IpcMessage::SetProfile("home".to_string()),
-            IpcMessage::SetManualBrightness(2),
+            IpcMessage::Explain,
+            IpcMessage::SetProfile {
+                name: "home".to_string(),
+                pin: PinMode::UntilNetworkChange,
+            },
+            IpcMessage::SetProfile {
+                name: "mobile".to_string(),
+                pin: PinMode::Until(1_736_182_800),
+            },
+            IpcMessage::ReleaseProfilePin,
+            IpcMessage::SetManualBrightness {
+                brightness: 2,
+                until: OverrideMode::Indefinite,
+            },
+            IpcMessage::SetManualBrightness {
+                brightness: 1,
+                until: OverrideMode::Until(1_736_182_800),
+            },
+            IpcMessage::SetManualBrightness {
+                brightness: 3,
+                until: OverrideMode::UntilNextSchedule,
+            },
+            IpcMessage::SetManualBrightness {
+                brightness: 0,
+                until: OverrideMode::UntilIdle,
+            },
             IpcMessage::ClearManualOverride,
             IpcMessage::ListProfiles,
             IpcMessage::AddTimeSchedule {
@@ -376,6 +496,8 @@ mod tests {
                 minute: 30,
                 brightness: 3,
             },
+            IpcMessage::GetLocationHistory,
+            IpcMessage::Reload,
             IpcMessage::Shutdown,
         ];
 
@@ -397,12 +519,89 @@ mod tests {
                 current_brightness: 2,
                 is_idle: false,
                 is_fullscreen: false,
-                manual_override: None,
+                is_video_playing: true,
+                in_meeting: false,
+                manual_override: Some(2),
+                manual_override_expiry: Some("until 2025-01-06 18:00".to_string()),
+                manual_override_remaining: Some(1_800),
+                media_players: vec![MediaPlayerStatus {
+                    bus_name: "org.mpris.MediaPlayer2.mpv".to_string(),
+                    identity: Some("mpv Media Player".to_string()),
+                    status: PlaybackStatus::Playing,
+                    suppressing: true,
+                }],
+                profile_pin: Some("until the network changes".to_string()),
+                battery: Some(BatteryLevel {
+                    percentage: 42,
+                    status: BatteryStatus::Discharging,
+                    batteries: vec![Battery {
+                        name: "BAT0".to_string(),
+                        percentage: 42,
+                        status: BatteryStatus::Discharging,
+                        energy_now: Some(21_000_000),
+                        energy_full: Some(50_000_000),
+                    }],
+                }),
+                config_error: Some("Profile 'home' has idle_timeout of 0".to_string()),
+                config_warnings: vec![
+                    "Profile 'dark' never turns the backlight on; add a time schedule or set default_brightness".to_string(),
+                ],
+            }),
+            IpcResponse::Explanation(DecisionTrace {
+                profile: "mobile".to_string(),
+                inputs: DecisionInputs {
+                    time: "Sat 2024-06-15 22:30:00".to_string(),
+                    power: PowerState::Battery,
+                    power_mode: Some(PowerMode::PowerSaver),
+                    battery: Some(42),
+                    discharging: true,
+                    idle: true,
+                    idle_seconds: 0,
+                    video: false,
+                    fullscreen: false,
+                    meeting: false,
+                    ssid: None,
+                    app_id: None,
+                    current_brightness: 2,
+                    max_brightness: 3,
+                },
+                rules: vec![
+                    RuleTrace {
+                        rule: RuleLayer::Idle,
+                        status: RuleStatus::Won(0),
+                        reason: "idle timeout reached".to_string(),
+                    },
+                    RuleTrace {
+                        rule: RuleLayer::Schedule,
+                        status: RuleStatus::Matched(2),
+                        reason: "20:00 entry in effect since Sat 20:00".to_string(),
+                    },
+                    RuleTrace {
+                        rule: RuleLayer::Media,
+                        status: RuleStatus::Disabled,
+                        reason: "disabled in enabled_rules".to_string(),
+                    },
+                ],
+                winner: Some(RuleLayer::Idle),
+                requested: 0,
+                limits: vec![LimitTrace {
+                    limit: "on_battery min_brightness".to_string(),
+                    from: 0,
+                    to: 1,
+                }],
+                brightness: 1,
             }),
             IpcResponse::ProfileChanged,
             IpcResponse::BrightnessSet,
             IpcResponse::ProfileList(vec!["home".to_string(), "office".to_string()]),
             IpcResponse::ScheduleAdded,
+            IpcResponse::LocationHistory(vec![LocationChange {
+                timestamp: "2025-01-06 08:59:12".to_string(),
+                network: "SSID eduroam, gateway 00:11:22:33:44:55".to_string(),
+                from_profile: "home".to_string(),
+                to_profile: "office".to_string(),
+                reason: "matched location".to_string(),
+            }]),
             IpcResponse::Error("Test error".to_string()),
             IpcResponse::Ok,
         ];
@@ -459,7 +658,16 @@ mod tests {
                     current_brightness: 1,
                     is_idle: false,
                     is_fullscreen: false,
+                    is_video_playing: false,
+                    in_meeting: false,
                     manual_override: None,
+                    manual_override_expiry: None,
+                    manual_override_remaining: None,
+                    media_players: vec![],
+                    profile_pin: None,
+                    battery: None,
+                    config_error: None,
+                    config_warnings: vec![],
                 }),
                 _ => IpcResponse::Ok,
             };
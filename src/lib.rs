@@ -1,13 +1,21 @@
 // Core library modules
 pub mod brightness;
 pub mod config;
+pub mod config_watcher;
 pub mod error;
+pub mod holidays;
 pub mod ipc;
 pub mod location;
+pub mod meeting;
 pub mod monitors;
 pub mod power;
 pub mod rules;
+pub mod sleep;
+pub mod solar;
 pub mod video_detector;
 pub mod wayland_idle;
 
+#[cfg(test)]
+mod test_support;
+
 pub use error::{Error, Result};
@@ -1,8 +1,759 @@
 // Location detection using WiFi SSID
+// Backends: NetworkManager over D-Bus (event-driven), nmcli/iw, then nl80211 netlink
 use crate::{Error, Result};
+use futures_lite::StreamExt;
+use nix::sys::socket::{
+    bind, recv, send, setsockopt, socket, sockopt, AddressFamily, MsgFlags, NetlinkAddr, SockFlag,
+    SockProtocol, SockType,
+};
+use nix::sys::time::TimeVal;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::path::Path;
 use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{proxy, Connection};
 
-pub struct LocationDetector;
+/// Well-known bus name of NetworkManager
+const NM_BUS_NAME: &str = "org.freedesktop.NetworkManager";
+/// NMActiveConnectionState: NM_ACTIVE_CONNECTION_STATE_ACTIVATED
+const NM_ACTIVE_CONNECTION_ACTIVATED: u32 = 2;
+/// Source of the routing and ARP tables
+const PROC_ROOT: &str = "/proc";
+/// Source of network interface state
+const SYS_NET_ROOT: &str = "/sys/class/net";
+/// RTF_GATEWAY route flag
+const RTF_GATEWAY: u32 = 0x2;
+/// ARPHRD_ETHER link type
+const ARPHRD_ETHER: u32 = 1;
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager",
+    gen_blocking = false
+)]
+trait NetworkManager {
+    #[zbus(property)]
+    fn active_connections(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    #[zbus(signal)]
+    fn state_changed(&self, state: u32) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Connection.Active",
+    default_service = "org.freedesktop.NetworkManager",
+    gen_blocking = false
+)]
+trait ActiveConnection {
+    #[zbus(property, name = "Type")]
+    fn connection_type(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn specific_object(&self) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(property)]
+    fn state(&self) -> zbus::Result<u32>;
+
+    #[zbus(property)]
+    fn id(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn uuid(&self) -> zbus::Result<String>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.AccessPoint",
+    default_service = "org.freedesktop.NetworkManager",
+    gen_blocking = false
+)]
+trait AccessPoint {
+    #[zbus(property)]
+    fn ssid(&self) -> zbus::Result<Vec<u8>>;
+
+    #[zbus(property)]
+    fn hw_address(&self) -> zbus::Result<String>;
+}
+
+/// An activated NetworkManager connection profile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveNetworkConnection {
+    /// Human-readable connection name (e.g. "Office LAN")
+    pub id: String,
+    pub uuid: String,
+}
+
+/// Everything known about the current network attachment
+/// Location profiles are resolved by scoring their matchers against this
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkFingerprint {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ssid: Option<String>,
+    /// MAC of the associated access point, lowercase aa:bb:cc:dd:ee:ff
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bssid: Option<String>,
+    /// MAC of the IPv4 default gateway, lowercase aa:bb:cc:dd:ee:ff
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gateway_mac: Option<String>,
+    /// Activated NetworkManager connections (empty without NetworkManager)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub connections: Vec<ActiveNetworkConnection>,
+    /// Physical wired interfaces with carrier
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub wired_interfaces: Vec<String>,
+}
+
+impl NetworkFingerprint {
+    /// Whether nothing at all is known about the network
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether both fingerprints describe the same network
+    /// The BSSID is ignored so roaming between access points does not count as a change
+    pub fn same_network(&self, other: &Self) -> bool {
+        self.ssid == other.ssid
+            && self.gateway_mac == other.gateway_mac
+            && self.connections == other.connections
+            && self.wired_interfaces == other.wired_interfaces
+    }
+}
+
+impl std::fmt::Display for NetworkFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(ref ssid) = self.ssid {
+            match self.bssid {
+                Some(ref bssid) => parts.push(format!("SSID {} ({})", ssid, bssid)),
+                None => parts.push(format!("SSID {}", ssid)),
+            }
+        }
+        if let Some(ref mac) = self.gateway_mac {
+            parts.push(format!("gateway {}", mac));
+        }
+        for connection in &self.connections {
+            parts.push(format!("connection {}", connection.id));
+        }
+        for interface in &self.wired_interfaces {
+            parts.push(format!("wired {}", interface));
+        }
+
+        if parts.is_empty() {
+            write!(f, "no network")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+/// Holds back network changes until they have been stable for a settle time
+///
+/// Roaming between access points or a brief disconnect produces short-lived
+/// fingerprints; only networks that outlast the settle time are reported.
+#[derive(Debug, Default)]
+pub struct LocationDebouncer {
+    settled: Option<NetworkFingerprint>,
+    pending: Option<(NetworkFingerprint, Instant)>,
+}
+
+impl LocationDebouncer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the current network
+    /// Returns it once it differs from the settled network and has been stable for `settle`.
+    /// The very first network is reported immediately, there is nothing to flap from.
+    pub fn observe(
+        &mut self,
+        fingerprint: NetworkFingerprint,
+        now: Instant,
+        settle: Duration,
+    ) -> Option<NetworkFingerprint> {
+        if self.settled.as_ref() == Some(&fingerprint) {
+            self.pending = None;
+            return None;
+        }
+
+        let since = match self.pending {
+            Some((ref pending, since)) if *pending == fingerprint => since,
+            _ => {
+                self.pending = Some((fingerprint.clone(), now));
+                now
+            }
+        };
+
+        if self.settled.is_none() || now.duration_since(since) >= settle {
+            Some(fingerprint)
+        } else {
+            None
+        }
+    }
+
+    /// Mark the pending network as handled so it is not reported again
+    pub fn accept(&mut self) {
+        if let Some((fingerprint, _)) = self.pending.take() {
+            self.settled = Some(fingerprint);
+        }
+    }
+
+    /// Forget the settled network so the next observation is reported immediately
+    pub fn reset(&mut self) {
+        self.settled = None;
+        self.pending = None;
+    }
+
+    /// Whether a network change is waiting to settle (or to be accepted)
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+}
+
+/// How long the active profile has been in use, for `min_dwell_time`
+///
+/// Counting starts when the daemon loads the active profile, so the dwell time also
+/// holds for the first switch after startup.
+#[derive(Debug)]
+pub struct ProfileDwell {
+    since: Instant,
+}
+
+impl ProfileDwell {
+    pub fn new(now: Instant) -> Self {
+        Self { since: now }
+    }
+
+    /// Restart counting after the active profile changed
+    pub fn reset(&mut self, now: Instant) {
+        self.since = now;
+    }
+
+    /// Whether the active profile must be kept for now
+    pub fn holds(&self, min_dwell: Duration, now: Instant) -> bool {
+        now.duration_since(self.since) < min_dwell
+    }
+}
+
+/// Reads the active WiFi network from NetworkManager and watches for changes
+pub struct NetworkManagerBackend {
+    conn: Connection,
+    changed: Arc<Notify>,
+    watcher: JoinHandle<()>,
+}
+
+impl NetworkManagerBackend {
+    /// Connect to NetworkManager on the given bus and subscribe to state changes
+    pub async fn new(conn: Connection) -> Result<Self> {
+        let dbus = zbus::fdo::DBusProxy::new(&conn)
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create D-Bus proxy: {}", e)))?;
+        let has_owner = dbus
+            .name_has_owner(NM_BUS_NAME.try_into().expect("valid bus name"))
+            .await
+            .unwrap_or(false);
+        if !has_owner {
+            return Err(Error::monitor_unavailable(
+                "NetworkManager",
+                "org.freedesktop.NetworkManager is not running",
+                "Falling back to nmcli/iw polling for location detection",
+            ));
+        }
+
+        let nm = NetworkManagerProxy::new(&conn)
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
+        let mut state_changes = nm
+            .receive_state_changed()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to subscribe to NM state: {}", e)))?;
+        let mut connection_changes = nm.receive_active_connections_changed().await;
+
+        let changed = Arc::new(Notify::new());
+        let notify = Arc::clone(&changed);
+        let watcher = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Some(_) = state_changes.next() => {}
+                    Some(_) = connection_changes.next() => {}
+                    else => break,
+                }
+                notify.notify_one();
+            }
+        });
+
+        Ok(Self {
+            conn,
+            changed,
+            watcher,
+        })
+    }
+
+    /// Wait until NetworkManager reports a state or active-connection change
+    pub async fn wait_for_change(&self) {
+        self.changed.notified().await;
+    }
+
+    /// Get the SSID of the activated WiFi connection, if any
+    pub async fn get_ssid(&self) -> Result<Option<String>> {
+        Ok(self.get_network().await?.ssid)
+    }
+
+    /// Read the SSID, BSSID and activated connections from NetworkManager
+    /// Gateway and wired-interface fields are left empty
+    pub async fn get_network(&self) -> Result<NetworkFingerprint> {
+        let nm = NetworkManagerProxy::builder(&self.conn)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
+
+        let active_connections = nm.active_connections().await.map_err(|e| {
+            Error::ipc_protocol(format!("Failed to read active connections: {}", e))
+        })?;
+
+        let mut network = NetworkFingerprint::default();
+        for path in active_connections {
+            let active = ActiveConnectionProxy::builder(&self.conn)
+                .path(path)
+                .map_err(|e| Error::ipc_protocol(format!("Invalid object path: {}", e)))?
+                .cache_properties(CacheProperties::No)
+                .build()
+                .await
+                .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
+
+            // Connections can disappear between listing and querying, skip them
+            let Ok(connection_type) = active.connection_type().await else {
+                continue;
+            };
+            if active.state().await.ok() != Some(NM_ACTIVE_CONNECTION_ACTIVATED) {
+                continue;
+            }
+
+            if let (Ok(id), Ok(uuid)) = (active.id().await, active.uuid().await) {
+                network
+                    .connections
+                    .push(ActiveNetworkConnection { id, uuid });
+            }
+
+            if connection_type != "802-11-wireless" || network.ssid.is_some() {
+                continue;
+            }
+
+            let Ok(ap_path) = active.specific_object().await else {
+                continue;
+            };
+            if ap_path.as_str() == "/" {
+                continue;
+            }
+
+            let ap = AccessPointProxy::builder(&self.conn)
+                .path(ap_path)
+                .map_err(|e| Error::ipc_protocol(format!("Invalid object path: {}", e)))?
+                .cache_properties(CacheProperties::No)
+                .build()
+                .await
+                .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
+
+            if let Ok(ssid) = ap.ssid().await {
+                let ssid = String::from_utf8_lossy(&ssid).to_string();
+                if !ssid.is_empty() {
+                    network.ssid = Some(ssid);
+                    network.bssid = ap
+                        .hw_address()
+                        .await
+                        .ok()
+                        .filter(|mac| !mac.is_empty())
+                        .map(|mac| mac.to_lowercase());
+                }
+            }
+        }
+
+        Ok(network)
+    }
+}
+
+impl Drop for NetworkManagerBackend {
+    fn drop(&mut self) {
+        self.watcher.abort();
+    }
+}
+
+/// Look up the MAC address of the IPv4 default gateway
+/// Reads `<proc_root>/net/route` for the default route and `<proc_root>/net/arp` for its neighbour
+fn read_gateway_mac(proc_root: &Path) -> Option<String> {
+    let routes = fs::read_to_string(proc_root.join("net/route")).ok()?;
+    let gateway = routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        let (destination, gateway, flags) = (fields.get(1)?, fields.get(2)?, fields.get(3)?);
+        let flags = u32::from_str_radix(flags, 16).ok()?;
+        if *destination != "00000000" || flags & RTF_GATEWAY == 0 {
+            return None;
+        }
+        // The kernel prints the address as a host-endian u32 of network-order bytes
+        let gateway = u32::from_str_radix(gateway, 16).ok()?;
+        Some(Ipv4Addr::from(gateway.to_ne_bytes()))
+    })?;
+
+    let arp = fs::read_to_string(proc_root.join("net/arp")).ok()?;
+    arp.lines().skip(1).find_map(|line| {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        let (ip, mac) = (fields.first()?, fields.get(3)?);
+        if ip.parse::<Ipv4Addr>().ok()? != gateway || *mac == "00:00:00:00:00:00" {
+            return None;
+        }
+        Some(mac.to_lowercase())
+    })
+}
+
+/// List physical Ethernet interfaces that have a carrier (cable plugged in)
+fn read_wired_interfaces(net_root: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(net_root) else {
+        return Vec::new();
+    };
+
+    let read = |dir: &Path, file: &str| {
+        fs::read_to_string(dir.join(file))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+
+    let mut interfaces: Vec<String> = entries
+        .flatten()
+        .filter(|entry| {
+            let dir = entry.path();
+            // Virtual interfaces (bridges, veth, docker) have no backing device
+            dir.join("device").exists()
+                && !dir.join("wireless").exists()
+                && read(&dir, "type") == ARPHRD_ETHER.to_string()
+                && read(&dir, "carrier") == "1"
+        })
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    interfaces.sort();
+    interfaces
+}
+
+// nl80211 constants (linux/netlink.h, linux/genetlink.h, linux/nl80211.h)
+const NLMSG_HDRLEN: usize = 16;
+const GENL_HDRLEN: usize = 4;
+const NLA_HDRLEN: usize = 4;
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_ACK: u16 = 0x4;
+const NLM_F_DUMP: u16 = 0x300;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+const NL80211_CMD_GET_INTERFACE: u8 = 5;
+const NL80211_CMD_GET_STATION: u8 = 17;
+const NL80211_ATTR_IFINDEX: u16 = 3;
+const NL80211_ATTR_IFNAME: u16 = 4;
+const NL80211_ATTR_IFTYPE: u16 = 5;
+const NL80211_ATTR_MAC: u16 = 6;
+const NL80211_ATTR_SSID: u16 = 52;
+const NL80211_IFTYPE_STATION: u32 = 2;
+
+/// A wireless interface as reported by nl80211
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WirelessInterface {
+    pub ifindex: u32,
+    pub name: String,
+    /// SSID of the associated network (station interfaces only)
+    pub ssid: Option<String>,
+    /// MAC address of the associated access point, as aa:bb:cc:dd:ee:ff
+    pub bssid: Option<String>,
+}
+
+/// Reads the associated SSID/BSSID directly from the kernel via nl80211
+/// Works without NetworkManager, nmcli or iw
+pub struct Nl80211Backend {
+    family_id: u16,
+}
+
+impl Nl80211Backend {
+    /// Resolve the nl80211 generic netlink family
+    /// Fails if the kernel has no cfg80211 support (e.g. in containers)
+    pub fn new() -> Result<Self> {
+        let socket = NetlinkSocket::open()?;
+        let request = build_genl_request(
+            GENL_ID_CTRL,
+            NLM_F_REQUEST | NLM_F_ACK,
+            1,
+            CTRL_CMD_GETFAMILY,
+            &[(CTRL_ATTR_FAMILY_NAME, b"nl80211\0".as_slice())],
+        );
+
+        let mut family_id = None;
+        socket.transact(&request, |payload| {
+            for (kind, value) in parse_attributes(payload) {
+                if kind == CTRL_ATTR_FAMILY_ID && value.len() >= 2 {
+                    family_id = Some(u16::from_ne_bytes([value[0], value[1]]));
+                }
+            }
+        })?;
+
+        family_id
+            .map(|family_id| Self { family_id })
+            .ok_or_else(|| {
+                Error::monitor_unavailable(
+                    "nl80211",
+                    "Kernel did not report the nl80211 family",
+                    "Native WiFi detection will be disabled",
+                )
+            })
+    }
+
+    /// List wireless interfaces with their associated SSID and BSSID
+    pub fn interfaces(&self) -> Result<Vec<WirelessInterface>> {
+        let socket = NetlinkSocket::open()?;
+        let request = build_genl_request(
+            self.family_id,
+            NLM_F_REQUEST | NLM_F_DUMP,
+            1,
+            NL80211_CMD_GET_INTERFACE,
+            &[],
+        );
+
+        let mut interfaces = Vec::new();
+        socket.transact(&request, |payload| {
+            if let Some(interface) = parse_interface(payload) {
+                interfaces.push(interface);
+            }
+        })?;
+
+        for interface in &mut interfaces {
+            if interface.ssid.is_some() {
+                // An interface going down must not hide the others
+                interface.bssid = self
+                    .station_bssid(&socket, interface.ifindex)
+                    .unwrap_or_else(|e| {
+                        eprintln!("nl80211: no station info for {}: {}", interface.name, e);
+                        None
+                    });
+            }
+        }
+
+        Ok(interfaces)
+    }
+
+    /// Get the SSID of the first associated station interface
+    pub fn get_ssid(&self) -> Result<Option<String>> {
+        Ok(self
+            .interfaces()?
+            .into_iter()
+            .find_map(|interface| interface.ssid))
+    }
+
+    /// Get the MAC of the access point a station interface is associated with
+    fn station_bssid(&self, socket: &NetlinkSocket, ifindex: u32) -> Result<Option<String>> {
+        let ifindex = ifindex.to_ne_bytes();
+        let request = build_genl_request(
+            self.family_id,
+            NLM_F_REQUEST | NLM_F_DUMP,
+            2,
+            NL80211_CMD_GET_STATION,
+            &[(NL80211_ATTR_IFINDEX, ifindex.as_slice())],
+        );
+
+        let mut bssid = None;
+        socket.transact(&request, |payload| {
+            for (kind, value) in parse_attributes(payload) {
+                if kind == NL80211_ATTR_MAC && bssid.is_none() {
+                    bssid = format_mac(value);
+                }
+            }
+        })?;
+
+        Ok(bssid)
+    }
+}
+
+/// Parse a GET_INTERFACE reply payload (after the generic netlink header)
+fn parse_interface(payload: &[u8]) -> Option<WirelessInterface> {
+    let mut ifindex = None;
+    let mut name = None;
+    let mut iftype = None;
+    let mut ssid = None;
+
+    for (kind, value) in parse_attributes(payload) {
+        match kind {
+            NL80211_ATTR_IFINDEX if value.len() >= 4 => {
+                ifindex = Some(u32::from_ne_bytes([value[0], value[1], value[2], value[3]]));
+            }
+            NL80211_ATTR_IFNAME => {
+                let value = value.split(|&b| b == 0).next().unwrap_or_default();
+                name = Some(String::from_utf8_lossy(value).to_string());
+            }
+            NL80211_ATTR_IFTYPE if value.len() >= 4 => {
+                iftype = Some(u32::from_ne_bytes([value[0], value[1], value[2], value[3]]));
+            }
+            NL80211_ATTR_SSID if !value.is_empty() => {
+                ssid = Some(String::from_utf8_lossy(value).to_string());
+            }
+            _ => {}
+        }
+    }
+
+    // Only station (client) interfaces are associated with a network we can locate by
+    let ssid = ssid.filter(|_| iftype == Some(NL80211_IFTYPE_STATION));
+
+    Some(WirelessInterface {
+        ifindex: ifindex?,
+        name: name?,
+        ssid,
+        bssid: None,
+    })
+}
+
+/// Build a netlink message with a generic netlink header and attributes
+fn build_genl_request(
+    family: u16,
+    flags: u16,
+    seq: u32,
+    command: u8,
+    attributes: &[(u16, &[u8])],
+) -> Vec<u8> {
+    let mut message = vec![0u8; NLMSG_HDRLEN];
+    message.extend_from_slice(&[command, 1, 0, 0]);
+
+    for (kind, value) in attributes {
+        let len = (NLA_HDRLEN + value.len()) as u16;
+        message.extend_from_slice(&len.to_ne_bytes());
+        message.extend_from_slice(&kind.to_ne_bytes());
+        message.extend_from_slice(value);
+        message.resize(align4(message.len()), 0);
+    }
+
+    let len = message.len() as u32;
+    message[0..4].copy_from_slice(&len.to_ne_bytes());
+    message[4..6].copy_from_slice(&family.to_ne_bytes());
+    message[6..8].copy_from_slice(&flags.to_ne_bytes());
+    message[8..12].copy_from_slice(&seq.to_ne_bytes());
+    message
+}
+
+/// Iterate over netlink attributes as (type, payload) pairs
+fn parse_attributes(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut attributes = Vec::new();
+
+    while data.len() >= NLA_HDRLEN {
+        let len = u16::from_ne_bytes([data[0], data[1]]) as usize;
+        // Strip the NLA_F_NESTED / NLA_F_NET_BYTEORDER flags
+        let kind = u16::from_ne_bytes([data[2], data[3]]) & 0x3fff;
+        if len < NLA_HDRLEN || len > data.len() {
+            break;
+        }
+        attributes.push((kind, &data[NLA_HDRLEN..len]));
+        data = &data[align4(len).min(data.len())..];
+    }
+
+    attributes
+}
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn format_mac(bytes: &[u8]) -> Option<String> {
+    if bytes.len() != 6 {
+        return None;
+    }
+    Some(
+        bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+/// Minimal blocking NETLINK_GENERIC socket
+struct NetlinkSocket {
+    fd: OwnedFd,
+}
+
+impl NetlinkSocket {
+    fn open() -> Result<Self> {
+        let fd = socket(
+            AddressFamily::Netlink,
+            SockType::Raw,
+            SockFlag::SOCK_CLOEXEC,
+            SockProtocol::NetlinkGeneric,
+        )
+        .map_err(|e| Error::Io(e.into()))?;
+        // Port ID 0 lets the kernel assign one
+        bind(fd.as_raw_fd(), &NetlinkAddr::new(0, 0)).map_err(|e| Error::Io(e.into()))?;
+
+        // Don't let a misbehaving kernel reply stall the daemon loop
+        setsockopt(&fd, sockopt::ReceiveTimeout, &TimeVal::new(1, 0))
+            .map_err(|e| Error::Io(e.into()))?;
+
+        Ok(Self { fd })
+    }
+
+    /// Send a request and feed every reply payload (after the genl header) to `handle`
+    /// until the kernel acknowledges the request or finishes the dump
+    /// Requests must set NLM_F_ACK or NLM_F_DUMP, otherwise this waits for the receive timeout
+    fn transact(&self, request: &[u8], mut handle: impl FnMut(&[u8])) -> Result<()> {
+        send(self.fd.as_raw_fd(), request, MsgFlags::empty()).map_err(|e| Error::Io(e.into()))?;
+
+        let mut buffer = vec![0u8; 32 * 1024];
+
+        loop {
+            let received = recv(self.fd.as_raw_fd(), &mut buffer, MsgFlags::empty())
+                .map_err(|e| Error::Io(e.into()))?;
+
+            let mut data = &buffer[..received];
+            while data.len() >= NLMSG_HDRLEN {
+                let len = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]) as usize;
+                let kind = u16::from_ne_bytes([data[4], data[5]]);
+                if len < NLMSG_HDRLEN || len > data.len() {
+                    return Err(Error::Parse("Truncated netlink message".to_string()));
+                }
+                let payload = &data[NLMSG_HDRLEN..len];
+
+                match kind {
+                    NLMSG_DONE => return Ok(()),
+                    NLMSG_ERROR => {
+                        let code = payload
+                            .get(..4)
+                            .map(|b| i32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                            .unwrap_or(0);
+                        if code == 0 {
+                            // Acknowledgement
+                            return Ok(());
+                        }
+                        return Err(Error::Io(io::Error::from_raw_os_error(-code)));
+                    }
+                    _ => {
+                        if payload.len() >= GENL_HDRLEN {
+                            handle(&payload[GENL_HDRLEN..]);
+                        }
+                    }
+                }
+
+                data = &data[align4(len).min(data.len())..];
+            }
+        }
+    }
+}
+
+pub struct LocationDetector {
+    network_manager: Option<NetworkManagerBackend>,
+    nl80211: Option<Nl80211Backend>,
+}
 
 impl Default for LocationDetector {
     fn default() -> Self {
@@ -11,13 +762,70 @@ impl Default for LocationDetector {
 }
 
 impl LocationDetector {
+    /// Create a detector that uses the nmcli/iw command-line tools, falling back to nl80211
     pub fn new() -> Self {
-        Self
+        Self {
+            network_manager: None,
+            nl80211: Nl80211Backend::new().ok(),
+        }
+    }
+
+    /// Create a detector that prefers NetworkManager on the system bus
+    /// Falls back to command-line tools if NetworkManager is unavailable
+    pub async fn with_network_manager() -> Self {
+        match Connection::system().await {
+            Ok(conn) => Self::with_connection(conn).await,
+            Err(e) => {
+                eprintln!("LocationDetector: D-Bus system bus unavailable: {}", e);
+                Self::new()
+            }
+        }
+    }
+
+    /// Create a detector using NetworkManager on an existing D-Bus connection
+    pub async fn with_connection(conn: Connection) -> Self {
+        match NetworkManagerBackend::new(conn).await {
+            Ok(backend) => Self {
+                network_manager: Some(backend),
+                ..Self::new()
+            },
+            Err(e) => {
+                eprintln!("LocationDetector: {}", e);
+                Self::new()
+            }
+        }
+    }
+
+    /// Whether location changes are reported via wait_for_change instead of polling
+    pub fn is_event_driven(&self) -> bool {
+        self.network_manager.is_some()
+    }
+
+    /// Wait until the network state changes
+    /// Never resolves when no event-driven backend is available
+    pub async fn wait_for_change(&self) {
+        match self.network_manager {
+            Some(ref backend) => backend.wait_for_change().await,
+            None => std::future::pending().await,
+        }
     }
 
     /// Get current WiFi SSID
-    pub fn get_current_ssid(&self) -> Result<Option<String>> {
-        // Try nmcli first (NetworkManager)
+    pub async fn get_current_ssid(&self) -> Result<Option<String>> {
+        // Prefer NetworkManager over D-Bus; it is authoritative when running
+        if let Some(ref backend) = self.network_manager {
+            match backend.get_ssid().await {
+                Ok(ssid) => return Ok(ssid),
+                Err(e) => eprintln!("NetworkManager query failed: {}, trying CLI tools...", e),
+            }
+        }
+
+        self.get_ssid_fallback()
+    }
+
+    /// Get the SSID without NetworkManager's D-Bus API: nmcli, iw, then nl80211
+    fn get_ssid_fallback(&self) -> Result<Option<String>> {
+        // Try nmcli (NetworkManager)
         if let Ok(ssid) = self.get_ssid_nmcli() {
             return Ok(Some(ssid));
         }
@@ -27,10 +835,53 @@ impl LocationDetector {
             return Ok(Some(ssid));
         }
 
+        // Ask the kernel directly when no userspace tools are installed
+        if let Some(ref backend) = self.nl80211 {
+            return backend.get_ssid();
+        }
+
         // No WiFi connection or unable to detect
         Ok(None)
     }
 
+    /// Collect every location signal: WiFi, default gateway, NM connections and wired links
+    pub async fn get_fingerprint(&self) -> Result<NetworkFingerprint> {
+        let network = match self.network_manager {
+            Some(ref backend) => backend
+                .get_network()
+                .await
+                .inspect_err(|e| {
+                    eprintln!("NetworkManager query failed: {}, trying CLI tools...", e)
+                })
+                .ok(),
+            None => None,
+        };
+        let mut fingerprint = match network {
+            Some(network) => network,
+            None => NetworkFingerprint {
+                ssid: self.get_ssid_fallback()?,
+                ..Default::default()
+            },
+        };
+
+        // nl80211 knows the BSSID even when NetworkManager or the CLI tools found the SSID
+        if fingerprint.bssid.is_none() && fingerprint.ssid.is_some() {
+            if let Some(ref backend) = self.nl80211 {
+                fingerprint.bssid = backend.interfaces().ok().and_then(|interfaces| {
+                    interfaces
+                        .into_iter()
+                        .find(|i| i.ssid == fingerprint.ssid)
+                        .and_then(|i| i.bssid)
+                });
+            }
+        }
+
+        fingerprint.gateway_mac = read_gateway_mac(Path::new(PROC_ROOT));
+        fingerprint.wired_interfaces = read_wired_interfaces(Path::new(SYS_NET_ROOT));
+
+        Ok(fingerprint)
+    }
+
     fn get_ssid_nmcli(&self) -> Result<String> {
         let output = Command::new("nmcli")
             .args(["-t", "-f", "active,ssid", "dev", "wifi"])
@@ -99,10 +950,490 @@ impl LocationDetector {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_location_detector_creation() {
+    use crate::test_support::PrivateBus;
+    use tokio::time::{timeout, Duration};
+    use zbus::interface;
+    use zbus::zvariant::ObjectPath;
+
+    #[tokio::test]
+    async fn test_location_detector_creation() {
         let detector = LocationDetector::new();
         // Just verify it can be created
-        let _ = detector.get_current_ssid();
+        let _ = detector.get_current_ssid().await;
+        assert!(!detector.is_event_driven());
+    }
+
+    fn network(ssid: &str) -> NetworkFingerprint {
+        NetworkFingerprint {
+            ssid: Some(ssid.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_debouncer_first_network_is_immediate() {
+        let mut debouncer = LocationDebouncer::new();
+        let now = Instant::now();
+        let settle = Duration::from_secs(5);
+
+        assert_eq!(
+            debouncer.observe(network("home"), now, settle),
+            Some(network("home"))
+        );
+        debouncer.accept();
+        assert!(!debouncer.is_pending());
+        assert_eq!(debouncer.observe(network("home"), now, settle), None);
+    }
+
+    #[test]
+    fn test_profile_dwell() {
+        let start = Instant::now();
+        let min_dwell = Duration::from_secs(300);
+        let mut dwell = ProfileDwell::new(start);
+
+        // The profile loaded at startup is kept like any other
+        assert!(dwell.holds(min_dwell, start + Duration::from_secs(10)));
+        assert!(!dwell.holds(min_dwell, start + min_dwell));
+        assert!(!dwell.holds(Duration::ZERO, start));
+
+        // A switch restarts the count
+        let switched = start + Duration::from_secs(400);
+        dwell.reset(switched);
+        assert!(dwell.holds(min_dwell, switched + Duration::from_secs(299)));
+        assert!(!dwell.holds(min_dwell, switched + min_dwell));
+    }
+
+    #[test]
+    fn test_debouncer_settle_time() {
+        let mut debouncer = LocationDebouncer::new();
+        let start = Instant::now();
+        let settle = Duration::from_secs(5);
+        debouncer.observe(network("home"), start, settle);
+        debouncer.accept();
+
+        // New network is held back until it has been stable for the settle time
+        let at = |secs| start + Duration::from_secs(secs);
+        assert_eq!(debouncer.observe(network("cafe"), at(1), settle), None);
+        assert!(debouncer.is_pending());
+        assert_eq!(debouncer.observe(network("cafe"), at(5), settle), None);
+        assert_eq!(
+            debouncer.observe(network("cafe"), at(6), settle),
+            Some(network("cafe"))
+        );
+
+        // Reported again until accepted (e.g. while a dwell time blocks the switch)
+        assert_eq!(
+            debouncer.observe(network("cafe"), at(7), settle),
+            Some(network("cafe"))
+        );
+        debouncer.accept();
+        assert_eq!(debouncer.observe(network("cafe"), at(8), settle), None);
+    }
+
+    #[test]
+    fn test_debouncer_brief_disconnect() {
+        let mut debouncer = LocationDebouncer::new();
+        let start = Instant::now();
+        let settle = Duration::from_secs(5);
+        let at = |secs| start + Duration::from_secs(secs);
+        debouncer.observe(network("home"), start, settle);
+        debouncer.accept();
+
+        // A short drop and reconnect never reaches the settle time
+        assert_eq!(
+            debouncer.observe(NetworkFingerprint::default(), at(10), settle),
+            None
+        );
+        assert_eq!(debouncer.observe(network("home"), at(12), settle), None);
+        assert!(!debouncer.is_pending());
+
+        // Flapping restarts the settle timer
+        assert_eq!(debouncer.observe(network("a"), at(20), settle), None);
+        assert_eq!(debouncer.observe(network("b"), at(24), settle), None);
+        assert_eq!(debouncer.observe(network("b"), at(26), settle), None);
+        assert_eq!(
+            debouncer.observe(network("b"), at(29), settle),
+            Some(network("b"))
+        );
+    }
+
+    fn write_file(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_read_gateway_mac() {
+        let root = tempfile::TempDir::new().unwrap();
+        // Default route via 192.168.1.1 on wlan0, printed as a host-endian u32
+        let gateway = u32::from_ne_bytes([192, 168, 1, 1]);
+        write_file(
+            &root.path().join("net/route"),
+            &format!(
+                "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\n\
+                 wlan0\t0001A8C0\t00000000\t0001\t0\t0\t600\t00FFFFFF\n\
+                 wlan0\t00000000\t{:08X}\t0003\t0\t0\t600\t00000000\n",
+                gateway
+            ),
+        );
+        write_file(
+            &root.path().join("net/arp"),
+            "IP address       HW type     Flags       HW address            Mask     Device\n\
+             192.168.1.23     0x1         0x2         11:22:33:44:55:66     *        wlan0\n\
+             192.168.1.1      0x1         0x2         DE:AD:BE:EF:00:01     *        wlan0\n",
+        );
+
+        assert_eq!(
+            read_gateway_mac(root.path()),
+            Some("de:ad:be:ef:00:01".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_gateway_mac_without_default_route() {
+        let root = tempfile::TempDir::new().unwrap();
+        write_file(
+            &root.path().join("net/route"),
+            "Iface\tDestination\tGateway \tFlags\n\
+             wlan0\t0001A8C0\t00000000\t0001\n",
+        );
+        write_file(&root.path().join("net/arp"), "IP address HW type\n");
+        assert_eq!(read_gateway_mac(root.path()), None);
+        assert_eq!(read_gateway_mac(Path::new("/nonexistent")), None);
+    }
+
+    #[test]
+    fn test_read_wired_interfaces() {
+        let root = tempfile::TempDir::new().unwrap();
+        let interface = |name: &str, carrier: &str, device: bool, wireless: bool| {
+            let dir = root.path().join(name);
+            write_file(&dir.join("type"), "1\n");
+            write_file(&dir.join("carrier"), carrier);
+            if device {
+                fs::create_dir_all(dir.join("device")).unwrap();
+            }
+            if wireless {
+                fs::create_dir_all(dir.join("wireless")).unwrap();
+            }
+        };
+        interface("enp0s31f6", "1\n", true, false);
+        interface("enx00e04c", "0\n", true, false);
+        interface("wlan0", "1\n", true, true);
+        interface("docker0", "1\n", false, false);
+
+        assert_eq!(read_wired_interfaces(root.path()), vec!["enp0s31f6"]);
+    }
+
+    #[test]
+    fn test_fingerprint_display() {
+        assert_eq!(NetworkFingerprint::default().to_string(), "no network");
+        let fingerprint = NetworkFingerprint {
+            ssid: Some("eduroam".to_string()),
+            bssid: Some("aa:bb:cc:00:11:22".to_string()),
+            wired_interfaces: vec!["eth0".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            fingerprint.to_string(),
+            "SSID eduroam (aa:bb:cc:00:11:22), wired eth0"
+        );
+    }
+
+    fn attribute(kind: u16, value: &[u8]) -> Vec<u8> {
+        let mut attr = Vec::new();
+        attr.extend_from_slice(&((NLA_HDRLEN + value.len()) as u16).to_ne_bytes());
+        attr.extend_from_slice(&kind.to_ne_bytes());
+        attr.extend_from_slice(value);
+        attr.resize(align4(attr.len()), 0);
+        attr
+    }
+
+    #[test]
+    fn test_build_genl_request() {
+        let request = build_genl_request(
+            GENL_ID_CTRL,
+            NLM_F_REQUEST | NLM_F_ACK,
+            7,
+            CTRL_CMD_GETFAMILY,
+            &[(CTRL_ATTR_FAMILY_NAME, b"nl80211\0".as_slice())],
+        );
+
+        // Header + genl header + 4-byte attr header + 8 bytes of name
+        assert_eq!(request.len(), 32);
+        assert_eq!(u32::from_ne_bytes(request[0..4].try_into().unwrap()), 32);
+        assert_eq!(u16::from_ne_bytes([request[4], request[5]]), GENL_ID_CTRL);
+        assert_eq!(u16::from_ne_bytes([request[6], request[7]]), 0x5);
+        assert_eq!(u32::from_ne_bytes(request[8..12].try_into().unwrap()), 7);
+        assert_eq!(request[16], CTRL_CMD_GETFAMILY);
+
+        let attributes = parse_attributes(&request[NLMSG_HDRLEN + GENL_HDRLEN..]);
+        assert_eq!(
+            attributes,
+            vec![(CTRL_ATTR_FAMILY_NAME, b"nl80211\0".as_slice())]
+        );
+    }
+
+    #[test]
+    fn test_parse_attributes_truncated() {
+        let mut data = attribute(NL80211_ATTR_IFINDEX, &3u32.to_ne_bytes());
+        // Attribute claiming more bytes than available is dropped
+        data.extend_from_slice(&[40, 0, 4, 0, b'w']);
+        let attributes = parse_attributes(&data);
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(attributes[0].0, NL80211_ATTR_IFINDEX);
+    }
+
+    #[test]
+    fn test_parse_station_interface() {
+        let mut payload = attribute(NL80211_ATTR_IFINDEX, &3u32.to_ne_bytes());
+        payload.extend(attribute(NL80211_ATTR_IFNAME, b"wlan0\0"));
+        payload.extend(attribute(
+            NL80211_ATTR_IFTYPE,
+            &NL80211_IFTYPE_STATION.to_ne_bytes(),
+        ));
+        payload.extend(attribute(NL80211_ATTR_SSID, b"Home WiFi"));
+
+        assert_eq!(
+            parse_interface(&payload),
+            Some(WirelessInterface {
+                ifindex: 3,
+                name: "wlan0".to_string(),
+                ssid: Some("Home WiFi".to_string()),
+                bssid: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_access_point_interface() {
+        // Hotspot (AP mode) SSIDs are our own network, not a location
+        let mut payload = attribute(NL80211_ATTR_IFINDEX, &4u32.to_ne_bytes());
+        payload.extend(attribute(NL80211_ATTR_IFNAME, b"ap0\0"));
+        payload.extend(attribute(NL80211_ATTR_IFTYPE, &3u32.to_ne_bytes()));
+        payload.extend(attribute(NL80211_ATTR_SSID, b"Hotspot"));
+
+        let interface = parse_interface(&payload).unwrap();
+        assert_eq!(interface.name, "ap0");
+        assert_eq!(interface.ssid, None);
+
+        // Missing ifindex/name makes the reply unusable
+        assert_eq!(parse_interface(&attribute(NL80211_ATTR_SSID, b"x")), None);
+    }
+
+    #[test]
+    fn test_format_mac() {
+        assert_eq!(
+            format_mac(&[0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0xff]),
+            Some("00:1a:2b:3c:4d:ff".to_string())
+        );
+        assert_eq!(format_mac(&[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn test_nl80211_backend() {
+        // cfg80211 is often missing in containers and CI
+        let Ok(backend) = Nl80211Backend::new() else {
+            eprintln!("nl80211 not available, skipping");
+            return;
+        };
+        let interfaces = backend.interfaces().unwrap();
+        for interface in interfaces {
+            assert!(!interface.name.is_empty());
+        }
+    }
+
+    struct FakeNetworkManager {
+        active_connections: Vec<OwnedObjectPath>,
+    }
+
+    #[interface(name = "org.freedesktop.NetworkManager")]
+    impl FakeNetworkManager {
+        #[zbus(property)]
+        fn active_connections(&self) -> Vec<OwnedObjectPath> {
+            self.active_connections.clone()
+        }
+    }
+
+    struct FakeActiveConnection {
+        connection_type: String,
+        specific_object: OwnedObjectPath,
+        state: u32,
+        id: String,
+        uuid: String,
+    }
+
+    #[interface(name = "org.freedesktop.NetworkManager.Connection.Active")]
+    impl FakeActiveConnection {
+        #[zbus(property, name = "Type")]
+        fn connection_type(&self) -> String {
+            self.connection_type.clone()
+        }
+
+        #[zbus(property)]
+        fn specific_object(&self) -> OwnedObjectPath {
+            self.specific_object.clone()
+        }
+
+        #[zbus(property)]
+        fn state(&self) -> u32 {
+            self.state
+        }
+
+        #[zbus(property)]
+        fn id(&self) -> String {
+            self.id.clone()
+        }
+
+        #[zbus(property)]
+        fn uuid(&self) -> String {
+            self.uuid.clone()
+        }
+    }
+
+    struct FakeAccessPoint {
+        ssid: Vec<u8>,
+    }
+
+    #[interface(name = "org.freedesktop.NetworkManager.AccessPoint")]
+    impl FakeAccessPoint {
+        #[zbus(property)]
+        fn ssid(&self) -> Vec<u8> {
+            self.ssid.clone()
+        }
+
+        #[zbus(property)]
+        fn hw_address(&self) -> String {
+            "AA:BB:CC:00:11:22".to_string()
+        }
+    }
+
+    fn path(p: &str) -> OwnedObjectPath {
+        ObjectPath::try_from(p).unwrap().into()
+    }
+
+    /// Start a mock NetworkManager with one wired and one WiFi connection
+    async fn spawn_network_manager(bus: &PrivateBus, ssid: &str) -> Connection {
+        zbus::connection::Builder::address(bus.address())
+            .unwrap()
+            .name(NM_BUS_NAME)
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/NetworkManager",
+                FakeNetworkManager {
+                    active_connections: vec![
+                        path("/org/freedesktop/NetworkManager/ActiveConnection/1"),
+                        path("/org/freedesktop/NetworkManager/ActiveConnection/2"),
+                    ],
+                },
+            )
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/NetworkManager/ActiveConnection/1",
+                FakeActiveConnection {
+                    connection_type: "802-3-ethernet".to_string(),
+                    specific_object: path("/"),
+                    state: NM_ACTIVE_CONNECTION_ACTIVATED,
+                    id: "Wired connection 1".to_string(),
+                    uuid: "8a2f3c4e-0000-4000-8000-000000000001".to_string(),
+                },
+            )
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/NetworkManager/ActiveConnection/2",
+                FakeActiveConnection {
+                    connection_type: "802-11-wireless".to_string(),
+                    specific_object: path("/org/freedesktop/NetworkManager/AccessPoint/7"),
+                    state: NM_ACTIVE_CONNECTION_ACTIVATED,
+                    id: ssid.to_string(),
+                    uuid: "8a2f3c4e-0000-4000-8000-000000000002".to_string(),
+                },
+            )
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/NetworkManager/AccessPoint/7",
+                FakeAccessPoint {
+                    ssid: ssid.as_bytes().to_vec(),
+                },
+            )
+            .unwrap()
+            .build()
+            .await
+            .expect("Failed to start mock NetworkManager")
+    }
+
+    #[tokio::test]
+    async fn test_network_manager_fingerprint() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let _nm = spawn_network_manager(&bus, "eduroam").await;
+        let detector = LocationDetector::with_connection(bus.connect().await).await;
+
+        let fingerprint = detector.get_fingerprint().await.unwrap();
+        assert_eq!(fingerprint.ssid.as_deref(), Some("eduroam"));
+        assert_eq!(fingerprint.bssid.as_deref(), Some("aa:bb:cc:00:11:22"));
+        let names: Vec<_> = fingerprint.connections.iter().map(|c| &c.id).collect();
+        assert_eq!(names, ["Wired connection 1", "eduroam"]);
+    }
+
+    #[tokio::test]
+    async fn test_network_manager_unavailable() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let detector = LocationDetector::with_connection(bus.connect().await).await;
+        assert!(!detector.is_event_driven());
+    }
+
+    #[tokio::test]
+    async fn test_network_manager_ssid() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let _nm = spawn_network_manager(&bus, "OfficeNetwork").await;
+        let detector = LocationDetector::with_connection(bus.connect().await).await;
+
+        assert!(detector.is_event_driven());
+        assert_eq!(
+            detector.get_current_ssid().await.unwrap(),
+            Some("OfficeNetwork".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_network_manager_disconnect_event() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let nm = spawn_network_manager(&bus, "HomeWiFi").await;
+        let detector = LocationDetector::with_connection(bus.connect().await).await;
+        assert_eq!(
+            detector.get_current_ssid().await.unwrap(),
+            Some("HomeWiFi".to_string())
+        );
+
+        // Drop the WiFi connection and announce the change
+        let iface = nm
+            .object_server()
+            .interface::<_, FakeNetworkManager>("/org/freedesktop/NetworkManager")
+            .await
+            .unwrap();
+        {
+            let mut fake = iface.get_mut().await;
+            fake.active_connections
+                .retain(|p| p.as_str().ends_with("ActiveConnection/1"));
+            fake.active_connections_changed(iface.signal_emitter())
+                .await
+                .unwrap();
+        }
+
+        timeout(Duration::from_secs(5), detector.wait_for_change())
+            .await
+            .expect("No change notification from NetworkManager");
+        assert_eq!(detector.get_current_ssid().await.unwrap(), None);
     }
 }
@@ -0,0 +1,576 @@
+// Meeting detection (webcam use and screen sharing)
+// Webcams are detected by scanning /proc/*/fd for open /dev/video* handles,
+// screen sharing by following the desktop portal's ScreenCast sessions on the session bus
+use crate::{Error, Result};
+use futures_lite::StreamExt;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use zbus::message::Type as MessageType;
+use zbus::zvariant::{ObjectPath, Value};
+use zbus::{Connection, MatchRule, Message};
+
+/// Object path prefix of the desktop portal, including its request and session objects
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+/// Request objects the portal answers asynchronous calls on
+const PORTAL_REQUEST_PATH: &str = "/org/freedesktop/portal/desktop/request";
+const SCREENCAST_INTERFACE: &str = "org.freedesktop.portal.ScreenCast";
+const REQUEST_INTERFACE: &str = "org.freedesktop.portal.Request";
+const SESSION_INTERFACE: &str = "org.freedesktop.portal.Session";
+
+/// How long a /proc scan for open webcams is reused
+const WEBCAM_SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct MeetingDetector {
+    proc_root: PathBuf,
+    dev_root: PathBuf,
+    screencasts: Option<ScreenCastMonitor>,
+    /// Last /proc scan and its result
+    webcam_scan: Mutex<Option<(Instant, bool)>>,
+}
+
+impl MeetingDetector {
+    /// Create a detector using /proc, /dev and the D-Bus session bus
+    /// Screen-share detection is disabled if the session bus can't be monitored
+    pub async fn new() -> Self {
+        let screencasts = match Connection::session().await {
+            Ok(conn) => ScreenCastMonitor::new(conn).await,
+            Err(e) => Err(Error::ipc_protocol(format!(
+                "D-Bus session unavailable: {}",
+                e
+            ))),
+        };
+        let screencasts = screencasts
+            .map_err(|e| eprintln!("MeetingDetector: {}, screen sharing detection disabled", e))
+            .ok();
+
+        Self::with_sources("/proc", "/dev", screencasts)
+    }
+
+    /// Create a detector with custom proc and dev roots and an optional screencast monitor
+    pub fn with_sources(
+        proc_root: impl AsRef<Path>,
+        dev_root: impl AsRef<Path>,
+        screencasts: Option<ScreenCastMonitor>,
+    ) -> Self {
+        Self {
+            proc_root: proc_root.as_ref().to_path_buf(),
+            dev_root: dev_root.as_ref().to_path_buf(),
+            screencasts,
+            webcam_scan: Mutex::new(None),
+        }
+    }
+
+    /// Check whether a meeting appears to be in progress
+    pub fn is_meeting_active(&self, detect_webcam: bool, detect_screen_sharing: bool) -> bool {
+        if detect_webcam && self.is_webcam_in_use() {
+            return true;
+        }
+
+        detect_screen_sharing && self.is_screen_shared()
+    }
+
+    /// Check whether any process holds an open /dev/video* handle
+    ///
+    /// Scanning every open file descriptor is expensive, so a result is reused for
+    /// a few seconds and the scan is skipped on machines without video devices.
+    pub fn is_webcam_in_use(&self) -> bool {
+        let mut last_scan = self.webcam_scan.lock().unwrap();
+        if let Some((at, in_use)) = *last_scan {
+            if at.elapsed() < WEBCAM_SCAN_INTERVAL {
+                return in_use;
+            }
+        }
+
+        let in_use = self.has_video_devices() && self.scan_proc_for_video();
+        *last_scan = Some((Instant::now(), in_use));
+        in_use
+    }
+
+    /// Whether any /dev/video* node exists
+    fn has_video_devices(&self) -> bool {
+        fs::read_dir(&self.dev_root).is_ok_and(|entries| {
+            entries
+                .flatten()
+                .any(|entry| Self::is_video_device(&Path::new("/dev").join(entry.file_name())))
+        })
+    }
+
+    fn scan_proc_for_video(&self) -> bool {
+        let Ok(processes) = fs::read_dir(&self.proc_root) else {
+            return false;
+        };
+
+        for process in processes.flatten() {
+            let is_pid = process
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.bytes().all(|b| b.is_ascii_digit()));
+            if !is_pid {
+                continue;
+            }
+
+            // Processes of other users are not readable, skip them
+            let Ok(fds) = fs::read_dir(process.path().join("fd")) else {
+                continue;
+            };
+
+            for fd in fds.flatten() {
+                if let Ok(target) = fs::read_link(fd.path()) {
+                    if Self::is_video_device(&target) {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    fn is_video_device(path: &Path) -> bool {
+        path.to_str()
+            .and_then(|p| p.strip_prefix("/dev/video"))
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+    }
+
+    /// Check whether the desktop portal is streaming the screen to any application
+    pub fn is_screen_shared(&self) -> bool {
+        self.screencasts
+            .as_ref()
+            .is_some_and(ScreenCastMonitor::is_active)
+    }
+}
+
+/// A `ScreenCast.Start` call waiting for the portal's response
+#[derive(Debug)]
+struct PendingStart {
+    /// Unique bus name of the application
+    app: String,
+    /// Request object the portal answers on, known when the app passed a `handle_token`
+    request: Option<String>,
+    session: String,
+}
+
+/// Portal screencast sessions, built from the messages the portal exchanges with apps
+#[derive(Debug, Default)]
+struct ScreenCasts {
+    pending: Vec<PendingStart>,
+    /// Sessions with running streams and the application owning each
+    active: HashMap<String, String>,
+}
+
+impl ScreenCasts {
+    /// Update the sessions from a monitored message
+    fn handle(&mut self, msg: &Message) {
+        let header = msg.header();
+        let (Some(interface), Some(member), Some(path)) =
+            (header.interface(), header.member(), header.path())
+        else {
+            return;
+        };
+        let sender = header.sender().map(|name| name.to_string());
+        let body = msg.body();
+
+        match (header.message_type(), interface.as_str(), member.as_str()) {
+            (MessageType::MethodCall, SCREENCAST_INTERFACE, "Start") => {
+                let Some(app) = sender else { return };
+                let Ok((session, _parent_window, options)) =
+                    body.deserialize::<(ObjectPath<'_>, &str, HashMap<&str, Value<'_>>)>()
+                else {
+                    return;
+                };
+                let request = options
+                    .get("handle_token")
+                    .and_then(|token| <&str>::try_from(token).ok())
+                    .map(|token| request_path(&app, token));
+                self.pending.push(PendingStart {
+                    app,
+                    request,
+                    session: session.to_string(),
+                });
+            }
+            (MessageType::Signal, REQUEST_INTERFACE, "Response") => {
+                let Ok((response, results)) = body.deserialize::<(u32, HashMap<&str, Value<'_>>)>()
+                else {
+                    return;
+                };
+                // Only Start responses carry streams; without a handle_token the request
+                // path is random, so match the app's oldest Start instead
+                let has_streams = results.contains_key("streams");
+                let app = header.destination().map(|name| name.to_string());
+                let index = self
+                    .pending
+                    .iter()
+                    .position(|start| start.request.as_deref() == Some(path.as_str()))
+                    .or_else(|| {
+                        has_streams
+                            .then(|| {
+                                self.pending.iter().position(|start| {
+                                    start.request.is_none() && Some(&start.app) == app.as_ref()
+                                })
+                            })
+                            .flatten()
+                    });
+                if let Some(index) = index {
+                    let start = self.pending.remove(index);
+                    // 0 means success, anything else that the user cancelled or it failed
+                    if response == 0 && has_streams {
+                        self.active.insert(start.session, start.app);
+                    }
+                }
+            }
+            (MessageType::Signal, SESSION_INTERFACE, "Closed")
+            | (MessageType::MethodCall, SESSION_INTERFACE, "Close") => {
+                self.active.remove(path.as_str());
+                self.pending.retain(|start| start.session != path.as_str());
+            }
+            (MessageType::Signal, "org.freedesktop.DBus", "NameOwnerChanged") => {
+                // Sessions end with the application's connection
+                if let Ok((name, _old_owner, "")) = body.deserialize::<(&str, &str, &str)>() {
+                    self.active.retain(|_, app| app != name);
+                    self.pending.retain(|start| start.app != name);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Request object the portal uses for an application's `handle_token`
+fn request_path(app: &str, token: &str) -> String {
+    let sender = app.trim_start_matches(':').replace('.', "_");
+    format!("{}/{}/{}", PORTAL_REQUEST_PATH, sender, token)
+}
+
+/// Follows portal screencast sessions by monitoring the session bus
+///
+/// Portal session objects look the same for every session type, so sessions are
+/// tracked from `ScreenCast.Start` until they are closed. Sessions started before the
+/// monitor are not seen.
+pub struct ScreenCastMonitor {
+    sessions: Arc<Mutex<ScreenCasts>>,
+    watcher: JoinHandle<()>,
+}
+
+impl ScreenCastMonitor {
+    /// Turn a dedicated bus connection into a monitor for portal screencast traffic
+    /// The connection can't be used for anything else afterwards.
+    pub async fn new(conn: Connection) -> Result<Self> {
+        let rule = |message_type, interface, member| {
+            MatchRule::builder()
+                .msg_type(message_type)
+                .interface(interface)
+                .and_then(|builder| builder.member(member))
+                .and_then(|builder| builder.path_namespace(PORTAL_PATH))
+                .map(|builder| builder.build())
+        };
+        let rules = [
+            rule(MessageType::MethodCall, SCREENCAST_INTERFACE, "Start"),
+            rule(MessageType::Signal, REQUEST_INTERFACE, "Response"),
+            rule(MessageType::Signal, SESSION_INTERFACE, "Closed"),
+            rule(MessageType::MethodCall, SESSION_INTERFACE, "Close"),
+            MatchRule::builder()
+                .msg_type(MessageType::Signal)
+                .sender("org.freedesktop.DBus")
+                .and_then(|builder| builder.member("NameOwnerChanged"))
+                .map(|builder| builder.build()),
+        ]
+        .into_iter()
+        .collect::<zbus::Result<Vec<_>>>()
+        .map_err(|e| Error::ipc_protocol(format!("Invalid match rule: {}", e)))?;
+
+        let mut messages = zbus::MessageStream::from(&conn);
+        zbus::fdo::MonitoringProxy::new(&conn)
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?
+            .become_monitor(&rules, 0)
+            .await
+            .map_err(|e| {
+                Error::ipc_protocol(format!("Failed to monitor the session bus: {}", e))
+            })?;
+
+        let sessions = Arc::new(Mutex::new(ScreenCasts::default()));
+        let shared = Arc::clone(&sessions);
+        let watcher = tokio::spawn(async move {
+            while let Some(msg) = messages.next().await {
+                if let Ok(msg) = msg {
+                    shared.lock().unwrap().handle(&msg);
+                }
+            }
+        });
+
+        Ok(Self { sessions, watcher })
+    }
+
+    /// Whether any screencast session is streaming
+    pub fn is_active(&self) -> bool {
+        !self.sessions.lock().unwrap().active.is_empty()
+    }
+}
+
+impl Drop for ScreenCastMonitor {
+    fn drop(&mut self) {
+        self.watcher.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::PrivateBus;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
+    use zbus::zvariant::OwnedObjectPath;
+
+    fn fake_proc(fds: &[(&str, &str, &str)]) -> TempDir {
+        let root = TempDir::new().unwrap();
+        for (pid, fd, target) in fds {
+            let dir = root.path().join(pid).join("fd");
+            fs::create_dir_all(&dir).unwrap();
+            symlink(target, dir.join(fd)).unwrap();
+        }
+        root
+    }
+
+    fn fake_dev(nodes: &[&str]) -> TempDir {
+        let root = TempDir::new().unwrap();
+        for node in nodes {
+            fs::write(root.path().join(node), "").unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_webcam_detected() {
+        let root = fake_proc(&[
+            ("100", "0", "/dev/null"),
+            ("200", "3", "socket:[12345]"),
+            ("300", "17", "/dev/video0"),
+        ]);
+        let dev = fake_dev(&["video0"]);
+        let detector = MeetingDetector::with_sources(root.path(), dev.path(), None);
+        assert!(detector.is_webcam_in_use());
+    }
+
+    #[test]
+    fn test_no_webcam() {
+        let root = fake_proc(&[
+            ("100", "0", "/dev/null"),
+            ("200", "4", "/dev/video-something"),
+            // Non-PID directories are ignored
+            ("self", "5", "/dev/video1"),
+        ]);
+        let dev = fake_dev(&["video1"]);
+        let detector = MeetingDetector::with_sources(root.path(), dev.path(), None);
+        assert!(!detector.is_webcam_in_use());
+    }
+
+    #[test]
+    fn test_missing_proc_root() {
+        let dev = fake_dev(&["video0"]);
+        let detector = MeetingDetector::with_sources("/nonexistent/proc", dev.path(), None);
+        assert!(!detector.is_webcam_in_use());
+    }
+
+    #[test]
+    fn test_webcam_scan_skipped_and_cached() {
+        let root = fake_proc(&[("300", "17", "/dev/video0")]);
+
+        // Without video devices /proc is not scanned
+        let dev = fake_dev(&["null", "video-loopback"]);
+        let detector = MeetingDetector::with_sources(root.path(), dev.path(), None);
+        assert!(!detector.is_webcam_in_use());
+
+        // A scan result is reused until it is a few seconds old
+        let dev = fake_dev(&["video0"]);
+        let detector = MeetingDetector::with_sources(root.path(), dev.path(), None);
+        assert!(detector.is_webcam_in_use());
+        fs::remove_dir_all(root.path().join("300")).unwrap();
+        assert!(detector.is_webcam_in_use());
+        *detector.webcam_scan.lock().unwrap() = None;
+        assert!(!detector.is_webcam_in_use());
+    }
+
+    #[test]
+    fn test_request_path() {
+        assert_eq!(
+            request_path(":1.42", "kbd1"),
+            "/org/freedesktop/portal/desktop/request/1_42/kbd1"
+        );
+    }
+
+    /// The portal's ScreenCast interface at /org/freedesktop/portal/desktop
+    ///
+    /// Like xdg-desktop-portal, `Start` answers with a `Response` signal on a request
+    /// object, and sessions are separate objects that only implement
+    /// org.freedesktop.portal.Session.
+    struct FakeScreenCast;
+
+    #[zbus::interface(name = "org.freedesktop.portal.ScreenCast")]
+    impl FakeScreenCast {
+        async fn start(
+            &self,
+            _session_handle: ObjectPath<'_>,
+            parent_window: &str,
+            options: HashMap<&str, Value<'_>>,
+            #[zbus(header)] header: zbus::message::Header<'_>,
+            #[zbus(connection)] conn: &Connection,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            let app = header.sender().unwrap().to_string();
+            let token = options
+                .get("handle_token")
+                .and_then(|token| <&str>::try_from(token).ok())
+                .unwrap_or("portal_generated_17");
+            let request = request_path(&app, token);
+
+            // The test passes "cancel" as parent window to simulate a closed dialog
+            let mut results: HashMap<&str, Value<'_>> = HashMap::new();
+            let response = if parent_window == "cancel" {
+                1u32
+            } else {
+                results.insert(
+                    "streams",
+                    Value::new(vec![(42u32, HashMap::<&str, Value<'_>>::new())]),
+                );
+                0
+            };
+            conn.emit_signal(
+                Some(app.as_str()),
+                request.as_str(),
+                REQUEST_INTERFACE,
+                "Response",
+                &(response, results),
+            )
+            .await?;
+            Ok(OwnedObjectPath::try_from(request).unwrap())
+        }
+    }
+
+    struct FakeSession;
+
+    #[zbus::interface(name = "org.freedesktop.portal.Session")]
+    impl FakeSession {
+        fn close(&self) {}
+    }
+
+    /// Wait for the monitor to catch up with the bus
+    async fn eventually(detector: &MeetingDetector, shared: bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while detector.is_screen_shared() != shared {
+            assert!(
+                Instant::now() < deadline,
+                "screen sharing never became {}",
+                shared
+            );
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_screen_share_detection() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let root = fake_proc(&[]);
+        let dev = fake_dev(&[]);
+        let monitor = ScreenCastMonitor::new(bus.connect().await).await.unwrap();
+        let detector = MeetingDetector::with_sources(root.path(), dev.path(), Some(monitor));
+        assert!(!detector.is_screen_shared());
+
+        let portal = zbus::connection::Builder::address(bus.address())
+            .unwrap()
+            .name("org.freedesktop.portal.Desktop")
+            .unwrap()
+            .serve_at(PORTAL_PATH, FakeScreenCast)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let app = bus.connect().await;
+        let app_name = app
+            .unique_name()
+            .unwrap()
+            .trim_start_matches(':')
+            .replace('.', "_");
+        let session = |token: &str| format!("{}/session/{}/{}", PORTAL_PATH, app_name, token);
+        for token in ["cast1", "cast2", "cast3"] {
+            portal
+                .object_server()
+                .at(session(token), FakeSession)
+                .await
+                .unwrap();
+        }
+
+        let start = |session: String, parent_window: &'static str, token: Option<&'static str>| {
+            let app = app.clone();
+            async move {
+                let mut options: HashMap<&str, Value<'_>> = HashMap::new();
+                if let Some(token) = token {
+                    options.insert("handle_token", Value::from(token));
+                }
+                app.call_method(
+                    Some("org.freedesktop.portal.Desktop"),
+                    PORTAL_PATH,
+                    Some(SCREENCAST_INTERFACE),
+                    "Start",
+                    &(
+                        ObjectPath::try_from(session).unwrap(),
+                        parent_window,
+                        options,
+                    ),
+                )
+                .await
+                .unwrap();
+            }
+        };
+        let close = |session: String| {
+            let app = app.clone();
+            async move {
+                app.call_method(
+                    Some("org.freedesktop.portal.Desktop"),
+                    session.as_str(),
+                    Some(SESSION_INTERFACE),
+                    "Close",
+                    &(),
+                )
+                .await
+                .unwrap();
+            }
+        };
+
+        // A cancelled dialog doesn't start a stream; the next Start is what counts
+        start(session("cast1"), "cancel", Some("req1")).await;
+        start(session("cast2"), "", Some("req2")).await;
+        eventually(&detector, true).await;
+        assert!(detector.is_meeting_active(false, true));
+        assert!(!detector.is_meeting_active(true, false));
+        close(session("cast2")).await;
+        eventually(&detector, false).await;
+
+        // Without a handle_token the portal picks the request path
+        start(session("cast1"), "", None).await;
+        eventually(&detector, true).await;
+        // The portal closing the session ends it too
+        portal
+            .emit_signal(
+                None::<()>,
+                session("cast1").as_str(),
+                SESSION_INTERFACE,
+                "Closed",
+                &(HashMap::<&str, Value<'_>>::new(),),
+            )
+            .await
+            .unwrap();
+        eventually(&detector, false).await;
+
+        // Sessions end when the application disconnects
+        start(session("cast3"), "", Some("req3")).await;
+        eventually(&detector, true).await;
+        app.graceful_shutdown().await;
+        eventually(&detector, false).await;
+    }
+}
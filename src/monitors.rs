@@ -1,7 +1,7 @@
 // System monitors module
 // This module contains idle and fullscreen detection monitors
 
-use crate::location::LocationDetector;
+use crate::location::{LocationDetector, NetworkFingerprint};
 use crate::power::{PowerDetector, PowerState};
 use crate::wayland_idle::WaylandIdleDetector;
 use crate::{Error, Result};
@@ -321,6 +321,63 @@ impl FullscreenMonitor {
         Ok(false)
     }
 
+    /// Window class of the focused window (`_NET_ACTIVE_WINDOW`), None if no window
+    /// has focus or it has no class
+    pub fn active_app_id(&self) -> Result<Option<String>> {
+        use x11rb::protocol::xproto::ConnectionExt as XprotoConnectionExt;
+        use x11rb::protocol::xproto::*;
+
+        let (conn, screen_num) = x11rb::connect(None)
+            .map_err(|e| Error::x11_connection(format!("Failed to connect to X11: {}", e)))?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let net_active_window_atom = conn
+            .intern_atom(false, b"_NET_ACTIVE_WINDOW")
+            .map_err(|e| {
+                Error::X11Protocol(format!("Failed to intern _NET_ACTIVE_WINDOW atom: {}", e))
+            })?
+            .reply()
+            .map_err(|e| {
+                Error::X11Protocol(format!(
+                    "Failed to get _NET_ACTIVE_WINDOW atom reply: {}",
+                    e
+                ))
+            })?
+            .atom;
+
+        let active_reply = conn
+            .get_property(false, root, net_active_window_atom, AtomEnum::WINDOW, 0, 1)
+            .map_err(|e| Error::X11Protocol(format!("Failed to get active window: {}", e)))?
+            .reply()
+            .map_err(|e| Error::X11Protocol(format!("Failed to get active window reply: {}", e)))?;
+        let Some(window) = active_reply
+            .value32()
+            .and_then(|mut windows| windows.next())
+        else {
+            return Ok(None);
+        };
+        if window == x11rb::NONE {
+            return Ok(None);
+        }
+
+        // WM_CLASS holds the instance and class names, each NUL-terminated
+        let Ok(class_reply) = conn
+            .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)
+            .map_err(|e| Error::X11Protocol(format!("Failed to get window class: {}", e)))?
+            .reply()
+        else {
+            // The window was closed in the meantime
+            return Ok(None);
+        };
+        let class = class_reply
+            .value
+            .split(|byte| *byte == 0)
+            .filter(|part| !part.is_empty())
+            .nth(1)
+            .map(|class| String::from_utf8_lossy(class).into_owned());
+        Ok(class)
+    }
+
     /// Check if a specific window is in fullscreen mode
     fn is_window_fullscreen<C: Connection>(
         &self,
@@ -383,8 +440,13 @@ impl LocationMonitor {
     }
 
     /// Get current WiFi SSID
-    pub fn get_current_ssid(&self) -> Result<Option<String>> {
-        self.detector.get_current_ssid()
+    pub async fn get_current_ssid(&self) -> Result<Option<String>> {
+        self.detector.get_current_ssid().await
+    }
+
+    /// Get all location signals for the current network
+    pub async fn get_fingerprint(&self) -> Result<NetworkFingerprint> {
+        self.detector.get_fingerprint().await
     }
 }
 
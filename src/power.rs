@@ -1,16 +1,472 @@
 // Power state detection (AC vs Battery)
+// Backends: UPower over D-Bus (event-driven), then /sys/class/power_supply
 use crate::{Error, Result};
+use futures_lite::StreamExt;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use zbus::proxy::CacheProperties;
+use zbus::{proxy, Connection};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Well-known bus name of UPower
+const UPOWER_BUS_NAME: &str = "org.freedesktop.UPower";
+/// Object path of the UPower daemon
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+/// Composite battery device that combines all system batteries
+const UPOWER_DISPLAY_DEVICE_PATH: &str = "/org/freedesktop/UPower/devices/DisplayDevice";
+/// First and longest wait before resubscribing to UPower after its signals stop
+const UPOWER_RETRY_MIN: Duration = Duration::from_secs(1);
+const UPOWER_RETRY_MAX: Duration = Duration::from_secs(60);
+/// Well-known bus name of power-profiles-daemon
+const POWER_PROFILES_BUS_NAME: &str = "net.hadess.PowerProfiles";
+/// Object path of power-profiles-daemon
+const POWER_PROFILES_PATH: &str = "/net/hadess/PowerProfiles";
+
+#[proxy(
+    interface = "org.freedesktop.UPower",
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower",
+    gen_blocking = false
+)]
+trait UPower {
+    #[zbus(property)]
+    fn on_battery(&self) -> zbus::Result<bool>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.UPower.Device",
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower/devices/DisplayDevice",
+    gen_blocking = false
+)]
+trait UPowerDevice {
+    #[zbus(property)]
+    fn is_present(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn percentage(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn state(&self) -> zbus::Result<u32>;
+}
+
+#[proxy(
+    interface = "net.hadess.PowerProfiles",
+    default_service = "net.hadess.PowerProfiles",
+    default_path = "/net/hadess/PowerProfiles",
+    gen_blocking = false
+)]
+trait PowerProfiles {
+    #[zbus(property)]
+    fn active_profile(&self) -> zbus::Result<String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PowerState {
     AC,
     Battery,
     Unknown,
 }
 
-pub struct PowerDetector;
+impl std::fmt::Display for PowerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            PowerState::AC => "ac",
+            PowerState::Battery => "battery",
+            PowerState::Unknown => "unknown",
+        })
+    }
+}
+
+/// Power mode selected in power-profiles-daemon (the desktop's power mode switch)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PowerMode {
+    PowerSaver,
+    Balanced,
+    Performance,
+}
+
+impl PowerMode {
+    /// Parse an `ActiveProfile` value
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "power-saver" => Some(PowerMode::PowerSaver),
+            "balanced" => Some(PowerMode::Balanced),
+            "performance" => Some(PowerMode::Performance),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for PowerMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            PowerMode::PowerSaver => "power-saver",
+            PowerMode::Balanced => "balanced",
+            PowerMode::Performance => "performance",
+        })
+    }
+}
+
+/// Charging state of a battery, as reported by sysfs `status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BatteryStatus {
+    Charging,
+    Discharging,
+    Full,
+    /// Plugged in but held below a charge threshold
+    NotCharging,
+    Unknown,
+}
+
+impl BatteryStatus {
+    fn from_sysfs(status: &str) -> Self {
+        match status {
+            "Charging" => BatteryStatus::Charging,
+            "Discharging" => BatteryStatus::Discharging,
+            "Full" => BatteryStatus::Full,
+            "Not charging" => BatteryStatus::NotCharging,
+            _ => BatteryStatus::Unknown,
+        }
+    }
+
+    /// Map a UPower device `State` (UP_DEVICE_STATE_*)
+    fn from_upower(state: u32) -> Self {
+        match state {
+            1 => BatteryStatus::Charging,
+            // Empty batteries are still draining
+            2 | 3 => BatteryStatus::Discharging,
+            4 => BatteryStatus::Full,
+            // Pending charge/discharge: plugged in but held by a charge threshold
+            5 | 6 => BatteryStatus::NotCharging,
+            _ => BatteryStatus::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for BatteryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            BatteryStatus::Charging => "Charging",
+            BatteryStatus::Discharging => "Discharging",
+            BatteryStatus::Full => "Full",
+            BatteryStatus::NotCharging => "Not charging",
+            BatteryStatus::Unknown => "Unknown",
+        })
+    }
+}
+
+/// A single system battery
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Battery {
+    /// sysfs name, e.g. BAT0
+    pub name: String,
+    pub percentage: u8,
+    pub status: BatteryStatus,
+    /// Remaining and full energy (µWh) or charge (µAh), if reported
+    #[serde(default)]
+    pub energy_now: Option<u64>,
+    #[serde(default)]
+    pub energy_full: Option<u64>,
+}
+
+/// Combined level of all system batteries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatteryLevel {
+    pub percentage: u8,
+    pub status: BatteryStatus,
+    pub batteries: Vec<Battery>,
+}
+
+impl BatteryLevel {
+    /// Combine several batteries (e.g. internal and removable ThinkPad batteries)
+    ///
+    /// The percentage is weighted by capacity when every battery reports energy or
+    /// charge, otherwise it is the mean of the `capacity` values.
+    pub fn aggregate(batteries: Vec<Battery>) -> Option<Self> {
+        if batteries.is_empty() {
+            return None;
+        }
+
+        let energy: Option<(u64, u64)> = batteries.iter().try_fold((0, 0), |(now, full), b| {
+            Some((now + b.energy_now?, full + b.energy_full?))
+        });
+        let percentage = match energy {
+            Some((now, full)) if full > 0 => ((now * 100 + full / 2) / full).min(100) as u8,
+            _ => {
+                let sum: u32 = batteries.iter().map(|b| b.percentage as u32).sum();
+                (sum / batteries.len() as u32) as u8
+            }
+        };
+
+        let any = |status| batteries.iter().any(|b| b.status == status);
+        let status = if any(BatteryStatus::Charging) {
+            BatteryStatus::Charging
+        } else if any(BatteryStatus::Discharging) {
+            BatteryStatus::Discharging
+        } else if batteries.iter().all(|b| b.status == BatteryStatus::Full) {
+            BatteryStatus::Full
+        } else if any(BatteryStatus::NotCharging) || any(BatteryStatus::Full) {
+            BatteryStatus::NotCharging
+        } else {
+            BatteryStatus::Unknown
+        };
+
+        Some(Self {
+            percentage,
+            status,
+            batteries,
+        })
+    }
+
+    /// Whether the system is running off this battery
+    pub fn is_discharging(&self) -> bool {
+        self.status == BatteryStatus::Discharging
+    }
+}
+
+/// Power state as last reported by UPower
+#[derive(Debug, Clone, PartialEq)]
+struct UPowerSnapshot {
+    on_battery: bool,
+    battery: Option<BatteryLevel>,
+}
+
+impl UPowerSnapshot {
+    /// Read OnBattery and the display device from UPower
+    async fn read(conn: &Connection) -> Result<Self> {
+        let upower = UPowerProxy::builder(conn)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
+        let on_battery = upower
+            .on_battery()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to read OnBattery: {}", e)))?;
+
+        let device = UPowerDeviceProxy::builder(conn)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
+        // Desktops have no display device battery
+        let battery = if device.is_present().await.unwrap_or(false) {
+            let percentage = device.percentage().await.map_err(|e| {
+                Error::ipc_protocol(format!("Failed to read battery percentage: {}", e))
+            })?;
+            let state = device.state().await.unwrap_or(0);
+            // UPower only exposes the combined device, so individual batteries are not listed
+            Some(BatteryLevel {
+                percentage: percentage.round().clamp(0.0, 100.0) as u8,
+                status: BatteryStatus::from_upower(state),
+                batteries: vec![],
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            on_battery,
+            battery,
+        })
+    }
+}
+
+/// Event-driven power state from UPower, refreshed on PropertiesChanged
+///
+/// While UPower is not running the snapshot is empty and callers fall back to sysfs;
+/// it is read again once UPower is back.
+pub struct UPowerBackend {
+    snapshot: Arc<RwLock<Option<UPowerSnapshot>>>,
+    changed: Arc<Notify>,
+    watcher: JoinHandle<()>,
+}
+
+/// Property change streams of the UPower daemon and display device
+type UPowerChanges = (
+    zbus::fdo::PropertiesChangedStream,
+    zbus::fdo::PropertiesChangedStream,
+);
+
+impl UPowerBackend {
+    /// Connect to UPower on the given bus and subscribe to property changes
+    pub async fn new(conn: Connection) -> Result<Self> {
+        let dbus = zbus::fdo::DBusProxy::new(&conn)
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create D-Bus proxy: {}", e)))?;
+        let has_owner = dbus
+            .name_has_owner(UPOWER_BUS_NAME.try_into().expect("valid bus name"))
+            .await
+            .unwrap_or(false);
+        if !has_owner {
+            return Err(Error::monitor_unavailable(
+                "UPower",
+                "org.freedesktop.UPower is not running",
+                "Falling back to polling /sys/class/power_supply",
+            ));
+        }
+
+        let owner_changes = dbus
+            .receive_name_owner_changed_with_args(&[(0, UPOWER_BUS_NAME)])
+            .await
+            .map_err(|e| {
+                Error::ipc_protocol(format!("Failed to watch the UPower bus name: {}", e))
+            })?;
+        let changes = Self::subscribe_all(&conn).await?;
+        let snapshot = Arc::new(RwLock::new(Some(UPowerSnapshot::read(&conn).await?)));
+
+        let changed = Arc::new(Notify::new());
+        let watcher = tokio::spawn(Self::watch(
+            conn,
+            owner_changes,
+            changes,
+            Arc::clone(&snapshot),
+            Arc::clone(&changed),
+        ));
+
+        Ok(Self {
+            snapshot,
+            changed,
+            watcher,
+        })
+    }
+
+    /// Keep the snapshot current until the backend is dropped
+    async fn watch(
+        conn: Connection,
+        mut owner_changes: zbus::fdo::NameOwnerChangedStream,
+        mut changes: UPowerChanges,
+        snapshot: Arc<RwLock<Option<UPowerSnapshot>>>,
+        changed: Arc<Notify>,
+    ) {
+        let mut retry = UPOWER_RETRY_MIN;
+        loop {
+            let (ref mut daemon_changes, ref mut device_changes) = changes;
+            tokio::select! {
+                Some(_) = daemon_changes.next() => {}
+                Some(_) = device_changes.next() => {}
+                Some(owner) = owner_changes.next() => {
+                    let restarted = owner
+                        .args()
+                        .is_ok_and(|args| args.new_owner().is_some());
+                    if !restarted {
+                        eprintln!("UPower stopped, falling back to /sys/class/power_supply");
+                        Self::update(&snapshot, &changed, None);
+                        continue;
+                    }
+                    println!("UPower restarted");
+                }
+                else => {
+                    // The signal streams ended; resubscribe with a growing delay and
+                    // use sysfs meanwhile
+                    Self::update(&snapshot, &changed, None);
+                    eprintln!("UPower change signals ended, resubscribing in {:?}", retry);
+                    tokio::time::sleep(retry).await;
+                    retry = (retry * 2).min(UPOWER_RETRY_MAX);
+                    match Self::subscribe_all(&conn).await {
+                        Ok(resubscribed) => changes = resubscribed,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            match UPowerSnapshot::read(&conn).await {
+                Ok(current) => {
+                    retry = UPOWER_RETRY_MIN;
+                    Self::update(&snapshot, &changed, Some(current));
+                }
+                Err(e) => eprintln!("UPower query failed: {}", e),
+            }
+        }
+    }
+
+    /// Store a new snapshot and wake up waiters if it differs
+    fn update(
+        snapshot: &RwLock<Option<UPowerSnapshot>>,
+        changed: &Notify,
+        current: Option<UPowerSnapshot>,
+    ) {
+        let mut snapshot = snapshot.write().unwrap();
+        if *snapshot != current {
+            *snapshot = current;
+            changed.notify_one();
+        }
+    }
+
+    async fn subscribe_all(conn: &Connection) -> Result<UPowerChanges> {
+        Ok((
+            Self::subscribe(conn, UPOWER_PATH).await?,
+            Self::subscribe(conn, UPOWER_DISPLAY_DEVICE_PATH).await?,
+        ))
+    }
+
+    async fn subscribe(
+        conn: &Connection,
+        path: &'static str,
+    ) -> Result<zbus::fdo::PropertiesChangedStream> {
+        let properties = zbus::fdo::PropertiesProxy::builder(conn)
+            .destination(UPOWER_BUS_NAME)
+            .and_then(|builder| builder.path(path))
+            .map_err(|e| Error::ipc_protocol(format!("Invalid UPower object: {}", e)))?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
+        properties.receive_properties_changed().await.map_err(|e| {
+            Error::ipc_protocol(format!("Failed to subscribe to UPower changes: {}", e))
+        })
+    }
+
+    /// Wait until UPower reports a change of power source or battery level
+    pub async fn wait_for_change(&self) {
+        self.changed.notified().await;
+    }
+
+    /// Current power source, None while UPower is not running
+    pub fn power_state(&self) -> Option<PowerState> {
+        let snapshot = self.snapshot.read().unwrap();
+        snapshot.as_ref().map(|snapshot| {
+            if snapshot.on_battery {
+                PowerState::Battery
+            } else {
+                PowerState::AC
+            }
+        })
+    }
+
+    /// Combined battery level (inner None without a battery), None while UPower is
+    /// not running
+    pub fn battery_level(&self) -> Option<Option<BatteryLevel>> {
+        let snapshot = self.snapshot.read().unwrap();
+        snapshot.as_ref().map(|snapshot| snapshot.battery.clone())
+    }
+}
+
+impl Drop for UPowerBackend {
+    fn drop(&mut self) {
+        self.watcher.abort();
+    }
+}
+
+/// Whether a power_supply type is an external supply (wall adapter or USB-C charger)
+fn is_external_supply(supply_type: &str) -> bool {
+    supply_type == "Mains" || supply_type.starts_with("USB")
+}
+
+pub struct PowerDetector {
+    power_supply_path: PathBuf,
+    upower: Option<UPowerBackend>,
+}
 
 impl Default for PowerDetector {
     fn default() -> Self {
@@ -19,79 +475,718 @@ impl Default for PowerDetector {
 }
 
 impl PowerDetector {
+    /// Create a detector polling /sys/class/power_supply
     pub fn new() -> Self {
-        Self
+        Self::with_root("/sys/class/power_supply")
+    }
+
+    /// Create a detector reading power supplies from a custom directory
+    pub fn with_root(power_supply_path: impl AsRef<Path>) -> Self {
+        Self {
+            power_supply_path: power_supply_path.as_ref().to_path_buf(),
+            upower: None,
+        }
+    }
+
+    /// Create a detector that prefers UPower on the system bus
+    /// Falls back to sysfs if UPower is unavailable
+    pub async fn with_upower() -> Self {
+        match Connection::system().await {
+            Ok(conn) => Self::with_connection(conn).await,
+            Err(e) => {
+                eprintln!("PowerDetector: D-Bus system bus unavailable: {}", e);
+                Self::new()
+            }
+        }
+    }
+
+    /// Create a detector using UPower on an existing D-Bus connection
+    pub async fn with_connection(conn: Connection) -> Self {
+        match UPowerBackend::new(conn).await {
+            Ok(backend) => Self {
+                upower: Some(backend),
+                ..Self::new()
+            },
+            Err(e) => {
+                eprintln!("PowerDetector: {}", e);
+                Self::new()
+            }
+        }
+    }
+
+    /// Whether power changes are reported via wait_for_change instead of polling
+    pub fn is_event_driven(&self) -> bool {
+        self.upower.is_some()
+    }
+
+    /// Wait until the power source or battery level changes
+    /// Never resolves when no event-driven backend is available
+    pub async fn wait_for_change(&self) {
+        match self.upower {
+            Some(ref backend) => backend.wait_for_change().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Read every system battery and combine them
+    /// Returns None on machines without a battery
+    pub fn get_battery_level(&self) -> Result<Option<BatteryLevel>> {
+        if let Some(level) = self.upower.as_ref().and_then(UPowerBackend::battery_level) {
+            return Ok(level);
+        }
+        self.read_sysfs_battery_level()
+    }
+
+    fn read_sysfs_battery_level(&self) -> Result<Option<BatteryLevel>> {
+        if !self.power_supply_path.exists() {
+            return Ok(None);
+        }
+
+        let mut batteries = Vec::new();
+        for entry in fs::read_dir(&self.power_supply_path).map_err(Error::Io)? {
+            let path = entry.map_err(Error::Io)?.path();
+            let read = |file: &str| {
+                fs::read_to_string(path.join(file))
+                    .ok()
+                    .map(|s| s.trim().to_string())
+            };
+            let read_u64 = |file: &str| read(file).and_then(|v| v.parse::<u64>().ok());
+
+            if read("type").as_deref() != Some("Battery") {
+                continue;
+            }
+            // Mice, keyboards and headsets report scope=Device; they don't power the laptop
+            if read("scope").as_deref() == Some("Device") {
+                continue;
+            }
+            if read("present").as_deref() == Some("0") {
+                continue;
+            }
+
+            let (energy_now, energy_full) = match (read_u64("energy_now"), read_u64("energy_full"))
+            {
+                (Some(now), Some(full)) => (Some(now), Some(full)),
+                _ => (read_u64("charge_now"), read_u64("charge_full")),
+            };
+            let percentage = match (read_u64("capacity"), energy_now, energy_full) {
+                (Some(capacity), _, _) => capacity.min(100) as u8,
+                (None, Some(now), Some(full)) if full > 0 => (now * 100 / full).min(100) as u8,
+                _ => continue,
+            };
+
+            batteries.push(Battery {
+                name: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                percentage,
+                status: BatteryStatus::from_sysfs(read("status").as_deref().unwrap_or("")),
+                energy_now,
+                energy_full,
+            });
+        }
+
+        batteries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(BatteryLevel::aggregate(batteries))
     }
 
     /// Get current power state
     pub fn get_power_state(&self) -> Result<PowerState> {
-        // Check /sys/class/power_supply/
-        let power_supply_path = Path::new("/sys/class/power_supply");
+        if let Some(state) = self.upower.as_ref().and_then(UPowerBackend::power_state) {
+            return Ok(state);
+        }
 
-        if !power_supply_path.exists() {
+        if !self.power_supply_path.exists() {
             return Ok(PowerState::Unknown);
         }
 
-        // Look for AC adapter
-        let entries = fs::read_dir(power_supply_path).map_err(Error::Io)?;
-
-        for entry in entries {
-            let entry = entry.map_err(Error::Io)?;
-            let path = entry.path();
-
-            // Check if this is an AC adapter
-            let type_file = path.join("type");
-            if let Ok(device_type) = fs::read_to_string(&type_file) {
-                if device_type.trim() == "Mains" {
-                    // This is the AC adapter, check if it's online
-                    let online_file = path.join("online");
-                    if let Ok(online) = fs::read_to_string(&online_file) {
-                        let is_online = online.trim() == "1";
-                        return Ok(if is_online {
-                            PowerState::AC
-                        } else {
-                            PowerState::Battery
-                        });
-                    }
-                }
+        // Any online adapter means external power; laptops may have several
+        // (barrel jack, USB-C PD ports), and offline ones don't rule out the others
+        let mut found_adapter = false;
+        for entry in fs::read_dir(&self.power_supply_path).map_err(Error::Io)? {
+            let path = entry.map_err(Error::Io)?.path();
+            let read = |file: &str| {
+                fs::read_to_string(path.join(file))
+                    .ok()
+                    .map(|s| s.trim().to_string())
+            };
+
+            if !read("type").is_some_and(|t| is_external_supply(&t)) {
+                continue;
+            }
+            // Peripherals charged over USB report scope=Device
+            if read("scope").as_deref() == Some("Device") {
+                continue;
+            }
+            match read("online").as_deref() {
+                Some("0") => found_adapter = true,
+                Some(_) => return Ok(PowerState::AC),
+                None => {}
             }
         }
+        if found_adapter {
+            return Ok(PowerState::Battery);
+        }
 
         // Fallback: check battery status
-        let entries = fs::read_dir(power_supply_path).map_err(Error::Io)?;
-
-        for entry in entries {
-            let entry = entry.map_err(Error::Io)?;
-            let path = entry.path();
-
-            let type_file = path.join("type");
-            if let Ok(device_type) = fs::read_to_string(&type_file) {
-                if device_type.trim() == "Battery" {
-                    let status_file = path.join("status");
-                    if let Ok(status) = fs::read_to_string(&status_file) {
-                        let status = status.trim();
-                        return Ok(match status {
-                            "Charging" | "Full" => PowerState::AC,
-                            "Discharging" => PowerState::Battery,
-                            _ => PowerState::Unknown,
-                        });
+        Ok(match self.read_sysfs_battery_level()? {
+            Some(level) => match level.status {
+                BatteryStatus::Charging | BatteryStatus::Full | BatteryStatus::NotCharging => {
+                    PowerState::AC
+                }
+                BatteryStatus::Discharging => PowerState::Battery,
+                BatteryStatus::Unknown => PowerState::Unknown,
+            },
+            None => PowerState::Unknown,
+        })
+    }
+}
+
+/// Follows the active power-profiles-daemon mode, refreshed on PropertiesChanged
+pub struct PowerProfilesMonitor {
+    mode: Arc<RwLock<Option<PowerMode>>>,
+    changed: Arc<Notify>,
+    watcher: JoinHandle<()>,
+}
+
+impl PowerProfilesMonitor {
+    /// Connect to power-profiles-daemon on the system bus
+    pub async fn with_system_bus() -> Result<Self> {
+        let conn = Connection::system().await.map_err(|e| {
+            Error::monitor_unavailable(
+                "PowerProfiles",
+                format!("D-Bus system bus unavailable: {}", e),
+                "Per-power-mode profile overrides are disabled",
+            )
+        })?;
+        Self::new(conn).await
+    }
+
+    /// Connect to power-profiles-daemon on the given bus and subscribe to mode changes
+    pub async fn new(conn: Connection) -> Result<Self> {
+        let dbus = zbus::fdo::DBusProxy::new(&conn)
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create D-Bus proxy: {}", e)))?;
+        let has_owner = dbus
+            .name_has_owner(POWER_PROFILES_BUS_NAME.try_into().expect("valid bus name"))
+            .await
+            .unwrap_or(false);
+        if !has_owner {
+            return Err(Error::monitor_unavailable(
+                "PowerProfiles",
+                "net.hadess.PowerProfiles is not running",
+                "Per-power-mode profile overrides are disabled",
+            ));
+        }
+
+        let properties = zbus::fdo::PropertiesProxy::builder(&conn)
+            .destination(POWER_PROFILES_BUS_NAME)
+            .and_then(|builder| builder.path(POWER_PROFILES_PATH))
+            .map_err(|e| Error::ipc_protocol(format!("Invalid power-profiles object: {}", e)))?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
+        let mut changes = properties.receive_properties_changed().await.map_err(|e| {
+            Error::ipc_protocol(format!("Failed to subscribe to power mode changes: {}", e))
+        })?;
+        let mode = Arc::new(RwLock::new(Self::read_mode(&conn).await?));
+
+        let changed = Arc::new(Notify::new());
+        let notify = Arc::clone(&changed);
+        let shared = Arc::clone(&mode);
+        let watcher = tokio::spawn(async move {
+            while changes.next().await.is_some() {
+                match Self::read_mode(&conn).await {
+                    Ok(current) => {
+                        let previous = std::mem::replace(&mut *shared.write().unwrap(), current);
+                        if previous != current {
+                            notify.notify_one();
+                        }
                     }
+                    Err(e) => eprintln!("power-profiles-daemon query failed: {}", e),
                 }
             }
-        }
+        });
+
+        Ok(Self {
+            mode,
+            changed,
+            watcher,
+        })
+    }
+
+    async fn read_mode(conn: &Connection) -> Result<Option<PowerMode>> {
+        let proxy = PowerProfilesProxy::builder(conn)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
+        let name = proxy
+            .active_profile()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to read ActiveProfile: {}", e)))?;
+        Ok(PowerMode::from_name(&name))
+    }
+
+    /// Wait until the active power mode changes
+    pub async fn wait_for_change(&self) {
+        self.changed.notified().await;
+    }
+
+    /// Currently active power mode, None if unrecognised
+    pub fn active_mode(&self) -> Option<PowerMode> {
+        *self.mode.read().unwrap()
+    }
+}
 
-        Ok(PowerState::Unknown)
+impl Drop for PowerProfilesMonitor {
+    fn drop(&mut self) {
+        self.watcher.abort();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::PrivateBus;
+    use tokio::time::{timeout, Duration};
+    use zbus::interface;
 
     #[test]
     fn test_power_detector_creation() {
         let detector = PowerDetector::new();
         let state = detector.get_power_state();
         assert!(state.is_ok());
+        assert!(detector.get_battery_level().is_ok());
+    }
+
+    fn supply(root: &Path, name: &str, files: &[(&str, &str)]) {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            fs::write(dir.join(file), format!("{}\n", contents)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_multi_battery_aggregation() {
+        let root = tempfile::TempDir::new().unwrap();
+        // Internal battery nearly empty, large external battery full-ish
+        supply(
+            root.path(),
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("capacity", "10"),
+                ("energy_now", "2300000"),
+                ("energy_full", "23000000"),
+            ],
+        );
+        supply(
+            root.path(),
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Unknown"),
+                ("capacity", "80"),
+                ("energy_now", "57600000"),
+                ("energy_full", "72000000"),
+            ],
+        );
+        supply(root.path(), "AC", &[("type", "Mains"), ("online", "0")]);
+        // Wireless mouse battery is ignored
+        supply(
+            root.path(),
+            "hidpp_battery_0",
+            &[
+                ("type", "Battery"),
+                ("scope", "Device"),
+                ("status", "Discharging"),
+                ("capacity", "5"),
+            ],
+        );
+
+        let level = PowerDetector::with_root(root.path())
+            .get_battery_level()
+            .unwrap()
+            .unwrap();
+        assert_eq!(level.batteries.len(), 2);
+        // (2.3 + 57.6) / (23 + 72) Wh
+        assert_eq!(level.percentage, 63);
+        assert_eq!(level.status, BatteryStatus::Discharging);
+        assert!(level.is_discharging());
+    }
+
+    #[test]
+    fn test_battery_capacity_fallback() {
+        let root = tempfile::TempDir::new().unwrap();
+        supply(
+            root.path(),
+            "BAT0",
+            &[("type", "Battery"), ("status", "Full"), ("capacity", "100")],
+        );
+        supply(
+            root.path(),
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Charging"),
+                ("charge_now", "1500000"),
+                ("charge_full", "3000000"),
+            ],
+        );
+
+        let level = PowerDetector::with_root(root.path())
+            .get_battery_level()
+            .unwrap()
+            .unwrap();
+        // BAT0 has no energy values, so capacities are averaged
+        assert_eq!(level.percentage, 75);
+        assert_eq!(level.status, BatteryStatus::Charging);
+    }
+
+    #[test]
+    fn test_no_battery() {
+        let root = tempfile::TempDir::new().unwrap();
+        supply(root.path(), "AC", &[("type", "Mains"), ("online", "1")]);
+        let detector = PowerDetector::with_root(root.path());
+        assert_eq!(detector.get_battery_level().unwrap(), None);
+        assert_eq!(detector.get_power_state().unwrap(), PowerState::AC);
+
+        let missing = PowerDetector::with_root("/nonexistent/power_supply");
+        assert_eq!(missing.get_battery_level().unwrap(), None);
+    }
+
+    #[test]
+    fn test_battery_status_aggregation() {
+        let battery = |status| Battery {
+            name: "BAT".to_string(),
+            percentage: 50,
+            status,
+            energy_now: None,
+            energy_full: None,
+        };
+        let status = |statuses: &[BatteryStatus]| {
+            BatteryLevel::aggregate(statuses.iter().map(|s| battery(*s)).collect())
+                .unwrap()
+                .status
+        };
+        assert_eq!(
+            status(&[BatteryStatus::Full, BatteryStatus::Full]),
+            BatteryStatus::Full
+        );
+        assert_eq!(
+            status(&[BatteryStatus::Full, BatteryStatus::NotCharging]),
+            BatteryStatus::NotCharging
+        );
+        assert_eq!(
+            status(&[BatteryStatus::Unknown, BatteryStatus::Discharging]),
+            BatteryStatus::Discharging
+        );
+        assert_eq!(BatteryLevel::aggregate(vec![]), None);
+    }
+
+    #[test]
+    fn test_usb_c_supplies() {
+        let root = tempfile::TempDir::new().unwrap();
+        supply(
+            root.path(),
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("capacity", "50"),
+            ],
+        );
+        // The barrel jack is unplugged but a USB-C charger is connected
+        supply(root.path(), "AC", &[("type", "Mains"), ("online", "0")]);
+        supply(
+            root.path(),
+            "ucsi-source-psy-USBC000:001",
+            &[("type", "USB"), ("online", "0")],
+        );
+        supply(
+            root.path(),
+            "ucsi-source-psy-USBC000:002",
+            &[("type", "USB_PD"), ("online", "1")],
+        );
+        let detector = PowerDetector::with_root(root.path());
+        assert_eq!(detector.get_power_state().unwrap(), PowerState::AC);
+
+        // Unplugging the charger leaves only offline adapters
+        supply(
+            root.path(),
+            "ucsi-source-psy-USBC000:002",
+            &[("type", "USB_PD"), ("online", "0")],
+        );
+        assert_eq!(detector.get_power_state().unwrap(), PowerState::Battery);
+
+        // A phone charging from the laptop is not a power source
+        supply(
+            root.path(),
+            "phone",
+            &[("type", "USB"), ("scope", "Device"), ("online", "1")],
+        );
+        assert_eq!(detector.get_power_state().unwrap(), PowerState::Battery);
+    }
+
+    #[test]
+    fn test_power_state_from_battery_status() {
+        let root = tempfile::TempDir::new().unwrap();
+        supply(
+            root.path(),
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Not charging"),
+                ("capacity", "80"),
+            ],
+        );
+        let detector = PowerDetector::with_root(root.path());
+        assert_eq!(detector.get_power_state().unwrap(), PowerState::AC);
+
+        supply(
+            root.path(),
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("capacity", "80"),
+            ],
+        );
+        assert_eq!(detector.get_power_state().unwrap(), PowerState::Battery);
+    }
+
+    struct FakeUPower {
+        on_battery: bool,
+    }
+
+    #[interface(name = "org.freedesktop.UPower")]
+    impl FakeUPower {
+        #[zbus(property)]
+        fn on_battery(&self) -> bool {
+            self.on_battery
+        }
+    }
+
+    struct FakeDisplayDevice {
+        percentage: f64,
+        state: u32,
+    }
+
+    #[interface(name = "org.freedesktop.UPower.Device")]
+    impl FakeDisplayDevice {
+        #[zbus(property)]
+        fn is_present(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn percentage(&self) -> f64 {
+            self.percentage
+        }
+
+        #[zbus(property)]
+        fn state(&self) -> u32 {
+            self.state
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upower_backend() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let service = zbus::connection::Builder::address(bus.address())
+            .unwrap()
+            .name(UPOWER_BUS_NAME)
+            .unwrap()
+            .serve_at(UPOWER_PATH, FakeUPower { on_battery: false })
+            .unwrap()
+            .serve_at(
+                UPOWER_DISPLAY_DEVICE_PATH,
+                FakeDisplayDevice {
+                    percentage: 81.6,
+                    state: 1,
+                },
+            )
+            .unwrap()
+            .build()
+            .await
+            .expect("Failed to start mock UPower");
+
+        let detector = PowerDetector::with_connection(bus.connect().await).await;
+        assert!(detector.is_event_driven());
+        assert_eq!(detector.get_power_state().unwrap(), PowerState::AC);
+        let level = detector.get_battery_level().unwrap().unwrap();
+        assert_eq!(level.percentage, 82);
+        assert_eq!(level.status, BatteryStatus::Charging);
+
+        // Unplug: UPower emits PropertiesChanged on both objects
+        let upower = service
+            .object_server()
+            .interface::<_, FakeUPower>(UPOWER_PATH)
+            .await
+            .unwrap();
+        upower.get_mut().await.on_battery = true;
+        upower
+            .get()
+            .await
+            .on_battery_changed(upower.signal_emitter())
+            .await
+            .unwrap();
+        let device = service
+            .object_server()
+            .interface::<_, FakeDisplayDevice>(UPOWER_DISPLAY_DEVICE_PATH)
+            .await
+            .unwrap();
+        device.get_mut().await.state = 2;
+        device
+            .get()
+            .await
+            .state_changed(device.signal_emitter())
+            .await
+            .unwrap();
+
+        timeout(Duration::from_secs(5), async {
+            while detector.get_battery_level().unwrap().unwrap().status
+                != BatteryStatus::Discharging
+            {
+                detector.wait_for_change().await;
+            }
+        })
+        .await
+        .expect("No change notification from UPower");
+        assert_eq!(detector.get_power_state().unwrap(), PowerState::Battery);
+    }
+
+    async fn fake_upower(bus: &PrivateBus, on_battery: bool) -> Connection {
+        zbus::connection::Builder::address(bus.address())
+            .unwrap()
+            .name(UPOWER_BUS_NAME)
+            .unwrap()
+            .serve_at(UPOWER_PATH, FakeUPower { on_battery })
+            .unwrap()
+            .serve_at(
+                UPOWER_DISPLAY_DEVICE_PATH,
+                FakeDisplayDevice {
+                    percentage: 50.0,
+                    state: if on_battery { 2 } else { 1 },
+                },
+            )
+            .unwrap()
+            .build()
+            .await
+            .expect("Failed to start mock UPower")
+    }
+
+    #[tokio::test]
+    async fn test_upower_restart() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let service = fake_upower(&bus, false).await;
+        let backend = UPowerBackend::new(bus.connect().await).await.unwrap();
+        assert_eq!(backend.power_state(), Some(PowerState::AC));
+
+        // UPower going away empties the snapshot so callers use sysfs
+        service.graceful_shutdown().await;
+        timeout(Duration::from_secs(5), async {
+            while backend.power_state().is_some() {
+                backend.wait_for_change().await;
+            }
+        })
+        .await
+        .expect("UPower shutdown not noticed");
+        assert_eq!(backend.battery_level(), None);
+
+        // A new UPower instance is read again
+        let _service = fake_upower(&bus, true).await;
+        timeout(Duration::from_secs(5), async {
+            while backend.power_state() != Some(PowerState::Battery) {
+                backend.wait_for_change().await;
+            }
+        })
+        .await
+        .expect("UPower restart not noticed");
+        assert_eq!(
+            backend.battery_level().flatten().map(|level| level.status),
+            Some(BatteryStatus::Discharging)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upower_unavailable() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let detector = PowerDetector::with_connection(bus.connect().await).await;
+        assert!(!detector.is_event_driven());
+    }
+
+    struct FakePowerProfiles {
+        active_profile: String,
+    }
+
+    #[interface(name = "net.hadess.PowerProfiles")]
+    impl FakePowerProfiles {
+        #[zbus(property)]
+        fn active_profile(&self) -> String {
+            self.active_profile.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_power_profiles_monitor() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        assert!(PowerProfilesMonitor::new(bus.connect().await)
+            .await
+            .is_err());
+
+        let service = zbus::connection::Builder::address(bus.address())
+            .unwrap()
+            .name(POWER_PROFILES_BUS_NAME)
+            .unwrap()
+            .serve_at(
+                POWER_PROFILES_PATH,
+                FakePowerProfiles {
+                    active_profile: "balanced".to_string(),
+                },
+            )
+            .unwrap()
+            .build()
+            .await
+            .expect("Failed to start mock power-profiles-daemon");
+
+        let monitor = PowerProfilesMonitor::new(bus.connect().await)
+            .await
+            .unwrap();
+        assert_eq!(monitor.active_mode(), Some(PowerMode::Balanced));
+
+        let iface = service
+            .object_server()
+            .interface::<_, FakePowerProfiles>(POWER_PROFILES_PATH)
+            .await
+            .unwrap();
+        iface.get_mut().await.active_profile = "power-saver".to_string();
+        iface
+            .get()
+            .await
+            .active_profile_changed(iface.signal_emitter())
+            .await
+            .unwrap();
+
+        timeout(Duration::from_secs(5), monitor.wait_for_change())
+            .await
+            .expect("No change notification from power-profiles-daemon");
+        assert_eq!(monitor.active_mode(), Some(PowerMode::PowerSaver));
     }
 }
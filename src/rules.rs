@@ -1,13 +1,24 @@
 // Rule engine module
 // This module evaluates rules and determines appropriate brightness levels
 
-use crate::config::{Config, TimeSchedule};
-use chrono::{DateTime, Local, Timelike};
+use crate::config::{
+    BrightnessValue, Config, ManualOverride, OverrideExpiry, RuleLayer, SuppressionPolicy,
+    TimeSchedule, DEFAULT_RULE_ORDER,
+};
+use crate::holidays::Holidays;
+use crate::power::{BatteryLevel, PowerMode, PowerState};
+use chrono::{DateTime, Datelike, Days, Local, NaiveDateTime, NaiveTime, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
 use std::sync::{Arc, RwLock};
 
+/// How many days back a schedule entry can carry over; a week covers every day filter
+const SCHEDULE_LOOKBACK_DAYS: u64 = 7;
+/// Longest stretch of local time a DST or time zone change can skip
+const MAX_DST_GAP_MINUTES: i64 = 24 * 60;
+
 pub struct RuleEngine {
     config: Arc<RwLock<Config>>,
-    pub manual_override: Option<u32>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -16,94 +27,1510 @@ pub enum BrightnessDecision {
     NoChange,
 }
 
+/// Result of evaluating the rules, with a trace of how it came about
+#[derive(Debug)]
+pub struct Decision {
+    pub action: BrightnessDecision,
+    pub trace: DecisionTrace,
+}
+
+/// Why the rules chose a brightness, as shown by `kbd-backlight why`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecisionTrace {
+    /// Profile whose rules were evaluated
+    pub profile: String,
+    /// The system state the rules saw
+    pub inputs: DecisionInputs,
+    /// Every rule in evaluation order, followed by the disabled ones
+    pub rules: Vec<RuleTrace>,
+    /// Rule that decided, None if every rule that could apply is disabled
+    pub winner: Option<RuleLayer>,
+    /// Brightness the winning rule asked for
+    pub requested: u32,
+    /// Limits that changed the requested brightness, in the order applied
+    pub limits: Vec<LimitTrace>,
+    /// Resulting brightness
+    pub brightness: u32,
+}
+
+/// How a single rule fared
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleTrace {
+    pub rule: RuleLayer,
+    pub status: RuleStatus,
+    /// What the rule looked at, e.g. "no meeting in progress"
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleStatus {
+    /// Switched off in the profile's `enabled_rules`
+    Disabled,
+    /// Didn't apply
+    NoMatch,
+    /// Applied, but a rule before it won
+    Matched(u32),
+    /// Applied first and decided the brightness
+    Won(u32),
+}
+
+impl std::fmt::Display for RuleStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            RuleStatus::Disabled => "disabled".to_string(),
+            RuleStatus::NoMatch => "no match".to_string(),
+            RuleStatus::Matched(brightness) => format!("matched: {}", brightness),
+            RuleStatus::Won(brightness) => format!("won: {}", brightness),
+        };
+        f.pad(&status)
+    }
+}
+
+/// A limit that changed the brightness after the winning rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LimitTrace {
+    /// Which setting applied, e.g. "on_battery max_brightness"
+    pub limit: String,
+    pub from: u32,
+    pub to: u32,
+}
+
+/// Snapshot of the `SystemContext` a decision was made from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecisionInputs {
+    /// Local time, formatted as `Sat 2024-06-15 22:30:00`
+    pub time: String,
+    pub power: PowerState,
+    pub power_mode: Option<PowerMode>,
+    /// Battery percentage, None on machines without a battery
+    pub battery: Option<u8>,
+    pub discharging: bool,
+    pub idle: bool,
+    /// Seconds since the last input; only read when a custom rule uses it
+    pub idle_seconds: u64,
+    pub video: bool,
+    pub fullscreen: bool,
+    pub meeting: bool,
+    /// Only read when a custom rule uses it
+    pub ssid: Option<String>,
+    /// Only read when a custom rule uses it
+    pub app_id: Option<String>,
+    pub current_brightness: u32,
+    pub max_brightness: u32,
+}
+
+impl From<&SystemContext> for DecisionInputs {
+    fn from(context: &SystemContext) -> Self {
+        Self {
+            time: context
+                .current_time
+                .format("%a %Y-%m-%d %H:%M:%S")
+                .to_string(),
+            power: context.power_state,
+            power_mode: context.power_mode,
+            battery: context.battery.as_ref().map(|battery| battery.percentage),
+            discharging: context
+                .battery
+                .as_ref()
+                .is_some_and(|battery| battery.is_discharging()),
+            idle: context.is_idle,
+            idle_seconds: context.idle_seconds,
+            video: context.is_video_playing,
+            fullscreen: context.is_fullscreen,
+            meeting: context.is_in_meeting,
+            ssid: context.ssid.clone(),
+            app_id: context.app_id.clone(),
+            current_brightness: context.previous_brightness,
+            max_brightness: context.max_brightness,
+        }
+    }
+}
+
 pub struct SystemContext {
     pub is_idle: bool,
+    pub is_video_playing: bool,
     pub is_fullscreen: bool,
+    pub is_in_meeting: bool,
     pub current_time: DateTime<Local>,
     pub previous_brightness: u32,
+    /// Whether the system runs on AC or battery
+    pub power_state: PowerState,
+    /// Active power-profiles-daemon mode, None if unavailable
+    pub power_mode: Option<PowerMode>,
+    /// Combined battery level, None on machines without a battery
+    pub battery: Option<BatteryLevel>,
+    /// Maximum brightness level of the device
+    pub max_brightness: u32,
+    /// Connected WiFi network; only read when a custom rule uses `ssid`
+    pub ssid: Option<String>,
+    /// Seconds since the last input; only read when a custom rule uses `idle_seconds`
+    pub idle_seconds: u64,
+    /// Window class of the focused window; only read when a custom rule uses `app_id`
+    pub app_id: Option<String>,
 }
 
 impl RuleEngine {
     /// Create a new RuleEngine with the given configuration
     pub fn new(config: Arc<RwLock<Config>>) -> Self {
-        Self {
-            config,
-            manual_override: None,
-        }
+        Self { config }
     }
 
     /// Evaluate all rules and determine the appropriate brightness level
     ///
-    /// Rule Priority (highest to lowest):
+    /// Rules are tried in the active profile's `rule_order` and the first that applies
+    /// decides. The default order (highest to lowest):
     /// 1. Manual override
-    /// 2. Fullscreen detection (brightness = 0)
-    /// 3. Idle timeout (brightness = 0)
-    /// 4. Time-based schedule
-    /// 5. Default brightness (0 if no rules apply)
-    pub fn evaluate(&self, context: &SystemContext) -> BrightnessDecision {
-        // Priority 1: Manual override
-        if let Some(brightness) = self.manual_override {
-            return BrightnessDecision::SetBrightness(brightness);
+    /// 2. Custom rules, the first whose condition holds
+    /// 3. Meeting rule (webcam or screen share active)
+    /// 4. Video/fullscreen suppression per the profile's policy (brightness = 0)
+    /// 5. Idle timeout (brightness = 0)
+    /// 6. Time-based schedule
+    /// 7. Default brightness: the profile's `default_brightness`, else the global one,
+    ///    else 0
+    ///
+    /// The profile's `on_ac`/`on_battery` policy for the current power source raises
+    /// the idle, schedule and default rules to its floor and caps every rule but the
+    /// manual override, as does the override for the active power mode. While
+    /// discharging, the profile's battery rules then force or cap the result. Manual
+    /// override is never limited.
+    ///
+    /// Every enabled rule is evaluated so the trace shows which rules would have
+    /// applied besides the winner.
+    pub fn evaluate(&self, context: &SystemContext) -> Decision {
+        let order = self.rule_order();
+        let mut winner = None;
+        let mut rules: Vec<RuleTrace> = order
+            .iter()
+            .map(|&rule| {
+                let brightness = self.evaluate_layer(rule, context);
+                let status = match brightness {
+                    Some(brightness) if winner.is_none() => {
+                        winner = Some((rule, brightness));
+                        RuleStatus::Won(brightness)
+                    }
+                    Some(brightness) => RuleStatus::Matched(brightness),
+                    None => RuleStatus::NoMatch,
+                };
+                let reason = self.describe_layer(rule, context, brightness);
+                RuleTrace {
+                    rule,
+                    status,
+                    reason,
+                }
+            })
+            .collect();
+        rules.extend(
+            DEFAULT_RULE_ORDER
+                .iter()
+                .filter(|rule| !order.contains(rule))
+                .map(|&rule| RuleTrace {
+                    rule,
+                    status: RuleStatus::Disabled,
+                    reason: "disabled in enabled_rules".to_string(),
+                }),
+        );
+
+        // Every rule that could apply is disabled
+        let (layer, requested) = winner.unwrap_or((RuleLayer::Default, 0));
+        let mut limits = Vec::new();
+        let brightness = self.limit(layer, context, requested, &mut limits);
+
+        let profile = self
+            .config
+            .read()
+            .map(|config| config.active_profile.clone())
+            .unwrap_or_default();
+        Decision {
+            action: BrightnessDecision::SetBrightness(brightness),
+            trace: DecisionTrace {
+                profile,
+                inputs: DecisionInputs::from(context),
+                rules,
+                winner: winner.map(|(layer, _)| layer),
+                requested,
+                limits,
+                brightness,
+            },
         }
+    }
+
+    /// Enabled rules of the active profile in evaluation order
+    pub fn rule_order(&self) -> Vec<RuleLayer> {
+        let Ok(config) = self.config.read() else {
+            return DEFAULT_RULE_ORDER.to_vec();
+        };
+        config.profiles.get(&config.active_profile).map_or_else(
+            || DEFAULT_RULE_ORDER.to_vec(),
+            |profile| profile.rule_order(),
+        )
+    }
 
-        // Priority 2: Fullscreen detection
-        if context.is_fullscreen {
-            return BrightnessDecision::SetBrightness(0);
+    /// Brightness a single rule asks for, None if it doesn't apply
+    pub fn evaluate_layer(&self, layer: RuleLayer, context: &SystemContext) -> Option<u32> {
+        match layer {
+            RuleLayer::Manual => self
+                .manual_override()
+                .filter(|manual| !self.is_override_expired(manual, context))
+                .map(|manual| manual.brightness),
+            RuleLayer::Custom => self.get_custom_rule_brightness(context),
+            RuleLayer::Meeting => self.get_meeting_brightness(context),
+            RuleLayer::Media => self.is_media_suppressed(context).then_some(0),
+            RuleLayer::Idle => context.is_idle.then_some(0),
+            RuleLayer::Schedule => self.get_time_based_brightness(context),
+            RuleLayer::Default => Some(self.get_default_brightness(context).unwrap_or(0)),
         }
+    }
+
+    /// Apply the power source floor and caps and the battery rules to the winning rule
+    pub fn apply_limits(&self, layer: RuleLayer, context: &SystemContext, brightness: u32) -> u32 {
+        self.limit(layer, context, brightness, &mut Vec::new())
+    }
 
-        // Priority 3: Idle timeout
-        if context.is_idle {
-            return BrightnessDecision::SetBrightness(0);
+    /// `apply_limits`, recording each limit that changes the brightness
+    fn limit(
+        &self,
+        layer: RuleLayer,
+        context: &SystemContext,
+        brightness: u32,
+        trace: &mut Vec<LimitTrace>,
+    ) -> u32 {
+        if layer == RuleLayer::Manual {
+            return brightness;
         }
 
-        // Priority 4: Time-based schedule
-        if let Some(brightness) = self.get_time_based_brightness(context) {
-            return BrightnessDecision::SetBrightness(brightness);
+        let mut step = |limit: &dyn Fn() -> String, from: u32, to: u32| {
+            if from != to {
+                trace.push(LimitTrace {
+                    limit: limit(),
+                    from,
+                    to,
+                });
+            }
+            to
+        };
+        let source = match context.power_state {
+            PowerState::Battery => "on_battery",
+            _ => "on_ac",
+        };
+
+        // The power source floor keeps the backlight on, even when idle, but yields to
+        // meetings and media suppression
+        let brightness = match layer {
+            RuleLayer::Idle | RuleLayer::Schedule | RuleLayer::Default => step(
+                &|| format!("{} min_brightness", source),
+                brightness,
+                self.get_power_source_floor(context)
+                    .map_or(brightness, |floor| brightness.max(floor)),
+            ),
+            _ => brightness,
+        };
+        let brightness = step(
+            &|| format!("{} max_brightness", source),
+            brightness,
+            self.apply_power_source_cap(context, brightness),
+        );
+        let brightness = step(
+            &|| {
+                let mode = context.power_mode.map(|mode| mode.to_string());
+                format!("power_modes.{} max_brightness", mode.unwrap_or_default())
+            },
+            brightness,
+            self.apply_power_mode_cap(context, brightness),
+        );
+        step(
+            &|| {
+                let battery = context.battery.as_ref().map_or(0, |b| b.percentage);
+                format!("battery_rules at {}%", battery)
+            },
+            brightness,
+            self.apply_battery_rules(context, brightness),
+        )
+    }
+
+    /// Explain what a rule saw, given the brightness it asked for
+    fn describe_layer(
+        &self,
+        layer: RuleLayer,
+        context: &SystemContext,
+        brightness: Option<u32>,
+    ) -> String {
+        let Ok(config) = self.config.read() else {
+            return String::new();
+        };
+        let Some(profile) = config.profiles.get(&config.active_profile) else {
+            return format!("profile '{}' not found", config.active_profile);
+        };
+
+        match layer {
+            RuleLayer::Manual => match (config.manual_override, brightness) {
+                (Some(manual), Some(_)) => {
+                    format!(
+                        "manual override set to {} {}",
+                        manual.brightness, manual.expiry
+                    )
+                }
+                (Some(manual), None) => format!("manual override ended ({})", manual.expiry),
+                (None, _) => "no manual override".to_string(),
+            },
+            RuleLayer::Custom => match profile
+                .custom_rules
+                .iter()
+                .position(|rule| rule.when.matches(context))
+            {
+                Some(index) => format!(
+                    "rule #{} holds: {}",
+                    index + 1,
+                    profile.custom_rules[index].when.source()
+                ),
+                None if profile.custom_rules.is_empty() => "no custom rules".to_string(),
+                None => "no condition holds".to_string(),
+            },
+            RuleLayer::Meeting => {
+                let enabled = profile.meeting.as_ref().is_some_and(|m| m.enabled);
+                match (context.is_in_meeting, enabled, brightness) {
+                    (false, _, _) => "no meeting in progress".to_string(),
+                    (true, false, _) => "meeting in progress, but no meeting rule".to_string(),
+                    (true, true, None) => {
+                        "idle ends the meeting (suppress_idle = false)".to_string()
+                    }
+                    (true, true, Some(_)) => "meeting in progress".to_string(),
+                }
+            }
+            RuleLayer::Media => {
+                let signals = match (context.is_video_playing, context.is_fullscreen) {
+                    (true, true) => "video playing in a fullscreen window",
+                    (true, false) => "video playing",
+                    (false, true) => "fullscreen window",
+                    (false, false) => return "no video or fullscreen window".to_string(),
+                };
+                match brightness {
+                    Some(_) => format!("{} suppresses the backlight", signals),
+                    None => format!("{}, not suppressed by the suppression policy", signals),
+                }
+            }
+            RuleLayer::Idle => match brightness {
+                Some(_) => "idle timeout reached".to_string(),
+                None => "not idle".to_string(),
+            },
+            RuleLayer::Schedule => {
+                let schedules = profile.time_schedules_for(context.power_state);
+                match latest_schedule(
+                    schedules,
+                    &context.current_time,
+                    config.coordinates(),
+                    &config.holiday_calendar,
+                ) {
+                    Some((since, schedule)) => {
+                        let entry = schedule.at.map_or_else(
+                            || {
+                                format!(
+                                    "{:02}:{:02}",
+                                    schedule.hour.unwrap_or_default(),
+                                    schedule.minute.unwrap_or_default()
+                                )
+                            },
+                            |at| at.to_string(),
+                        );
+                        format!(
+                            "{} entry in effect since {}{}",
+                            entry,
+                            since.format("%a %H:%M"),
+                            if schedule.interpolate {
+                                ", ramping to the next entry"
+                            } else {
+                                ""
+                            }
+                        )
+                    }
+                    None if schedules.is_empty() => "no time schedules".to_string(),
+                    None => "no schedule entry applies".to_string(),
+                }
+            }
+            RuleLayer::Default => match (profile.default_brightness, config.default_brightness) {
+                (Some(value), _) => format!("profile default_brightness = {}", value),
+                (None, Some(value)) => format!("global default_brightness = {}", value),
+                (None, None) => "no default_brightness, off".to_string(),
+            },
         }
+    }
+
+    /// Get the brightness of the first custom rule whose condition holds
+    pub fn get_custom_rule_brightness(&self, context: &SystemContext) -> Option<u32> {
+        let config = self.config.read().ok()?;
+        let profile = config.profiles.get(&config.active_profile)?;
+        profile
+            .custom_rules
+            .iter()
+            .find(|rule| rule.when.matches(context))
+            .map(|rule| rule.then.brightness.to_level(context.max_brightness))
+    }
+
+    /// Get the brightness used when no schedule applies
+    pub fn get_default_brightness(&self, context: &SystemContext) -> Option<u32> {
+        let config = self.config.read().ok()?;
+        let profile = config.profiles.get(&config.active_profile)?;
+        profile
+            .default_brightness
+            .or(config.default_brightness)
+            .map(|value| value.to_level(context.max_brightness))
+    }
+
+    /// Get the lowest brightness allowed on the current power source
+    pub fn get_power_source_floor(&self, context: &SystemContext) -> Option<u32> {
+        let config = self.config.read().ok()?;
+        let profile = config.profiles.get(&config.active_profile)?;
+        profile.brightness_floor(context.power_state, context.max_brightness)
+    }
+
+    /// Limit brightness by the cap of the active profile's power source policy
+    pub fn apply_power_source_cap(&self, context: &SystemContext, brightness: u32) -> u32 {
+        let Ok(config) = self.config.read() else {
+            return brightness;
+        };
+        config
+            .profiles
+            .get(&config.active_profile)
+            .and_then(|profile| profile.brightness_cap(context.power_state, context.max_brightness))
+            .map_or(brightness, |cap| brightness.min(cap))
+    }
+
+    /// Limit brightness by the active profile's override for the current power mode
+    pub fn apply_power_mode_cap(&self, context: &SystemContext, brightness: u32) -> u32 {
+        let Some(mode) = context.power_mode else {
+            return brightness;
+        };
+        let Ok(config) = self.config.read() else {
+            return brightness;
+        };
+        config
+            .profiles
+            .get(&config.active_profile)
+            .and_then(|profile| profile.power_modes.get(&mode)?.max_brightness)
+            .map_or(brightness, |cap| {
+                brightness.min(cap.to_level(context.max_brightness))
+            })
+    }
+
+    /// Limit brightness by the active profile's battery rules
+    /// Every rule whose threshold is above the battery level applies; the lowest
+    /// forced brightness wins, and the lowest cap bounds the result
+    pub fn apply_battery_rules(&self, context: &SystemContext, brightness: u32) -> u32 {
+        let Some(battery) = context.battery.as_ref().filter(|b| b.is_discharging()) else {
+            return brightness;
+        };
+        let Ok(config) = self.config.read() else {
+            return brightness;
+        };
+        let Some(profile) = config.profiles.get(&config.active_profile) else {
+            return brightness;
+        };
+
+        let applicable = || {
+            profile
+                .battery_rules
+                .iter()
+                .filter(|rule| battery.percentage < rule.below)
+        };
+        let forced = applicable()
+            .filter_map(|rule| rule.brightness)
+            .map(|value| value.to_level(context.max_brightness))
+            .min();
+        let cap = applicable()
+            .filter_map(|rule| rule.max_brightness)
+            .map(|value| value.to_level(context.max_brightness))
+            .min();
+
+        let brightness = forced.unwrap_or(brightness);
+        cap.map_or(brightness, |cap| brightness.min(cap))
+    }
 
-        // Priority 5: Default brightness (0 if no rules apply)
-        BrightnessDecision::SetBrightness(0)
+    /// The manual override, kept in the configuration so it is saved to state.toml
+    pub fn manual_override(&self) -> Option<ManualOverride> {
+        self.config.read().ok()?.manual_override
     }
 
-    /// Set or clear manual override
+    /// Set or clear a manual override held until `kbd-backlight auto`
     pub fn set_manual_override(&mut self, brightness: Option<u32>) {
-        self.manual_override = brightness;
+        self.set_timed_override(brightness.map(|brightness| ManualOverride {
+            brightness,
+            expiry: OverrideExpiry::Indefinite,
+        }));
+    }
+
+    /// Set or clear a manual override with any expiry
+    pub fn set_timed_override(&mut self, manual: Option<ManualOverride>) {
+        if let Ok(mut config) = self.config.write() {
+            config.manual_override = manual;
+        }
+    }
+
+    /// Clear the manual override once it has ended, returning it
+    pub fn expire_manual_override(&mut self, context: &SystemContext) -> Option<ManualOverride> {
+        let manual = self.manual_override()?;
+        if !self.is_override_expired(&manual, context) {
+            return None;
+        }
+        self.set_timed_override(None);
+        Some(manual)
+    }
+
+    /// Whether a manual override has ended: its time has passed, a schedule entry has
+    /// taken effect since it was set, or the idle timeout was reached
+    pub fn is_override_expired(&self, manual: &ManualOverride, context: &SystemContext) -> bool {
+        let now = &context.current_time;
+        match manual.expiry {
+            OverrideExpiry::Indefinite => false,
+            OverrideExpiry::UntilTime { until } => now.timestamp() >= until,
+            OverrideExpiry::UntilNextSchedule { since } => self
+                .schedule_boundaries(context)
+                .0
+                .is_some_and(|latest| latest.timestamp() > since),
+            OverrideExpiry::UntilIdle => context.is_idle,
+        }
+    }
+
+    /// When the manual override ends, None if it isn't timed or has no schedule
+    /// entry to end at
+    pub fn manual_override_ends(&self, context: &SystemContext) -> Option<DateTime<Local>> {
+        match self.manual_override()?.expiry {
+            OverrideExpiry::UntilTime { until } => Local.timestamp_opt(until, 0).single(),
+            OverrideExpiry::UntilNextSchedule { .. } => self.schedule_boundaries(context).1,
+            OverrideExpiry::Indefinite | OverrideExpiry::UntilIdle => None,
+        }
+    }
+
+    /// When the next schedule entry of the active profile takes effect, if any
+    pub fn next_schedule_change(&self, context: &SystemContext) -> Option<DateTime<Local>> {
+        self.schedule_boundaries(context).1
+    }
+
+    /// When the schedule entry in effect took effect, and when the next one will
+    fn schedule_boundaries(
+        &self,
+        context: &SystemContext,
+    ) -> (Option<DateTime<Local>>, Option<DateTime<Local>>) {
+        let Ok(config) = self.config.read() else {
+            return (None, None);
+        };
+        let Some(profile) = config.profiles.get(&config.active_profile) else {
+            return (None, None);
+        };
+        let schedules = profile.time_schedules_for(context.power_state);
+        let now = &context.current_time;
+        let coordinates = config.coordinates();
+        let latest = latest_schedule(schedules, now, coordinates, &config.holiday_calendar);
+        let next = next_schedule(schedules, now, coordinates, &config.holiday_calendar);
+        (latest.map(|(at, _)| at), next.map(|(at, _)| at))
+    }
+
+    /// Get the brightness of the active profile's meeting rule, if a meeting is in
+    /// progress and the rule applies (idle only ends it when suppress_idle is off)
+    pub fn get_meeting_brightness(&self, context: &SystemContext) -> Option<u32> {
+        if !context.is_in_meeting {
+            return None;
+        }
+
+        let config = self.config.read().ok()?;
+        let profile = config.profiles.get(&config.active_profile)?;
+        let meeting = profile.meeting.as_ref().filter(|m| m.enabled)?;
+
+        if context.is_idle && !meeting.suppress_idle {
+            return None;
+        }
+
+        Some(meeting.brightness.to_level(context.max_brightness))
+    }
+
+    /// Check whether video playback or fullscreen windows suppress the backlight
+    /// according to the active profile's suppression policy
+    pub fn is_media_suppressed(&self, context: &SystemContext) -> bool {
+        let Ok(config) = self.config.read() else {
+            return false;
+        };
+        let policy = config
+            .profiles
+            .get(&config.active_profile)
+            .map(|profile| profile.suppression_policy())
+            .unwrap_or(SuppressionPolicy::Video);
+
+        policy.suppresses(context.is_video_playing, context.is_fullscreen)
     }
 
     /// Get the brightness based on time schedule rules
     /// Returns the brightness from the most recent time rule
+    /// Sunrise/sunset schedules are resolved for the current date
     fn get_time_based_brightness(&self, context: &SystemContext) -> Option<u32> {
         let config = self.config.read().ok()?;
         let profile = config.profiles.get(&config.active_profile)?;
+        let schedules = profile.time_schedules_for(context.power_state);
+        let coordinates = config.coordinates();
+        let now = &context.current_time;
+        let level = |value: BrightnessValue| value.to_level(context.max_brightness);
+        let (since, schedule) =
+            latest_schedule(schedules, now, coordinates, &config.holiday_calendar)?;
+        if !schedule.interpolate {
+            return Some(level(schedule.brightness));
+        }
+
+        // Ramp towards the next entry; re-evaluated every minute by the daemon
+        let Some((next_at, next)) =
+            next_schedule(schedules, now, coordinates, &config.holiday_calendar)
+        else {
+            return Some(level(schedule.brightness));
+        };
+        Some(interpolate_brightness(
+            level(schedule.brightness),
+            level(next.brightness),
+            (*now - since).num_seconds(),
+            (next_at - since).num_seconds(),
+            context.max_brightness,
+        ))
+    }
+}
+
+/// Brightness `elapsed` seconds into a ramp of `total` seconds, rounded to the nearest
+/// device level
+pub fn interpolate_brightness(from: u32, to: u32, elapsed: i64, total: i64, max: u32) -> u32 {
+    let (from, to) = (from.min(max), to.min(max));
+    if total <= 0 {
+        return to;
+    }
+    let progress = (elapsed as f64 / total as f64).clamp(0.0, 1.0);
+    let level = from as f64 + (to as f64 - from as f64) * progress;
+    level.round() as u32
+}
+
+/// The schedule entry in effect at `now` and when it took effect
+///
+/// Looks back up to a week, so an evening entry carries over past midnight and day
+/// filters can skip whole days. Entries are compared as instants: a time skipped by a
+/// DST change takes effect when the gap ends, and a time repeated when the clocks go
+/// back takes effect the first time it occurs and isn't undone by the second pass.
+pub fn latest_schedule<'a, Tz: TimeZone>(
+    schedules: &'a [TimeSchedule],
+    now: &DateTime<Tz>,
+    coordinates: Option<(f64, f64)>,
+    holidays: &Holidays,
+) -> Option<(DateTime<Tz>, &'a TimeSchedule)> {
+    let timezone = now.timezone();
+    let today = now.date_naive();
+
+    for days_back in 0..=SCHEDULE_LOOKBACK_DAYS {
+        let Some(date) = today.checked_sub_days(Days::new(days_back)) else {
+            break;
+        };
+        let latest = schedules
+            .iter()
+            .filter(|schedule| schedule.applies_on(date, holidays))
+            .filter_map(|schedule| {
+                let time = schedule.time_on(date, coordinates, &timezone)?;
+                let instant = resolve_local_time(&timezone, date.and_time(time))?;
+                Some((instant, time, schedule))
+            })
+            .filter(|(instant, _, _)| instant <= now)
+            // Entries moved to the end of a DST gap keep their wall-clock order, and the
+            // earliest listed entry wins ties
+            .min_by_key(|(instant, time, _)| Reverse((instant.clone(), *time)));
+        if let Some((instant, _, schedule)) = latest {
+            return Some((instant, schedule));
+        }
+    }
+    None
+}
+
+/// The next schedule entry to take effect after `now` and when, looking up to a week
+/// ahead
+pub fn next_schedule<'a, Tz: TimeZone>(
+    schedules: &'a [TimeSchedule],
+    now: &DateTime<Tz>,
+    coordinates: Option<(f64, f64)>,
+    holidays: &Holidays,
+) -> Option<(DateTime<Tz>, &'a TimeSchedule)> {
+    let timezone = now.timezone();
+    let today = now.date_naive();
+
+    for days_ahead in 0..=SCHEDULE_LOOKBACK_DAYS {
+        let Some(date) = today.checked_add_days(Days::new(days_ahead)) else {
+            break;
+        };
+        let next = schedules
+            .iter()
+            .filter(|schedule| schedule.applies_on(date, holidays))
+            .filter_map(|schedule| {
+                let time = schedule.time_on(date, coordinates, &timezone)?;
+                let instant = resolve_local_time(&timezone, date.and_time(time))?;
+                Some((instant, time, schedule))
+            })
+            .filter(|(instant, _, _)| instant > now)
+            // Of entries sharing an instant, the one `latest_schedule` picks
+            .min_by_key(|(instant, time, _)| (instant.clone(), Reverse(*time)));
+        if let Some((instant, _, schedule)) = next {
+            return Some((instant, schedule));
+        }
+    }
+    None
+}
+
+/// Instant of a local date and time, mapping times in a DST gap to the end of the gap
+/// and repeated times to their first occurrence
+fn resolve_local_time<Tz: TimeZone>(timezone: &Tz, local: NaiveDateTime) -> Option<DateTime<Tz>> {
+    (0..=MAX_DST_GAP_MINUTES).find_map(|minutes| {
+        let shifted = local.checked_add_signed(chrono::Duration::minutes(minutes))?;
+        timezone.from_local_datetime(&shifted).earliest()
+    })
+}
+
+/// Condition of a custom rule, e.g. `power == 'battery' && battery < 30 && hour >= 22`
+///
+/// Parsed and type checked when the profile is loaded; `Config::validate` reports
+/// errors, and an invalid condition never matches.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct Condition {
+    source: String,
+    parsed: std::result::Result<Expr, ConditionError>,
+}
+
+impl Condition {
+    /// The condition as written
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Why the condition is invalid, if it is
+    pub fn error(&self) -> Option<&ConditionError> {
+        self.parsed.as_ref().err()
+    }
+
+    /// Whether the condition reads the given variable
+    pub fn uses(&self, variable: Variable) -> bool {
+        self.parsed.as_ref().is_ok_and(|expr| expr.uses(variable))
+    }
+
+    /// Evaluate the condition against the current system state
+    pub fn matches(&self, context: &SystemContext) -> bool {
+        self.parsed
+            .as_ref()
+            .is_ok_and(|expr| expr.eval(context) == Value::Bool(true))
+    }
+}
+
+impl From<String> for Condition {
+    fn from(source: String) -> Self {
+        let parsed = Parser::parse(&source);
+        Self { source, parsed }
+    }
+}
+
+impl From<Condition> for String {
+    fn from(condition: Condition) -> Self {
+        condition.source
+    }
+}
+
+/// Parse or type error in a condition, at a 1-based character column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl ConditionError {
+    fn new(column: usize, message: impl Into<String>) -> Self {
+        Self {
+            column,
+            message: message.into(),
+        }
+    }
+
+    /// The error with the condition and a marker under the offending column
+    pub fn describe(&self, source: &str) -> String {
+        format!(
+            "column {}: {}\n    {}\n    {}^",
+            self.column,
+            self.message,
+            source,
+            " ".repeat(self.column.saturating_sub(1))
+        )
+    }
+}
+
+impl std::fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+/// A value a condition can read from the system state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variable {
+    /// `'ac'`, `'battery'` or `'unknown'`
+    Power,
+    /// `'power-saver'`, `'balanced'` or `'performance'`, null without power-profiles-daemon
+    PowerMode,
+    /// Battery percentage, null without a battery
+    Battery,
+    /// Connected WiFi network, null when disconnected
+    Ssid,
+    /// Whether the idle timeout has passed
+    Idle,
+    /// Seconds since the last input
+    IdleSeconds,
+    Fullscreen,
+    Video,
+    Meeting,
+    /// Window class of the focused X11 window, null if unknown
+    AppId,
+    Hour,
+    Minute,
+    /// Local time as `'HH:MM'`
+    Time,
+    /// `'mon'` to `'sun'`
+    Weekday,
+    /// Current backlight level
+    Brightness,
+}
+
+const VARIABLES: [(&str, Variable); 15] = [
+    ("power", Variable::Power),
+    ("power_mode", Variable::PowerMode),
+    ("battery", Variable::Battery),
+    ("ssid", Variable::Ssid),
+    ("idle", Variable::Idle),
+    ("idle_seconds", Variable::IdleSeconds),
+    ("fullscreen", Variable::Fullscreen),
+    ("video", Variable::Video),
+    ("meeting", Variable::Meeting),
+    ("app_id", Variable::AppId),
+    ("hour", Variable::Hour),
+    ("minute", Variable::Minute),
+    ("time", Variable::Time),
+    ("weekday", Variable::Weekday),
+    ("brightness", Variable::Brightness),
+];
+
+impl Variable {
+    fn from_name(name: &str) -> Option<Self> {
+        VARIABLES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, variable)| *variable)
+    }
+
+    fn name(self) -> &'static str {
+        VARIABLES
+            .iter()
+            .find(|(_, variable)| *variable == self)
+            .map_or("?", |(name, _)| name)
+    }
+
+    fn kind(self) -> Kind {
+        match self {
+            Variable::Idle | Variable::Fullscreen | Variable::Video | Variable::Meeting => {
+                Kind::Bool
+            }
+            Variable::Battery
+            | Variable::IdleSeconds
+            | Variable::Hour
+            | Variable::Minute
+            | Variable::Brightness => Kind::Number,
+            Variable::Time => Kind::Time,
+            Variable::Power
+            | Variable::PowerMode
+            | Variable::Ssid
+            | Variable::AppId
+            | Variable::Weekday => Kind::Text,
+        }
+    }
+
+    /// The only strings this variable takes, if limited
+    fn allowed_values(self) -> Option<&'static [&'static str]> {
+        match self {
+            Variable::Power => Some(&["ac", "battery", "unknown"]),
+            Variable::PowerMode => Some(&["power-saver", "balanced", "performance"]),
+            Variable::Weekday => Some(&["mon", "tue", "wed", "thu", "fri", "sat", "sun"]),
+            _ => None,
+        }
+    }
+
+    fn value(self, context: &SystemContext) -> Value {
+        let text = |text: &str| Value::Text(text.to_string());
+        let optional =
+            |text: Option<&String>| text.map_or(Value::Null, |text| Value::Text(text.clone()));
+        match self {
+            Variable::Power => text(match context.power_state {
+                PowerState::AC => "ac",
+                PowerState::Battery => "battery",
+                PowerState::Unknown => "unknown",
+            }),
+            Variable::PowerMode => context
+                .power_mode
+                .map_or(Value::Null, |mode| Value::Text(mode.to_string())),
+            Variable::Battery => context.battery.as_ref().map_or(Value::Null, |battery| {
+                Value::Number(battery.percentage as f64)
+            }),
+            Variable::Ssid => optional(context.ssid.as_ref()),
+            Variable::Idle => Value::Bool(context.is_idle),
+            Variable::IdleSeconds => Value::Number(context.idle_seconds as f64),
+            Variable::Fullscreen => Value::Bool(context.is_fullscreen),
+            Variable::Video => Value::Bool(context.is_video_playing),
+            Variable::Meeting => Value::Bool(context.is_in_meeting),
+            Variable::AppId => optional(context.app_id.as_ref()),
+            Variable::Hour => Value::Number(context.current_time.hour() as f64),
+            Variable::Minute => Value::Number(context.current_time.minute() as f64),
+            Variable::Time => Value::Text(context.current_time.format("%H:%M").to_string()),
+            Variable::Weekday => Value::Text(
+                context
+                    .current_time
+                    .weekday()
+                    .to_string()
+                    .to_ascii_lowercase(),
+            ),
+            Variable::Brightness => Value::Number(context.previous_brightness as f64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Bool,
+    Number,
+    Text,
+    /// `'HH:MM'` text, which can be ordered
+    Time,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Kind::Bool => "a boolean",
+            Kind::Number => "a number",
+            Kind::Text => "text",
+            Kind::Time => "a time",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn is_equality(self) -> bool {
+        matches!(self, CompareOp::Eq | CompareOp::Ne)
+    }
+
+    /// Null equals only null and is never ordered
+    fn apply(self, left: &Value, right: &Value) -> bool {
+        let ordering = match (left, right) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (Value::Null, Value::Null) => Some(Ordering::Equal),
+            _ => None,
+        };
+        match self {
+            CompareOp::Eq => ordering == Some(Ordering::Equal),
+            CompareOp::Ne => ordering != Some(Ordering::Equal),
+            _ if matches!(left, Value::Null) || matches!(right, Value::Null) => false,
+            CompareOp::Lt => ordering == Some(Ordering::Less),
+            CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            CompareOp::Gt => ordering == Some(Ordering::Greater),
+            CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Literal(Value),
+    Variable(Variable),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+    In(Box<Expr>, Vec<Value>),
+}
+
+impl Expr {
+    fn eval(&self, context: &SystemContext) -> Value {
+        let is_true = |expr: &Expr| expr.eval(context) == Value::Bool(true);
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Variable(variable) => variable.value(context),
+            Expr::Not(expr) => Value::Bool(!is_true(expr)),
+            Expr::And(left, right) => Value::Bool(is_true(left) && is_true(right)),
+            Expr::Or(left, right) => Value::Bool(is_true(left) || is_true(right)),
+            Expr::Compare(left, op, right) => {
+                Value::Bool(op.apply(&left.eval(context), &right.eval(context)))
+            }
+            Expr::In(expr, values) => {
+                let value = expr.eval(context);
+                Value::Bool(values.iter().any(|item| CompareOp::Eq.apply(&value, item)))
+            }
+        }
+    }
+
+    fn uses(&self, variable: Variable) -> bool {
+        match self {
+            Expr::Literal(_) => false,
+            Expr::Variable(candidate) => *candidate == variable,
+            Expr::Not(expr) | Expr::In(expr, _) => expr.uses(variable),
+            Expr::And(left, right) | Expr::Or(left, right) | Expr::Compare(left, _, right) => {
+                left.uses(variable) || right.uses(variable)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Text(String),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Not,
+    And,
+    Or,
+    Compare(CompareOp),
+    End,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Number(number) => write!(f, "{}", number),
+            Token::Text(text) => write!(f, "'{}'", text),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::LBracket => write!(f, "'['"),
+            Token::RBracket => write!(f, "']'"),
+            Token::Comma => write!(f, "','"),
+            Token::Not => write!(f, "'!'"),
+            Token::And => write!(f, "'&&'"),
+            Token::Or => write!(f, "'||'"),
+            Token::Compare(_) => write!(f, "comparison"),
+            Token::End => write!(f, "end of condition"),
+        }
+    }
+}
+
+/// Split a condition into tokens with their 1-based columns
+fn tokenize(source: &str) -> std::result::Result<Vec<(Token, usize)>, ConditionError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        let column = idx + 1;
+        let c = chars[idx];
+        let next = chars.get(idx + 1).copied();
+        let (token, len) = match (c, next) {
+            _ if c.is_whitespace() => {
+                idx += 1;
+                continue;
+            }
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            ('[', _) => (Token::LBracket, 1),
+            (']', _) => (Token::RBracket, 1),
+            (',', _) => (Token::Comma, 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Compare(CompareOp::Eq), 2),
+            ('!', Some('=')) => (Token::Compare(CompareOp::Ne), 2),
+            ('<', Some('=')) => (Token::Compare(CompareOp::Le), 2),
+            ('>', Some('=')) => (Token::Compare(CompareOp::Ge), 2),
+            ('<', _) => (Token::Compare(CompareOp::Lt), 1),
+            ('>', _) => (Token::Compare(CompareOp::Gt), 1),
+            ('!', _) => (Token::Not, 1),
+            ('=', _) => return Err(ConditionError::new(column, "use '==' to compare")),
+            ('\'' | '"', _) => {
+                let end = chars[idx + 1..]
+                    .iter()
+                    .position(|candidate| *candidate == c)
+                    .ok_or_else(|| ConditionError::new(column, "unterminated string"))?;
+                let text: String = chars[idx + 1..idx + 1 + end].iter().collect();
+                (Token::Text(text), end + 2)
+            }
+            _ if c.is_ascii_digit() => {
+                let len = chars[idx..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit() || **c == '.')
+                    .count();
+                let text: String = chars[idx..idx + len].iter().collect();
+                let number = text.parse().map_err(|_| {
+                    ConditionError::new(column, format!("invalid number '{}'", text))
+                })?;
+                (Token::Number(number), len)
+            }
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                let len = chars[idx..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .count();
+                (Token::Ident(chars[idx..idx + len].iter().collect()), len)
+            }
+            _ => {
+                return Err(ConditionError::new(
+                    column,
+                    format!("unexpected character '{}'", c),
+                ))
+            }
+        };
+        tokens.push((token, column));
+        idx += len;
+    }
+    tokens.push((Token::End, chars.len() + 1));
+    Ok(tokens)
+}
+
+/// Parsed operand with its type (None for null) and where it starts
+struct Typed {
+    expr: Expr,
+    kind: Option<Kind>,
+    column: usize,
+}
+
+/// Recursive descent parser that type checks as it goes
+///
+/// ```text
+/// or         = and ("||" and)*
+/// and        = unary ("&&" unary)*
+/// unary      = "!" unary | comparison
+/// comparison = operand (("==" | "!=" | "<" | "<=" | ">" | ">=") operand | "in" list)?
+/// operand    = variable | number | string | true | false | null | "(" or ")"
+/// list       = "[" (operand ("," operand)*)? "]"
+/// ```
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn parse(source: &str) -> std::result::Result<Expr, ConditionError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+        if parser.peek() == &Token::End {
+            return Err(ConditionError::new(1, "empty condition"));
+        }
+        let typed = parser.or()?;
+        if parser.peek() != &Token::End {
+            return Err(parser.unexpected("'&&', '||' or end of condition"));
+        }
+        expect_bool(&typed)?;
+        Ok(typed.expr)
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn column(&self) -> usize {
+        self.tokens[self.position].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.position].0.clone();
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
 
-        // Find the most recent time schedule rule
-        let current_minutes = context.current_time.hour() * 60 + context.current_time.minute();
+    fn unexpected(&self, expected: &str) -> ConditionError {
+        ConditionError::new(
+            self.column(),
+            format!("expected {}, found {}", expected, self.peek()),
+        )
+    }
+
+    fn or(&mut self) -> std::result::Result<Typed, ConditionError> {
+        let mut left = self.and()?;
+        while self.peek() == &Token::Or {
+            self.advance();
+            let right = self.and()?;
+            expect_bool(&left)?;
+            expect_bool(&right)?;
+            left = Typed {
+                expr: Expr::Or(Box::new(left.expr), Box::new(right.expr)),
+                kind: Some(Kind::Bool),
+                column: left.column,
+            };
+        }
+        Ok(left)
+    }
 
-        let mut applicable_schedule: Option<&TimeSchedule> = None;
-        let mut best_minutes: Option<u32> = None;
+    fn and(&mut self) -> std::result::Result<Typed, ConditionError> {
+        let mut left = self.unary()?;
+        while self.peek() == &Token::And {
+            self.advance();
+            let right = self.unary()?;
+            expect_bool(&left)?;
+            expect_bool(&right)?;
+            left = Typed {
+                expr: Expr::And(Box::new(left.expr), Box::new(right.expr)),
+                kind: Some(Kind::Bool),
+                column: left.column,
+            };
+        }
+        Ok(left)
+    }
 
-        for schedule in &profile.time_schedules {
-            let schedule_minutes = schedule.hour as u32 * 60 + schedule.minute as u32;
+    fn unary(&mut self) -> std::result::Result<Typed, ConditionError> {
+        if self.peek() != &Token::Not {
+            return self.comparison();
+        }
+        let column = self.column();
+        self.advance();
+        let operand = self.unary()?;
+        expect_bool(&operand)?;
+        Ok(Typed {
+            expr: Expr::Not(Box::new(operand.expr)),
+            kind: Some(Kind::Bool),
+            column,
+        })
+    }
 
-            // Only consider schedules that have already occurred today
-            if schedule_minutes <= current_minutes {
-                // If this is the first applicable schedule or it's more recent than the current best
-                if best_minutes.is_none() || schedule_minutes > best_minutes.unwrap() {
-                    applicable_schedule = Some(schedule);
-                    best_minutes = Some(schedule_minutes);
+    fn comparison(&mut self) -> std::result::Result<Typed, ConditionError> {
+        let left = self.operand()?;
+        match self.peek().clone() {
+            Token::Compare(op) => {
+                let op_column = self.column();
+                self.advance();
+                let right = self.operand()?;
+                let (left, right) = check_comparison(left, op, op_column, right)?;
+                Ok(Typed {
+                    expr: Expr::Compare(Box::new(left.expr), op, Box::new(right.expr)),
+                    kind: Some(Kind::Bool),
+                    column: left.column,
+                })
+            }
+            Token::Ident(word) if word == "in" => {
+                let op_column = self.column();
+                self.advance();
+                if self.peek() != &Token::LBracket {
+                    return Err(self.unexpected("'['"));
+                }
+                self.advance();
+                let mut values = Vec::new();
+                while self.peek() != &Token::RBracket {
+                    if !values.is_empty() {
+                        if self.peek() != &Token::Comma {
+                            return Err(self.unexpected("',' or ']'"));
+                        }
+                        self.advance();
+                    }
+                    let item = self.operand()?;
+                    let (_, item) = check_comparison(
+                        Typed {
+                            expr: left.expr.clone(),
+                            kind: left.kind,
+                            column: left.column,
+                        },
+                        CompareOp::Eq,
+                        op_column,
+                        item,
+                    )?;
+                    match item.expr {
+                        Expr::Literal(value) => values.push(value),
+                        _ => {
+                            return Err(ConditionError::new(
+                                item.column,
+                                "lists may only contain literal values",
+                            ))
+                        }
+                    }
                 }
+                self.advance();
+                Ok(Typed {
+                    expr: Expr::In(Box::new(left.expr), values),
+                    kind: Some(Kind::Bool),
+                    column: left.column,
+                })
             }
+            _ => Ok(left),
         }
+    }
+
+    fn operand(&mut self) -> std::result::Result<Typed, ConditionError> {
+        let column = self.column();
+        let (expr, kind) = match self.peek().clone() {
+            Token::LParen => {
+                self.advance();
+                let inner = self.or()?;
+                if self.peek() != &Token::RParen {
+                    return Err(self.unexpected("')'"));
+                }
+                self.advance();
+                return Ok(Typed { column, ..inner });
+            }
+            Token::Number(number) => (Expr::Literal(Value::Number(number)), Some(Kind::Number)),
+            Token::Text(text) => (Expr::Literal(Value::Text(text)), Some(Kind::Text)),
+            Token::Ident(name) => match name.as_str() {
+                "true" | "false" => (Expr::Literal(Value::Bool(name == "true")), Some(Kind::Bool)),
+                "null" => (Expr::Literal(Value::Null), None),
+                _ => {
+                    let variable = Variable::from_name(&name).ok_or_else(|| {
+                        let names: Vec<_> = VARIABLES.iter().map(|(name, _)| *name).collect();
+                        ConditionError::new(
+                            column,
+                            format!(
+                                "unknown variable '{}', expected one of {}",
+                                name,
+                                names.join(", ")
+                            ),
+                        )
+                    })?;
+                    (Expr::Variable(variable), Some(variable.kind()))
+                }
+            },
+            _ => return Err(self.unexpected("a variable or value")),
+        };
+        self.advance();
+        Ok(Typed { expr, kind, column })
+    }
+}
+
+fn expect_bool(typed: &Typed) -> std::result::Result<(), ConditionError> {
+    match typed.kind {
+        Some(Kind::Bool) => Ok(()),
+        Some(kind) => Err(ConditionError::new(
+            typed.column,
+            format!("expected a boolean, found {}", kind),
+        )),
+        None => Err(ConditionError::new(
+            typed.column,
+            "expected a boolean, found null",
+        )),
+    }
+}
 
-        applicable_schedule.map(|s| s.brightness)
+/// Check that two operands can be compared, normalizing text literals compared with
+/// variables of a limited set of values or with `time`
+fn check_comparison(
+    left: Typed,
+    op: CompareOp,
+    op_column: usize,
+    right: Typed,
+) -> std::result::Result<(Typed, Typed), ConditionError> {
+    let (Some(left_kind), Some(right_kind)) = (left.kind, right.kind) else {
+        if op.is_equality() {
+            return Ok((left, right));
+        }
+        return Err(ConditionError::new(
+            op_column,
+            "null can only be compared with '==' or '!='",
+        ));
+    };
+
+    let left_variable = match left.expr {
+        Expr::Variable(variable) => Some(variable),
+        _ => None,
+    };
+    let right_variable = match right.expr {
+        Expr::Variable(variable) => Some(variable),
+        _ => None,
+    };
+    let left = normalize_literal(left, right_variable)?;
+    let right = normalize_literal(right, left_variable)?;
+
+    let kind = match (left_kind, right_kind) {
+        (a, b) if a == b => a,
+        (Kind::Time, Kind::Text) | (Kind::Text, Kind::Time) => Kind::Time,
+        _ => {
+            return Err(ConditionError::new(
+                right.column,
+                format!("cannot compare {} with {}", left_kind, right_kind),
+            ))
+        }
+    };
+    if !op.is_equality() && matches!(kind, Kind::Bool | Kind::Text) {
+        return Err(ConditionError::new(
+            op_column,
+            format!("{} can only be compared with '==' or '!='", kind),
+        ));
     }
+    Ok((left, right))
+}
+
+/// Validate a text literal against the variable it is compared with
+fn normalize_literal(
+    typed: Typed,
+    variable: Option<Variable>,
+) -> std::result::Result<Typed, ConditionError> {
+    let (Expr::Literal(Value::Text(text)), Some(variable)) = (&typed.expr, variable) else {
+        return Ok(typed);
+    };
+    let normalized = if variable.kind() == Kind::Time {
+        NaiveTime::parse_from_str(text.trim(), "%H:%M")
+            .map(|time| time.format("%H:%M").to_string())
+            .map_err(|_| {
+                ConditionError::new(
+                    typed.column,
+                    format!("invalid time '{}', expected 'HH:MM'", text),
+                )
+            })?
+    } else if let Some(allowed) = variable.allowed_values() {
+        let lower = text.trim().to_ascii_lowercase();
+        if !allowed.contains(&lower.as_str()) {
+            return Err(ConditionError::new(
+                typed.column,
+                format!(
+                    "invalid {} '{}', expected one of {}",
+                    variable.name(),
+                    text,
+                    allowed.join(", ")
+                ),
+            ));
+        }
+        lower
+    } else {
+        return Ok(typed);
+    };
+    Ok(Typed {
+        expr: Expr::Literal(Value::Text(normalized)),
+        ..typed
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{Config, LocationProfile, TimeSchedule};
-    use chrono::Local;
+    use crate::config::{
+        BatteryRule, BrightnessValue, Config, CustomAction, CustomRule, LocationProfile,
+        ManualOverride, MeetingRule, OverrideExpiry, PowerModeOverride, PowerSourcePolicy,
+        RuleLayer, ScheduleDate, ScheduleDay, TimeSchedule,
+    };
+    use crate::holidays::Holidays;
+    use crate::power::BatteryStatus;
+    use crate::solar::{self, SunTimes};
+    use chrono::{
+        FixedOffset, Local, MappedLocalTime, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc,
+    };
+    use proptest::prelude::*;
     use std::collections::HashMap;
 
     fn create_test_config() -> Arc<RwLock<Config>> {
@@ -115,24 +1542,28 @@ mod tests {
                 idle_timeout: 10,
                 time_schedules: vec![
                     TimeSchedule {
-                        hour: 9,
-                        minute: 0,
-                        brightness: 2,
+                        hour: Some(9),
+                        minute: Some(0),
+                        brightness: BrightnessValue::Level(2),
+                        ..Default::default()
                     },
                     TimeSchedule {
-                        hour: 14,
-                        minute: 30,
-                        brightness: 3,
+                        hour: Some(14),
+                        minute: Some(30),
+                        brightness: BrightnessValue::Level(3),
+                        ..Default::default()
                     },
                     TimeSchedule {
-                        hour: 22,
-                        minute: 0,
-                        brightness: 1,
+                        hour: Some(22),
+                        minute: Some(0),
+                        brightness: BrightnessValue::Level(1),
+                        ..Default::default()
                     },
                 ],
                 video_detection_enabled: true,
                 wifi_networks: vec![],
                 ac_always_on: false,
+                ..Default::default()
             },
         );
 
@@ -140,6 +1571,16 @@ mod tests {
             profiles,
             active_profile: "test".to_string(),
             auto_switch_location: false,
+            fallback_profile: None,
+            offline_profile: None,
+            location_settle_time: 5,
+            profile_pin: None,
+            manual_override: None,
+            latitude: None,
+            longitude: None,
+            default_brightness: None,
+            holidays: None,
+            holiday_calendar: Default::default(),
         }))
     }
 
@@ -155,9 +1596,18 @@ mod tests {
 
         SystemContext {
             is_idle,
+            is_video_playing: false,
             is_fullscreen,
+            is_in_meeting: false,
             current_time: time,
             previous_brightness: 2,
+            power_state: PowerState::Unknown,
+            power_mode: None,
+            battery: None,
+            max_brightness: 3,
+            ssid: None,
+            idle_seconds: 0,
+            app_id: None,
         }
     }
 
@@ -169,18 +1619,26 @@ mod tests {
 
         // Manual override should take precedence over everything
         let context = create_context(true, true, 10, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(3));
     }
 
     #[test]
     fn test_fullscreen_priority() {
         let config = create_test_config();
+        // Fullscreen suppression is opt-in for profiles with video detection
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .suppression_policy = Some(SuppressionPolicy::Either);
         let engine = RuleEngine::new(config);
 
         // Fullscreen should set brightness to 0
         let context = create_context(false, true, 10, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(0));
     }
 
@@ -191,7 +1649,7 @@ mod tests {
 
         // Idle should set brightness to 0
         let context = create_context(true, false, 10, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(0));
     }
 
@@ -202,17 +1660,17 @@ mod tests {
 
         // At 10:00, should use the 9:00 rule (brightness 2)
         let context = create_context(false, false, 10, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(2));
 
         // At 15:00, should use the 14:30 rule (brightness 3)
         let context = create_context(false, false, 15, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(3));
 
         // At 23:00, should use the 22:00 rule (brightness 1)
         let context = create_context(false, false, 23, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(1));
     }
 
@@ -223,50 +1681,73 @@ mod tests {
 
         // At 14:30 exactly, should use the 14:30 rule (brightness 3)
         let context = create_context(false, false, 14, 30);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(3));
 
         // At 14:29, should use the 9:00 rule (brightness 2)
         let context = create_context(false, false, 14, 29);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(2));
     }
 
     #[test]
     fn test_no_applicable_time_rule() {
         let config = create_test_config();
-        let engine = RuleEngine::new(config);
+        let engine = RuleEngine::new(config.clone());
 
-        // At 8:00 (before any schedule), should use default (0)
+        // At 8:00 (before today's first schedule), yesterday's 22:00 rule still applies
         let context = create_context(false, false, 8, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
+        assert_eq!(decision, BrightnessDecision::SetBrightness(1));
+
+        // Schedules that only start tomorrow don't apply, so the default (0) is used
+        let tomorrow = context.current_time.date_naive().succ_opt().unwrap();
+        for schedule in &mut config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .time_schedules
+        {
+            schedule.from = Some(ScheduleDate(tomorrow));
+        }
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(0));
     }
 
     #[test]
     fn test_rule_priority_order() {
         let config = create_test_config();
+        // Fullscreen suppression is opt-in for profiles with video detection
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .suppression_policy = Some(SuppressionPolicy::Either);
         let mut engine = RuleEngine::new(config);
 
         // Test that manual override beats fullscreen
         engine.set_manual_override(Some(2));
         let context = create_context(false, true, 10, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(2));
 
         // Clear manual override, fullscreen should now apply
         engine.set_manual_override(None);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(0));
 
         // Test that fullscreen beats idle
         let context = create_context(true, true, 10, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(0));
 
         // Test that idle beats time schedule
         let context = create_context(true, false, 10, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(0));
     }
 
@@ -278,12 +1759,12 @@ mod tests {
         // Set manual override
         engine.set_manual_override(Some(3));
         let context = create_context(false, false, 10, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(3));
 
         // Clear manual override
         engine.set_manual_override(None);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(2));
     }
 
@@ -299,6 +1780,7 @@ mod tests {
                 video_detection_enabled: true,
                 wifi_networks: vec![],
                 ac_always_on: false,
+                ..Default::default()
             },
         );
 
@@ -306,14 +1788,543 @@ mod tests {
             profiles,
             active_profile: "empty".to_string(),
             auto_switch_location: false,
+            fallback_profile: None,
+            offline_profile: None,
+            location_settle_time: 5,
+            profile_pin: None,
+            manual_override: None,
+            latitude: None,
+            longitude: None,
+            default_brightness: None,
+            holidays: None,
+            holiday_calendar: Default::default(),
         }));
 
         let engine = RuleEngine::new(config);
         let context = create_context(false, false, 10, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(0));
     }
 
+    #[test]
+    fn test_custom_rule_order() {
+        let config = create_test_config();
+        let mut engine = RuleEngine::new(config.clone());
+        engine.set_manual_override(Some(3));
+        {
+            let mut config = config.write().unwrap();
+            let profile = config.profiles.get_mut("test").unwrap();
+            // Idle ends a manual override; video and fullscreen are ignored
+            profile.rule_order = vec![RuleLayer::Idle, RuleLayer::Manual];
+            profile.enabled_rules.insert(RuleLayer::Media, false);
+        }
+        assert_eq!(
+            engine.rule_order(),
+            vec![
+                RuleLayer::Idle,
+                RuleLayer::Manual,
+                RuleLayer::Custom,
+                RuleLayer::Meeting,
+                RuleLayer::Schedule,
+                RuleLayer::Default,
+            ]
+        );
+
+        assert_eq!(
+            engine.evaluate(&create_context(true, false, 10, 0)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+        assert_eq!(
+            engine.evaluate(&create_context(false, true, 10, 0)).action,
+            BrightnessDecision::SetBrightness(3)
+        );
+        engine.set_manual_override(None);
+        assert_eq!(
+            engine.evaluate(&create_context(false, true, 10, 0)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+
+        // With idle disabled the schedule stays in effect
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .enabled_rules
+            .insert(RuleLayer::Idle, false);
+        assert_eq!(
+            engine.evaluate(&create_context(true, false, 10, 0)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+
+        // Nothing enabled applies: off, but still raised to the power source floor
+        {
+            let mut config = config.write().unwrap();
+            let profile = config.profiles.get_mut("test").unwrap();
+            profile.enabled_rules.insert(RuleLayer::Schedule, false);
+            profile.enabled_rules.insert(RuleLayer::Default, false);
+            profile.on_ac = Some(PowerSourcePolicy {
+                min_brightness: Some(BrightnessValue::Level(1)),
+                ..Default::default()
+            });
+        }
+        assert_eq!(
+            engine.evaluate(&create_context(false, false, 10, 0)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+        let on_ac = SystemContext {
+            power_state: PowerState::AC,
+            ..create_context(false, false, 10, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&on_ac).action,
+            BrightnessDecision::SetBrightness(1)
+        );
+    }
+
+    #[test]
+    fn test_timed_manual_overrides() {
+        let config = create_test_config();
+        let mut engine = RuleEngine::new(config.clone());
+        let at = |hour, minute| create_context(false, false, hour, minute);
+        let timed = |expiry| {
+            Some(ManualOverride {
+                brightness: 0,
+                expiry,
+            })
+        };
+
+        // Until a time
+        let until = at(11, 0).current_time.timestamp();
+        engine.set_timed_override(timed(OverrideExpiry::UntilTime { until }));
+        assert_eq!(
+            engine.evaluate(&at(10, 59)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+        assert_eq!(
+            engine
+                .manual_override_ends(&at(10, 30))
+                .map(|ends| ends.timestamp()),
+            Some(at(11, 0).current_time.timestamp())
+        );
+        assert_eq!(
+            engine.evaluate(&at(11, 0)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+        assert_eq!(engine.expire_manual_override(&at(10, 59)), None);
+        assert_eq!(
+            engine.expire_manual_override(&at(11, 0)),
+            timed(OverrideExpiry::UntilTime { until })
+        );
+        assert_eq!(engine.manual_override(), None);
+        assert_eq!(config.read().unwrap().manual_override, None);
+
+        // Until the next schedule entry, set at 10:00 while the 9:00 entry applies
+        let since = at(10, 0).current_time.timestamp();
+        engine.set_timed_override(timed(OverrideExpiry::UntilNextSchedule { since }));
+        assert_eq!(
+            engine
+                .manual_override_ends(&at(10, 0))
+                .map(|ends| ends.timestamp()),
+            Some(at(14, 30).current_time.timestamp())
+        );
+        assert_eq!(
+            engine.evaluate(&at(14, 29)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+        assert_eq!(
+            engine.evaluate(&at(14, 30)).action,
+            BrightnessDecision::SetBrightness(3)
+        );
+        assert!(engine.expire_manual_override(&at(14, 30)).is_some());
+
+        // Without schedules on this power source there is no next entry to wait for
+        assert!(engine.next_schedule_change(&at(10, 0)).is_some());
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .time_schedules
+            .clear();
+        assert_eq!(engine.next_schedule_change(&at(10, 0)), None);
+
+        // Until idle
+        engine.set_timed_override(timed(OverrideExpiry::UntilIdle));
+        assert_eq!(engine.manual_override_ends(&at(10, 0)), None);
+        assert_eq!(
+            engine.evaluate(&at(10, 0)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+        let idle = create_context(true, false, 10, 0);
+        let decision = engine.evaluate(&idle);
+        assert_eq!(decision.action, BrightnessDecision::SetBrightness(0));
+        assert_eq!(decision.trace.winner, Some(RuleLayer::Idle));
+        assert_eq!(decision.trace.rules[0].status, RuleStatus::NoMatch);
+        assert_eq!(
+            decision.trace.rules[0].reason,
+            "manual override ended (until idle)"
+        );
+        assert!(engine.expire_manual_override(&idle).is_some());
+
+        // Indefinite overrides never end on their own
+        engine.set_manual_override(Some(1));
+        assert_eq!(engine.expire_manual_override(&idle), None);
+        assert_eq!(engine.manual_override_ends(&at(10, 0)), None);
+        assert_eq!(
+            engine.evaluate(&idle).action,
+            BrightnessDecision::SetBrightness(1)
+        );
+    }
+
+    #[test]
+    fn test_decision_trace() {
+        let config = create_test_config();
+        {
+            let mut config = config.write().unwrap();
+            let profile = config.profiles.get_mut("test").unwrap();
+            profile.enabled_rules.insert(RuleLayer::Media, false);
+            profile.on_battery = Some(PowerSourcePolicy {
+                min_brightness: Some(BrightnessValue::Level(1)),
+                ..Default::default()
+            });
+        }
+        let engine = RuleEngine::new(config.clone());
+        let context = SystemContext {
+            power_state: PowerState::Battery,
+            ..create_context(true, true, 15, 0)
+        };
+
+        let decision = engine.evaluate(&context);
+        assert_eq!(decision.action, BrightnessDecision::SetBrightness(1));
+        let trace = decision.trace;
+        assert_eq!(trace.profile, "test");
+        assert_eq!(trace.winner, Some(RuleLayer::Idle));
+        assert_eq!(trace.requested, 0);
+        assert_eq!(trace.brightness, 1);
+        assert_eq!(
+            trace.limits,
+            vec![LimitTrace {
+                limit: "on_battery min_brightness".to_string(),
+                from: 0,
+                to: 1,
+            }]
+        );
+
+        let statuses: Vec<_> = trace
+            .rules
+            .iter()
+            .map(|rule| (rule.rule, rule.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (RuleLayer::Manual, RuleStatus::NoMatch),
+                (RuleLayer::Custom, RuleStatus::NoMatch),
+                (RuleLayer::Meeting, RuleStatus::NoMatch),
+                (RuleLayer::Idle, RuleStatus::Won(0)),
+                (RuleLayer::Schedule, RuleStatus::Matched(3)),
+                (RuleLayer::Default, RuleStatus::Matched(0)),
+                (RuleLayer::Media, RuleStatus::Disabled),
+            ]
+        );
+        let reasons: Vec<_> = trace
+            .rules
+            .iter()
+            .map(|rule| rule.reason.as_str())
+            .collect();
+        assert_eq!(reasons[1], "no custom rules");
+        assert_eq!(reasons[3], "idle timeout reached");
+        assert!(
+            reasons[4].starts_with("14:30 entry in effect since"),
+            "{}",
+            reasons[4]
+        );
+        assert_eq!(reasons[5], "no default_brightness, off");
+
+        assert_eq!(trace.inputs.power, PowerState::Battery);
+        assert!(trace.inputs.idle && trace.inputs.fullscreen);
+        assert_eq!(trace.inputs.current_brightness, 2);
+        assert!(
+            trace.inputs.time.ends_with("15:00:00"),
+            "{}",
+            trace.inputs.time
+        );
+
+        // Without any enabled rule nothing wins and the backlight stays off
+        {
+            let mut config = config.write().unwrap();
+            let profile = config.profiles.get_mut("test").unwrap();
+            for rule in DEFAULT_RULE_ORDER {
+                profile.enabled_rules.insert(rule, false);
+            }
+        }
+        let decision = engine.evaluate(&create_context(false, false, 15, 0));
+        assert_eq!(decision.action, BrightnessDecision::SetBrightness(0));
+        assert_eq!(decision.trace.winner, None);
+        assert!(decision
+            .trace
+            .rules
+            .iter()
+            .all(|rule| rule.status == RuleStatus::Disabled));
+    }
+
+    fn condition(source: &str) -> Condition {
+        Condition::from(source.to_string())
+    }
+
+    fn parse_error(source: &str) -> ConditionError {
+        condition(source)
+            .error()
+            .cloned()
+            .unwrap_or_else(|| panic!("'{}' should be rejected", source))
+    }
+
+    #[test]
+    fn test_condition_parsing() {
+        for valid in [
+            "power == 'battery' && battery < 30 && hour >= 22",
+            "!(video || fullscreen) && idle_seconds > 120",
+            "ssid == \"Office\" || ssid == null",
+            "weekday in ['Sat', 'sun'] && time >= '7:30' && time < '22:00'",
+            "power_mode != 'power-saver' && app_id in ['firefox', 'mpv']",
+            "meeting == false && !idle && brightness <= 1 && minute > 0",
+            "true",
+        ] {
+            let parsed = condition(valid);
+            assert!(parsed.error().is_none(), "{}: {:?}", valid, parsed.error());
+            assert_eq!(parsed.source(), valid);
+        }
+
+        let cases = [
+            ("batery < 30", 1, "unknown variable 'batery'"),
+            (
+                "battery < 30 && power == 'batery'",
+                26,
+                "invalid power 'batery'",
+            ),
+            ("battery < 'low'", 11, "cannot compare a number with text"),
+            ("battery = 30", 9, "use '=='"),
+            ("battery < 30 &&", 16, "expected a variable or value"),
+            ("battery < 30 hour", 14, "expected '&&', '||'"),
+            ("battery", 1, "expected a boolean, found a number"),
+            ("idle && hour", 9, "expected a boolean"),
+            ("ssid == 'Office", 9, "unterminated string"),
+            ("battery > null", 9, "null can only be compared"),
+            ("ssid < 'b'", 6, "text can only be compared"),
+            ("time > '25:00'", 8, "invalid time '25:00'"),
+            (
+                "weekday in ['mon', 'monday']",
+                20,
+                "invalid weekday 'monday'",
+            ),
+            ("(idle || video", 15, "expected ')'"),
+            ("idle # comment", 6, "unexpected character '#'"),
+            ("", 1, "empty condition"),
+        ];
+        for (source, column, message) in cases {
+            let error = parse_error(source);
+            assert_eq!(error.column, column, "{}: {}", source, error);
+            assert!(error.message.contains(message), "{}: {}", source, error);
+        }
+
+        let error = parse_error("battery < 30 && power == 'batery'");
+        assert_eq!(
+            error.describe("battery < 30 && power == 'batery'"),
+            "column 26: invalid power 'batery', expected one of ac, battery, unknown\n    \
+             battery < 30 && power == 'batery'\n    \
+             \x20                        ^"
+        );
+    }
+
+    #[test]
+    fn test_condition_evaluation() {
+        let base = || SystemContext {
+            current_time: Local.with_ymd_and_hms(2024, 6, 15, 22, 30, 0).unwrap(),
+            power_state: PowerState::Battery,
+            battery: Some(BatteryLevel {
+                percentage: 25,
+                status: BatteryStatus::Discharging,
+                batteries: vec![],
+            }),
+            ..create_context(false, false, 0, 0)
+        };
+        let matches = |source: &str, context: &SystemContext| condition(source).matches(context);
+
+        let context = base();
+        assert!(matches(
+            "power == 'battery' && battery < 30 && hour >= 22",
+            &context
+        ));
+        assert!(matches("power == 'BATTERY'", &context));
+        assert!(!matches("power == 'ac' || battery >= 30", &context));
+        // Saturday
+        assert!(matches(
+            "weekday in ['sat', 'sun'] && time >= '22:30'",
+            &context
+        ));
+        assert!(!matches("time > '22:30'", &context));
+        assert!(matches("minute == 30 && brightness == 2", &context));
+        assert!(matches(
+            "!idle && !video && !fullscreen && !meeting",
+            &context
+        ));
+
+        // Missing values are null: equal only to null and never ordered
+        assert!(matches("ssid == null && app_id != 'firefox'", &context));
+        assert!(!matches("power_mode == 'balanced'", &context));
+        let no_battery = SystemContext {
+            battery: None,
+            ..base()
+        };
+        assert!(!matches("battery < 30", &no_battery));
+        assert!(!matches("battery >= 30", &no_battery));
+        assert!(matches("battery == null", &no_battery));
+
+        let context = SystemContext {
+            ssid: Some("Office".to_string()),
+            idle_seconds: 300,
+            app_id: Some("mpv".to_string()),
+            is_video_playing: true,
+            power_mode: Some(PowerMode::PowerSaver),
+            ..base()
+        };
+        assert!(matches("ssid == 'Office' && idle_seconds > 120", &context));
+        assert!(!matches("ssid == 'office'", &context));
+        assert!(matches("video && app_id in ['vlc', 'mpv']", &context));
+        assert!(matches("power_mode == 'power-saver'", &context));
+
+        // Invalid conditions never match
+        assert!(!matches("battery < 'low'", &context));
+
+        let parsed = condition("ssid == 'Office' && (idle || app_id == 'mpv')");
+        assert!(parsed.uses(Variable::Ssid));
+        assert!(parsed.uses(Variable::AppId));
+        assert!(!parsed.uses(Variable::IdleSeconds));
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let config = create_test_config();
+        {
+            let mut config = config.write().unwrap();
+            let profile = config.profiles.get_mut("test").unwrap();
+            profile.meeting = Some(MeetingRule {
+                enabled: true,
+                brightness: BrightnessValue::Level(3),
+                suppress_idle: true,
+                detect_webcam: true,
+                detect_screen_sharing: true,
+            });
+            profile.custom_rules = vec![
+                CustomRule {
+                    when: condition("power == 'battery' && battery < 30 && hour >= 22"),
+                    then: CustomAction {
+                        brightness: BrightnessValue::Level(0),
+                    },
+                },
+                CustomRule {
+                    when: condition("power == 'battery'"),
+                    then: CustomAction {
+                        brightness: BrightnessValue::Percent(100),
+                    },
+                },
+            ];
+            profile.on_battery = Some(PowerSourcePolicy {
+                max_brightness: Some(BrightnessValue::Level(2)),
+                ..Default::default()
+            });
+        }
+        let mut engine = RuleEngine::new(config);
+        let on_battery = |percentage, hour| SystemContext {
+            power_state: PowerState::Battery,
+            battery: Some(BatteryLevel {
+                percentage,
+                status: BatteryStatus::Discharging,
+                batteries: vec![],
+            }),
+            is_in_meeting: true,
+            ..create_context(false, false, hour, 0)
+        };
+
+        // The first matching rule wins over meetings
+        assert_eq!(
+            engine.evaluate(&on_battery(20, 23)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+        // Later rules are still limited by the battery cap
+        assert_eq!(
+            engine.evaluate(&on_battery(50, 23)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+        // No rule matches on AC, so the meeting rule decides
+        let on_ac = SystemContext {
+            power_state: PowerState::AC,
+            is_in_meeting: true,
+            ..create_context(false, false, 23, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&on_ac).action,
+            BrightnessDecision::SetBrightness(3)
+        );
+
+        // Manual override still comes first
+        engine.set_manual_override(Some(1));
+        assert_eq!(
+            engine.evaluate(&on_battery(20, 23)).action,
+            BrightnessDecision::SetBrightness(1)
+        );
+    }
+
+    #[test]
+    fn test_default_brightness() {
+        let config = create_test_config();
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .time_schedules
+            .clear();
+        let engine = RuleEngine::new(config.clone());
+        let at = |is_idle| {
+            engine
+                .evaluate(&create_context(is_idle, false, 10, 0))
+                .action
+        };
+        assert_eq!(at(false), BrightnessDecision::SetBrightness(0));
+
+        // The global default applies to profiles without their own
+        config.write().unwrap().default_brightness = Some(BrightnessValue::Percent(70));
+        assert_eq!(at(false), BrightnessDecision::SetBrightness(2));
+
+        // The profile's default wins over the global one
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .default_brightness = Some(BrightnessValue::Level(3));
+        assert_eq!(at(false), BrightnessDecision::SetBrightness(3));
+
+        // Idle still turns the backlight off
+        assert_eq!(at(true), BrightnessDecision::SetBrightness(0));
+
+        // Schedules take precedence
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .time_schedules = vec![schedule(9, 0, 1)];
+        assert_eq!(at(false), BrightnessDecision::SetBrightness(1));
+    }
+
     #[test]
     fn test_profile_specific_rules() {
         let mut profiles = HashMap::new();
@@ -325,13 +2336,15 @@ mod tests {
                 name: "home".to_string(),
                 idle_timeout: 10,
                 time_schedules: vec![TimeSchedule {
-                    hour: 9,
-                    minute: 0,
-                    brightness: 2,
+                    hour: Some(9),
+                    minute: Some(0),
+                    brightness: BrightnessValue::Level(2),
+                    ..Default::default()
                 }],
                 video_detection_enabled: true,
                 wifi_networks: vec![],
                 ac_always_on: false,
+                ..Default::default()
             },
         );
 
@@ -342,13 +2355,15 @@ mod tests {
                 name: "office".to_string(),
                 idle_timeout: 5,
                 time_schedules: vec![TimeSchedule {
-                    hour: 9,
-                    minute: 0,
-                    brightness: 3,
+                    hour: Some(9),
+                    minute: Some(0),
+                    brightness: BrightnessValue::Level(3),
+                    ..Default::default()
                 }],
                 video_detection_enabled: true,
                 wifi_networks: vec![],
                 ac_always_on: false,
+                ..Default::default()
             },
         );
 
@@ -356,13 +2371,23 @@ mod tests {
             profiles,
             active_profile: "home".to_string(),
             auto_switch_location: false,
+            fallback_profile: None,
+            offline_profile: None,
+            location_settle_time: 5,
+            profile_pin: None,
+            manual_override: None,
+            latitude: None,
+            longitude: None,
+            default_brightness: None,
+            holidays: None,
+            holiday_calendar: Default::default(),
         }));
 
         let engine = RuleEngine::new(Arc::clone(&config));
 
         // At 10:00 with home profile, should get brightness 2
         let context = create_context(false, false, 10, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(2));
 
         // Switch to office profile
@@ -370,7 +2395,7 @@ mod tests {
 
         // At 10:00 with office profile, should get brightness 3
         let context = create_context(false, false, 10, 0);
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(3));
     }
 
@@ -385,13 +2410,15 @@ mod tests {
                 name: "home".to_string(),
                 idle_timeout: 10,
                 time_schedules: vec![TimeSchedule {
-                    hour: 9,
-                    minute: 0,
-                    brightness: 1,
+                    hour: Some(9),
+                    minute: Some(0),
+                    brightness: BrightnessValue::Level(1),
+                    ..Default::default()
                 }],
                 video_detection_enabled: true,
                 wifi_networks: vec![],
                 ac_always_on: false,
+                ..Default::default()
             },
         );
 
@@ -401,13 +2428,15 @@ mod tests {
                 name: "office".to_string(),
                 idle_timeout: 5,
                 time_schedules: vec![TimeSchedule {
-                    hour: 9,
-                    minute: 0,
-                    brightness: 2,
+                    hour: Some(9),
+                    minute: Some(0),
+                    brightness: BrightnessValue::Level(2),
+                    ..Default::default()
                 }],
                 video_detection_enabled: true,
                 wifi_networks: vec![],
                 ac_always_on: false,
+                ..Default::default()
             },
         );
 
@@ -417,13 +2446,15 @@ mod tests {
                 name: "travel".to_string(),
                 idle_timeout: 15,
                 time_schedules: vec![TimeSchedule {
-                    hour: 9,
-                    minute: 0,
-                    brightness: 3,
+                    hour: Some(9),
+                    minute: Some(0),
+                    brightness: BrightnessValue::Level(3),
+                    ..Default::default()
                 }],
                 video_detection_enabled: true,
                 wifi_networks: vec![],
                 ac_always_on: false,
+                ..Default::default()
             },
         );
 
@@ -431,6 +2462,16 @@ mod tests {
             profiles,
             active_profile: "home".to_string(),
             auto_switch_location: false,
+            fallback_profile: None,
+            offline_profile: None,
+            location_settle_time: 5,
+            profile_pin: None,
+            manual_override: None,
+            latitude: None,
+            longitude: None,
+            default_brightness: None,
+            holidays: None,
+            holiday_calendar: Default::default(),
         }));
 
         let engine = RuleEngine::new(Arc::clone(&config));
@@ -438,22 +2479,1038 @@ mod tests {
 
         // Test home profile
         config.write().unwrap().active_profile = "home".to_string();
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(1));
 
         // Test office profile
         config.write().unwrap().active_profile = "office".to_string();
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(2));
 
         // Test travel profile
         config.write().unwrap().active_profile = "travel".to_string();
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(3));
 
         // Verify switching back to home still works
         config.write().unwrap().active_profile = "home".to_string();
-        let decision = engine.evaluate(&context);
+        let decision = engine.evaluate(&context).action;
         assert_eq!(decision, BrightnessDecision::SetBrightness(1));
     }
+
+    #[test]
+    fn test_video_and_fullscreen_policies() {
+        let config = create_test_config();
+        let engine = RuleEngine::new(Arc::clone(&config));
+
+        let set_policy = |policy| {
+            let mut cfg = config.write().unwrap();
+            cfg.profiles.get_mut("test").unwrap().suppression_policy = Some(policy);
+        };
+        let context = |video, fullscreen| SystemContext {
+            is_video_playing: video,
+            is_fullscreen: fullscreen,
+            ..create_context(false, false, 10, 0)
+        };
+
+        // Default policy for video-enabled profiles only checks video playback
+        assert_eq!(
+            engine.evaluate(&context(true, false)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+        assert_eq!(
+            engine.evaluate(&context(false, true)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+
+        set_policy(SuppressionPolicy::Either);
+        assert_eq!(
+            engine.evaluate(&context(false, true)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+
+        set_policy(SuppressionPolicy::Video);
+        assert_eq!(
+            engine.evaluate(&context(false, true)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+        assert_eq!(
+            engine.evaluate(&context(true, false)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+
+        set_policy(SuppressionPolicy::Fullscreen);
+        assert_eq!(
+            engine.evaluate(&context(true, false)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+        assert_eq!(
+            engine.evaluate(&context(false, true)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+
+        set_policy(SuppressionPolicy::FullscreenAndVideo);
+        assert_eq!(
+            engine.evaluate(&context(true, false)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+        assert_eq!(
+            engine.evaluate(&context(false, true)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+        assert_eq!(
+            engine.evaluate(&context(true, true)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+    }
+
+    #[test]
+    fn test_fullscreen_only_without_video_detection() {
+        let config = create_test_config();
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .video_detection_enabled = false;
+        let engine = RuleEngine::new(config);
+
+        // With video detection disabled, playback alone no longer suppresses
+        let context = SystemContext {
+            is_video_playing: true,
+            ..create_context(false, false, 10, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&context).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+    }
+
+    #[test]
+    fn test_meeting_rule() {
+        let config = create_test_config();
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .meeting = Some(MeetingRule {
+            enabled: true,
+            brightness: BrightnessValue::Level(1),
+            suppress_idle: true,
+            detect_webcam: true,
+            detect_screen_sharing: true,
+        });
+        let mut engine = RuleEngine::new(Arc::clone(&config));
+
+        // Meeting beats idle and fullscreen
+        let context = SystemContext {
+            is_in_meeting: true,
+            ..create_context(true, true, 10, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&context).action,
+            BrightnessDecision::SetBrightness(1)
+        );
+
+        // No meeting: normal rules apply
+        let context = create_context(false, false, 10, 0);
+        assert_eq!(
+            engine.evaluate(&context).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+
+        // Percentages are resolved against the device's steps
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .meeting
+            .as_mut()
+            .unwrap()
+            .brightness = BrightnessValue::Percent(70);
+        let context = SystemContext {
+            is_in_meeting: true,
+            ..create_context(true, false, 10, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&context).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+
+        // Manual override still wins
+        engine.set_manual_override(Some(3));
+        let context = SystemContext {
+            is_in_meeting: true,
+            ..create_context(false, false, 10, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&context).action,
+            BrightnessDecision::SetBrightness(3)
+        );
+    }
+
+    #[test]
+    fn test_meeting_rule_without_idle_suppression() {
+        let config = create_test_config();
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .meeting = Some(MeetingRule {
+            enabled: true,
+            brightness: BrightnessValue::Level(1),
+            suppress_idle: false,
+            detect_webcam: true,
+            detect_screen_sharing: true,
+        });
+        let engine = RuleEngine::new(Arc::clone(&config));
+
+        let idle = SystemContext {
+            is_in_meeting: true,
+            ..create_context(true, false, 10, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&idle).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+
+        let active = SystemContext {
+            is_in_meeting: true,
+            ..create_context(false, false, 10, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&active).action,
+            BrightnessDecision::SetBrightness(1)
+        );
+
+        // Disabled meeting rules are ignored
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .meeting
+            .as_mut()
+            .unwrap()
+            .enabled = false;
+        assert_eq!(
+            engine.evaluate(&active).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+    }
+
+    fn battery(percentage: u8, status: BatteryStatus) -> Option<BatteryLevel> {
+        Some(BatteryLevel {
+            percentage,
+            status,
+            batteries: vec![],
+        })
+    }
+
+    #[test]
+    fn test_battery_rules() {
+        let config = create_test_config();
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .battery_rules = vec![
+            BatteryRule {
+                below: 20,
+                brightness: Some(BrightnessValue::Level(0)),
+                max_brightness: None,
+            },
+            BatteryRule {
+                below: 40,
+                brightness: None,
+                max_brightness: Some(BrightnessValue::Percent(50)),
+            },
+        ];
+        let mut engine = RuleEngine::new(config);
+
+        // 14:30 schedule sets brightness 3
+        let at = |percentage, status| SystemContext {
+            battery: battery(percentage, status),
+            ..create_context(false, false, 15, 0)
+        };
+
+        assert_eq!(
+            engine.evaluate(&at(80, BatteryStatus::Discharging)).action,
+            BrightnessDecision::SetBrightness(3)
+        );
+        // Capped at 50% of 3
+        assert_eq!(
+            engine.evaluate(&at(39, BatteryStatus::Discharging)).action,
+            BrightnessDecision::SetBrightness(1)
+        );
+        assert_eq!(
+            engine.evaluate(&at(19, BatteryStatus::Discharging)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+
+        // Rules only apply while running on battery
+        assert_eq!(
+            engine.evaluate(&at(19, BatteryStatus::Charging)).action,
+            BrightnessDecision::SetBrightness(3)
+        );
+        assert_eq!(
+            engine
+                .evaluate(&SystemContext {
+                    battery: None,
+                    ..at(19, BatteryStatus::Discharging)
+                })
+                .action,
+            BrightnessDecision::SetBrightness(3)
+        );
+
+        // Manual override is never limited
+        engine.set_manual_override(Some(2));
+        assert_eq!(
+            engine.evaluate(&at(10, BatteryStatus::Discharging)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+    }
+
+    #[test]
+    fn test_battery_rule_forces_brightness_on() {
+        let config = create_test_config();
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .battery_rules = vec![BatteryRule {
+            below: 50,
+            brightness: Some(BrightnessValue::Level(1)),
+            max_brightness: Some(BrightnessValue::Level(1)),
+        }];
+        let engine = RuleEngine::new(config);
+
+        // Forced brightness replaces the schedule, even before 9:00 when nothing applies
+        let context = SystemContext {
+            battery: battery(30, BatteryStatus::Discharging),
+            ..create_context(false, false, 8, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&context).action,
+            BrightnessDecision::SetBrightness(1)
+        );
+    }
+
+    #[test]
+    fn test_legacy_ac_always_on() {
+        let config = create_test_config();
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .ac_always_on = true;
+        let engine = RuleEngine::new(config.clone());
+
+        let on = |power_state, context: SystemContext| SystemContext {
+            power_state,
+            ..context
+        };
+
+        // Idle and before the first schedule, the backlight stays at 1 on AC
+        assert_eq!(
+            engine
+                .evaluate(&on(PowerState::AC, create_context(true, false, 15, 0)))
+                .action,
+            BrightnessDecision::SetBrightness(1)
+        );
+        assert_eq!(
+            engine
+                .evaluate(&on(PowerState::AC, create_context(false, false, 8, 0)))
+                .action,
+            BrightnessDecision::SetBrightness(1)
+        );
+        // Brighter schedules are lowered to 1
+        assert_eq!(
+            engine
+                .evaluate(&on(PowerState::AC, create_context(false, false, 15, 0)))
+                .action,
+            BrightnessDecision::SetBrightness(1)
+        );
+        // Video playback still turns it off
+        let video = SystemContext {
+            is_video_playing: true,
+            ..create_context(false, false, 15, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&on(PowerState::AC, video)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+        // No effect on battery
+        assert_eq!(
+            engine
+                .evaluate(&on(PowerState::Battery, create_context(true, false, 15, 0)))
+                .action,
+            BrightnessDecision::SetBrightness(0)
+        );
+
+        // With an explicit on_ac policy, ac_always_on is only a floor
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .on_ac = Some(PowerSourcePolicy::default());
+        assert_eq!(
+            engine
+                .evaluate(&on(PowerState::AC, create_context(false, false, 15, 0)))
+                .action,
+            BrightnessDecision::SetBrightness(3)
+        );
+    }
+
+    #[test]
+    fn test_power_source_policies() {
+        let config = create_test_config();
+        {
+            let mut config = config.write().unwrap();
+            let profile = config.profiles.get_mut("test").unwrap();
+            profile.on_ac = Some(PowerSourcePolicy {
+                min_brightness: Some(BrightnessValue::Level(2)),
+                ..Default::default()
+            });
+            profile.on_battery = Some(PowerSourcePolicy {
+                time_schedules: Some(vec![TimeSchedule {
+                    hour: Some(20),
+                    minute: Some(0),
+                    brightness: BrightnessValue::Level(3),
+                    ..Default::default()
+                }]),
+                idle_timeout: Some(5),
+                max_brightness: Some(BrightnessValue::Percent(70)),
+                ..Default::default()
+            });
+        }
+        let mut engine = RuleEngine::new(config.clone());
+
+        let on = |power_state, hour| SystemContext {
+            power_state,
+            ..create_context(false, false, hour, 0)
+        };
+
+        // AC floor raises the 22:00 schedule and the unscheduled morning
+        assert_eq!(
+            engine.evaluate(&on(PowerState::AC, 23)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+        assert_eq!(
+            engine.evaluate(&on(PowerState::AC, 7)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+        assert_eq!(
+            engine.evaluate(&on(PowerState::AC, 15)).action,
+            BrightnessDecision::SetBrightness(3)
+        );
+
+        // Battery schedules replace the profile's, capped at 70% of 3
+        assert_eq!(
+            engine.evaluate(&on(PowerState::Battery, 15)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+        assert_eq!(
+            engine.evaluate(&on(PowerState::Battery, 23)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+        assert_eq!(
+            engine.evaluate(&on(PowerState::Battery, 21)).action,
+            BrightnessDecision::SetBrightness(2)
+        );
+
+        // Unknown power source uses the plain profile
+        assert_eq!(
+            engine.evaluate(&on(PowerState::Unknown, 23)).action,
+            BrightnessDecision::SetBrightness(1)
+        );
+
+        // Manual override is never limited
+        engine.set_manual_override(Some(3));
+        assert_eq!(
+            engine.evaluate(&on(PowerState::Battery, 21)).action,
+            BrightnessDecision::SetBrightness(3)
+        );
+
+        let config = config.read().unwrap();
+        let profile = &config.profiles["test"];
+        assert_eq!(profile.idle_timeout_for(PowerState::Battery, None), 5);
+        assert_eq!(profile.idle_timeout_for(PowerState::AC, None), 10);
+    }
+
+    #[test]
+    fn test_power_source_floor_yields_to_meetings() {
+        let config = create_test_config();
+        {
+            let mut config = config.write().unwrap();
+            let profile = config.profiles.get_mut("test").unwrap();
+            profile.on_ac = Some(PowerSourcePolicy {
+                min_brightness: Some(BrightnessValue::Level(2)),
+                max_brightness: Some(BrightnessValue::Level(2)),
+                ..Default::default()
+            });
+            profile.meeting = Some(MeetingRule {
+                enabled: true,
+                brightness: BrightnessValue::Level(0),
+                suppress_idle: true,
+                detect_webcam: true,
+                detect_screen_sharing: true,
+            });
+        }
+        let engine = RuleEngine::new(config);
+
+        let context = SystemContext {
+            power_state: PowerState::AC,
+            is_in_meeting: true,
+            ..create_context(false, false, 15, 0)
+        };
+        assert_eq!(
+            engine.evaluate(&context).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+        // The cap applies to the schedule
+        assert_eq!(
+            engine
+                .evaluate(&SystemContext {
+                    is_in_meeting: false,
+                    ..context
+                })
+                .action,
+            BrightnessDecision::SetBrightness(2)
+        );
+    }
+
+    #[test]
+    fn test_power_mode_overrides() {
+        let config = create_test_config();
+        {
+            let mut config = config.write().unwrap();
+            let profile = config.profiles.get_mut("test").unwrap();
+            profile.power_modes.insert(
+                PowerMode::PowerSaver,
+                PowerModeOverride {
+                    max_brightness: Some(BrightnessValue::Level(1)),
+                    idle_timeout: Some(5),
+                },
+            );
+            profile.on_battery = Some(PowerSourcePolicy {
+                idle_timeout: Some(20),
+                ..Default::default()
+            });
+        }
+        let engine = RuleEngine::new(config.clone());
+
+        let in_mode = |power_mode| SystemContext {
+            power_mode,
+            ..create_context(false, false, 15, 0)
+        };
+        assert_eq!(
+            engine
+                .evaluate(&in_mode(Some(PowerMode::PowerSaver)))
+                .action,
+            BrightnessDecision::SetBrightness(1)
+        );
+        assert_eq!(
+            engine.evaluate(&in_mode(Some(PowerMode::Balanced))).action,
+            BrightnessDecision::SetBrightness(3)
+        );
+        assert_eq!(
+            engine.evaluate(&in_mode(None)).action,
+            BrightnessDecision::SetBrightness(3)
+        );
+
+        // The power mode only ever shortens the idle timeout
+        let config = config.read().unwrap();
+        let profile = &config.profiles["test"];
+        let saver = Some(PowerMode::PowerSaver);
+        assert_eq!(profile.idle_timeout_for(PowerState::Battery, saver), 5);
+        assert_eq!(profile.idle_timeout_for(PowerState::Battery, None), 20);
+        assert_eq!(
+            profile.idle_timeout_for(PowerState::AC, Some(PowerMode::Performance)),
+            10
+        );
+    }
+
+    #[test]
+    fn test_sunset_schedules() {
+        let config = create_test_config();
+        {
+            let mut config = config.write().unwrap();
+            config.latitude = Some(51.5);
+            config.longitude = Some(0.0);
+            config.profiles.get_mut("test").unwrap().time_schedules = vec![
+                TimeSchedule {
+                    brightness: BrightnessValue::Level(0),
+                    at: Some("sunrise".to_string().try_into().unwrap()),
+                    ..Default::default()
+                },
+                TimeSchedule {
+                    brightness: BrightnessValue::Level(2),
+                    at: Some("sunset-30m".to_string().try_into().unwrap()),
+                    ..Default::default()
+                },
+            ];
+        }
+        let config = config.read().unwrap();
+        let schedules = &config.profiles["test"].time_schedules;
+
+        // Pinned to UTC so the result doesn't depend on the machine's timezone
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let SunTimes::Normal { sunrise, sunset } = solar::sun_times(date, 51.5, 0.0) else {
+            panic!("London always has a sunrise and sunset");
+        };
+        let brightness_at = |time: DateTime<Utc>| {
+            latest_schedule(schedules, &time, config.coordinates(), &Holidays::default())
+                .map(|(_, schedule)| schedule.brightness)
+        };
+        let minutes = chrono::Duration::minutes;
+
+        assert_eq!(
+            brightness_at(sunset - minutes(25)),
+            Some(BrightnessValue::Level(2))
+        );
+        assert_eq!(
+            brightness_at(sunset - minutes(35)),
+            Some(BrightnessValue::Level(0))
+        );
+        assert_eq!(
+            brightness_at(sunrise + minutes(5)),
+            Some(BrightnessValue::Level(0))
+        );
+    }
+
+    #[test]
+    fn test_day_filters_and_holidays() {
+        let config = create_test_config();
+        {
+            let mut config = config.write().unwrap();
+            config.holidays = Some("holidays.txt".into());
+            config.holiday_calendar = Holidays::parse("2024-04-01\n").unwrap();
+            let entry = |hour, brightness, days: &[ScheduleDay]| TimeSchedule {
+                hour: Some(hour),
+                brightness: BrightnessValue::Level(brightness),
+                days: days.to_vec(),
+                ..Default::default()
+            };
+            let date = |text| {
+                Some(ScheduleDate(
+                    NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap(),
+                ))
+            };
+            config.profiles.get_mut("test").unwrap().time_schedules = vec![
+                entry(8, 2, &[ScheduleDay::Weekdays]),
+                entry(18, 0, &[ScheduleDay::Weekdays]),
+                entry(11, 1, &[ScheduleDay::Weekends, ScheduleDay::Holidays]),
+                entry(20, 0, &[]),
+                TimeSchedule {
+                    from: date("2024-12-20"),
+                    until: date("2024-12-31"),
+                    ..entry(7, 3, &[])
+                },
+            ];
+        }
+        let engine = RuleEngine::new(config);
+        let at = |month, day, hour| {
+            let context = SystemContext {
+                current_time: Local
+                    .with_ymd_and_hms(2024, month, day, hour, 30, 0)
+                    .earliest()
+                    .unwrap(),
+                ..create_context(false, false, 0, 0)
+            };
+            engine.evaluate(&context).action
+        };
+
+        // Friday 2024-03-15 follows the weekday schedule
+        assert_eq!(at(3, 15, 9), BrightnessDecision::SetBrightness(2));
+        // Saturday morning carries over Friday's 20:00 entry, not the weekday 08:00 one
+        assert_eq!(at(3, 16, 9), BrightnessDecision::SetBrightness(0));
+        assert_eq!(at(3, 16, 12), BrightnessDecision::SetBrightness(1));
+        // Monday before 08:00 still uses Sunday evening's entry
+        assert_eq!(at(3, 18, 7), BrightnessDecision::SetBrightness(0));
+        assert_eq!(at(3, 18, 9), BrightnessDecision::SetBrightness(2));
+
+        // Easter Monday is a holiday: weekday entries are skipped
+        assert_eq!(at(4, 1, 9), BrightnessDecision::SetBrightness(0));
+        assert_eq!(at(4, 1, 12), BrightnessDecision::SetBrightness(1));
+        assert_eq!(at(4, 2, 9), BrightnessDecision::SetBrightness(2));
+
+        // Date ranges are inclusive
+        assert_eq!(at(12, 19, 7), BrightnessDecision::SetBrightness(0));
+        assert_eq!(at(12, 20, 7), BrightnessDecision::SetBrightness(3));
+        assert_eq!(at(12, 31, 7), BrightnessDecision::SetBrightness(3));
+    }
+
+    /// Central European time for 2024: UTC+2 from 31 March 01:00 UTC until
+    /// 27 October 01:00 UTC, UTC+1 otherwise
+    #[derive(Debug, Clone, Copy)]
+    struct Cet2024;
+
+    impl Cet2024 {
+        fn winter() -> FixedOffset {
+            FixedOffset::east_opt(3600).unwrap()
+        }
+
+        fn summer() -> FixedOffset {
+            FixedOffset::east_opt(7200).unwrap()
+        }
+    }
+
+    impl TimeZone for Cet2024 {
+        type Offset = FixedOffset;
+
+        fn from_offset(_offset: &FixedOffset) -> Self {
+            Cet2024
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> MappedLocalTime<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_local_datetime(
+            &self,
+            local: &NaiveDateTime,
+        ) -> MappedLocalTime<FixedOffset> {
+            let valid: Vec<_> = [Self::summer(), Self::winter()]
+                .into_iter()
+                .filter(|offset| self.offset_from_utc_datetime(&(*local - *offset)) == *offset)
+                .collect();
+            match valid[..] {
+                [] => MappedLocalTime::None,
+                [offset] => MappedLocalTime::Single(offset),
+                [earliest, latest] => MappedLocalTime::Ambiguous(earliest, latest),
+                _ => unreachable!(),
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            let utc_time = |month, day| {
+                NaiveDate::from_ymd_opt(2024, month, day)
+                    .unwrap()
+                    .and_hms_opt(1, 0, 0)
+                    .unwrap()
+            };
+            if (utc_time(3, 31)..utc_time(10, 27)).contains(utc) {
+                Self::summer()
+            } else {
+                Self::winter()
+            }
+        }
+    }
+
+    fn utc(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Cet2024> {
+        Utc.with_ymd_and_hms(2024, month, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Cet2024)
+    }
+
+    fn schedule(hour: u8, minute: u8, brightness: u32) -> TimeSchedule {
+        TimeSchedule {
+            hour: Some(hour),
+            minute: Some(minute),
+            brightness: BrightnessValue::Level(brightness),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_schedule_carries_over_midnight() {
+        let schedules = [schedule(20, 0, 2)];
+        let holidays = Holidays::default();
+        let brightness =
+            |now| latest_schedule(&schedules, &now, None, &holidays).map(|(_, s)| s.brightness);
+
+        // 20:00 -> 2 alone stays on through the night and the next day
+        assert_eq!(
+            brightness(utc(6, 10, 17, 59)),
+            Some(BrightnessValue::Level(2))
+        );
+        assert_eq!(
+            brightness(utc(6, 10, 22, 0)),
+            Some(BrightnessValue::Level(2))
+        );
+        assert_eq!(
+            brightness(utc(6, 11, 9, 0)),
+            Some(BrightnessValue::Level(2))
+        );
+
+        // A week without an applicable entry falls back to the default
+        let weekly = [TimeSchedule {
+            days: vec![ScheduleDay::Day(chrono::Weekday::Mon)],
+            ..schedule(20, 0, 2)
+        }];
+        // Monday 2024-06-10 20:00 local
+        let (since, _) = latest_schedule(&weekly, &utc(6, 16, 12, 0), None, &holidays).unwrap();
+        assert_eq!(since, utc(6, 10, 18, 0));
+        assert!(latest_schedule(&weekly, &utc(6, 10, 17, 0), None, &holidays).is_some());
+        let until = TimeSchedule {
+            until: Some(ScheduleDate(NaiveDate::from_ymd_opt(2024, 6, 1).unwrap())),
+            ..schedule(20, 0, 2)
+        };
+        assert!(latest_schedule(&[until], &utc(6, 10, 12, 0), None, &holidays).is_none());
+    }
+
+    #[test]
+    fn test_schedule_dst_transitions() {
+        let holidays = Holidays::default();
+
+        // Clocks go from 02:00 to 03:00 on 31 March: 02:30 takes effect at 03:00
+        let schedules = [schedule(2, 30, 2), schedule(2, 45, 3), schedule(22, 0, 0)];
+        let at = |now| latest_schedule(&schedules, &now, None, &holidays).unwrap();
+        assert_eq!(
+            at(utc(3, 31, 0, 59)).1.brightness,
+            BrightnessValue::Level(0)
+        );
+        let (since, entry) = at(utc(3, 31, 1, 0));
+        // Both entries move to 03:00; the later wall-clock time wins
+        assert_eq!(entry.brightness, BrightnessValue::Level(3));
+        assert_eq!(since, utc(3, 31, 1, 0));
+
+        // Clocks go from 03:00 back to 02:00 on 27 October: 02:30 takes effect the first
+        // time and stays in effect during the repeated hour
+        let schedules = [schedule(2, 30, 2), schedule(22, 0, 0)];
+        let at = |now| latest_schedule(&schedules, &now, None, &holidays).unwrap();
+        // 02:15 CEST
+        assert_eq!(
+            at(utc(10, 27, 0, 15)).1.brightness,
+            BrightnessValue::Level(0)
+        );
+        // 02:30 CEST
+        assert_eq!(
+            at(utc(10, 27, 0, 30)).1.brightness,
+            BrightnessValue::Level(2)
+        );
+        // 02:15 CET, the second pass
+        let (since, entry) = at(utc(10, 27, 1, 15));
+        assert_eq!(entry.brightness, BrightnessValue::Level(2));
+        assert_eq!(since, utc(10, 27, 0, 30));
+    }
+
+    #[test]
+    fn test_interpolated_schedules() {
+        let config = create_test_config();
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .time_schedules = vec![
+            TimeSchedule {
+                interpolate: true,
+                ..schedule(18, 0, 0)
+            },
+            schedule(20, 0, 3),
+            TimeSchedule {
+                interpolate: true,
+                ..schedule(23, 0, 3)
+            },
+            schedule(23, 30, 0),
+        ];
+        let engine = RuleEngine::new(config);
+        let at = |hour, minute| {
+            engine
+                .evaluate(&create_context(false, false, hour, minute))
+                .action
+        };
+
+        // 0 -> 3 over two hours, rounded to the nearest level
+        assert_eq!(at(17, 59), BrightnessDecision::SetBrightness(0));
+        assert_eq!(at(18, 0), BrightnessDecision::SetBrightness(0));
+        assert_eq!(at(18, 19), BrightnessDecision::SetBrightness(0));
+        assert_eq!(at(18, 20), BrightnessDecision::SetBrightness(1));
+        assert_eq!(at(19, 0), BrightnessDecision::SetBrightness(2));
+        assert_eq!(at(19, 40), BrightnessDecision::SetBrightness(3));
+        assert_eq!(at(20, 0), BrightnessDecision::SetBrightness(3));
+        assert_eq!(at(22, 59), BrightnessDecision::SetBrightness(3));
+        // Ramps down as well
+        assert_eq!(at(23, 10), BrightnessDecision::SetBrightness(2));
+        assert_eq!(at(23, 20), BrightnessDecision::SetBrightness(1));
+        assert_eq!(at(23, 30), BrightnessDecision::SetBrightness(0));
+
+        // Idle still wins over a ramp
+        assert_eq!(
+            engine.evaluate(&create_context(true, false, 19, 0)).action,
+            BrightnessDecision::SetBrightness(0)
+        );
+    }
+
+    #[test]
+    fn test_percent_schedule_ramp() {
+        let config = create_test_config();
+        let ramp: Vec<TimeSchedule> = toml::from_str::<LocationProfile>(
+            r#"
+            name = "test"
+            idle_timeout = 30
+
+            [[time_schedules]]
+            hour = 18
+            brightness = "0%"
+            interpolate = true
+
+            [[time_schedules]]
+            hour = 20
+            brightness = "100%"
+            "#,
+        )
+        .unwrap()
+        .time_schedules;
+        assert_eq!(ramp[1].brightness, BrightnessValue::Percent(100));
+        config
+            .write()
+            .unwrap()
+            .profiles
+            .get_mut("test")
+            .unwrap()
+            .time_schedules = ramp;
+        let engine = RuleEngine::new(config);
+        let at = |hour, minute| {
+            engine
+                .evaluate(&create_context(false, false, hour, minute))
+                .action
+        };
+
+        // 0% -> 100% on a 3-step device ramps through every level
+        assert_eq!(at(18, 0), BrightnessDecision::SetBrightness(0));
+        assert_eq!(at(18, 40), BrightnessDecision::SetBrightness(1));
+        assert_eq!(at(19, 20), BrightnessDecision::SetBrightness(2));
+        assert_eq!(at(20, 0), BrightnessDecision::SetBrightness(3));
+    }
+
+    #[test]
+    fn test_interpolate_brightness() {
+        assert_eq!(interpolate_brightness(0, 3, 0, 7200, 3), 0);
+        assert_eq!(interpolate_brightness(0, 3, 3600, 7200, 3), 2);
+        assert_eq!(interpolate_brightness(0, 3, 7200, 7200, 3), 3);
+        assert_eq!(interpolate_brightness(0, 3, 9000, 7200, 3), 3);
+        assert_eq!(interpolate_brightness(3, 1, 1800, 3600, 3), 2);
+        // Levels above the device maximum are clamped before interpolating
+        assert_eq!(interpolate_brightness(0, 100, 3600, 7200, 3), 2);
+        assert_eq!(interpolate_brightness(1, 2, 0, 0, 3), 2);
+    }
+
+    #[test]
+    fn test_next_schedule() {
+        let schedules = [schedule(8, 0, 1), schedule(20, 0, 2)];
+        let holidays = Holidays::default();
+        // 2024-06-10 19:00 CEST
+        let (at, entry) = next_schedule(&schedules, &utc(6, 10, 17, 0), None, &holidays).unwrap();
+        assert_eq!(
+            (at, entry.brightness),
+            (utc(6, 10, 18, 0), BrightnessValue::Level(2))
+        );
+        // After the last entry of the day, the next one is tomorrow morning
+        let (at, entry) = next_schedule(&schedules, &utc(6, 10, 18, 0), None, &holidays).unwrap();
+        assert_eq!(
+            (at, entry.brightness),
+            (utc(6, 11, 6, 0), BrightnessValue::Level(1))
+        );
+        assert!(next_schedule(&[], &utc(6, 10, 18, 0), None, &holidays).is_none());
+    }
+
+    fn schedule_strategy() -> impl Strategy<Value = TimeSchedule> {
+        let days = prop::sample::subsequence(
+            vec![
+                ScheduleDay::Day(chrono::Weekday::Mon),
+                ScheduleDay::Day(chrono::Weekday::Wed),
+                ScheduleDay::Day(chrono::Weekday::Sat),
+                ScheduleDay::Weekdays,
+                ScheduleDay::Weekends,
+            ],
+            0..=2,
+        );
+        (0u8..24, 0u8..60, 0u32..4, days).prop_map(|(hour, minute, brightness, days)| {
+            TimeSchedule {
+                days,
+                ..schedule(hour, minute, brightness)
+            }
+        })
+    }
+
+    /// Brute force: walk back minute by minute to the last time an entry fired
+    fn reference_schedule(
+        schedules: &[TimeSchedule],
+        now: NaiveDateTime,
+    ) -> Option<BrightnessValue> {
+        let lookback = (SCHEDULE_LOOKBACK_DAYS as i64 + 1) * 24 * 60;
+        (0..lookback)
+            .map(|minutes| now - chrono::Duration::minutes(minutes))
+            .take_while(|time| time.date() >= now.date() - Days::new(SCHEDULE_LOOKBACK_DAYS))
+            .find_map(|time| {
+                let fired: Vec<_> = schedules
+                    .iter()
+                    .filter(|s| s.applies_on(time.date(), &Holidays::default()))
+                    .filter(|s| {
+                        s.hour.map(u32::from) == Some(time.hour())
+                            && u32::from(s.minute.unwrap_or_default()) == time.minute()
+                    })
+                    .collect();
+                fired.first().map(|s| s.brightness)
+            })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_latest_schedule_matches_reference(
+            schedules in prop::collection::vec(schedule_strategy(), 0..6),
+            minutes in 0i64..(14 * 24 * 60),
+        ) {
+            let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap()
+                + chrono::Duration::minutes(minutes);
+            let actual = latest_schedule(&schedules, &now, None, &Holidays::default())
+                .map(|(_, s)| s.brightness);
+            prop_assert_eq!(actual, reference_schedule(&schedules, now.naive_utc()));
+        }
+
+        #[test]
+        fn prop_schedule_entries_apply_in_order_across_dst(
+            schedules in prop::collection::vec(schedule_strategy(), 1..6),
+            spring in any::<bool>(),
+        ) {
+            let mut schedules = schedules;
+            // One entry that applies every day, so something is always in effect
+            schedules[0].days.clear();
+            let holidays = Holidays::default();
+
+            // Walk through the day of the DST change, one minute at a time
+            let start = if spring { utc(3, 30, 23, 0) } else { utc(10, 26, 22, 0) };
+            let mut previous = latest_schedule(&schedules, &start, None, &holidays)
+                .map(|(since, _)| since);
+            for minute in 1..=(25 * 60) {
+                let now = start + chrono::Duration::minutes(minute);
+                let current = latest_schedule(&schedules, &now, None, &holidays);
+                prop_assert!(current.is_some());
+                let (since, _) = current.unwrap();
+                prop_assert!(since <= now);
+                // Entries only ever take over when they fire, never by going back in time
+                if Some(since) != previous {
+                    prop_assert!(Some(since) > previous);
+                    prop_assert!(since > now - chrono::Duration::minutes(1));
+                }
+                previous = Some(since);
+            }
+        }
+    }
 }
@@ -0,0 +1,225 @@
+// Suspend/resume handling via systemd-logind
+// Holds a delay inhibitor so the daemon can turn the backlight off before the system sleeps
+use crate::{Error, Result};
+use futures_lite::StreamExt;
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use zbus::zvariant::OwnedFd;
+use zbus::{proxy, Connection};
+
+/// Well-known bus name of systemd-logind
+const LOGIND_BUS_NAME: &str = "org.freedesktop.login1";
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1",
+    gen_blocking = false
+)]
+trait LogindManager {
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// A suspend/resume transition reported by logind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepEvent {
+    /// The system is about to sleep; sleep is delayed until the inhibitor is released
+    Suspending,
+    /// The system has woken up
+    Resumed,
+}
+
+pub struct SleepMonitor {
+    manager: LogindManagerProxy<'static>,
+    events: tokio::sync::Mutex<mpsc::UnboundedReceiver<SleepEvent>>,
+    inhibitor: Mutex<Option<OwnedFd>>,
+    watcher: JoinHandle<()>,
+}
+
+impl SleepMonitor {
+    /// Connect to logind on the system bus
+    pub async fn with_system_bus() -> Result<Self> {
+        let conn = Connection::system().await.map_err(|e| {
+            Error::monitor_unavailable(
+                "Sleep",
+                format!("D-Bus system bus unavailable: {}", e),
+                "The backlight is not turned off before suspend",
+            )
+        })?;
+        Self::new(conn).await
+    }
+
+    /// Connect to logind on the given bus, subscribe to PrepareForSleep and take a
+    /// delay inhibitor
+    pub async fn new(conn: Connection) -> Result<Self> {
+        let dbus = zbus::fdo::DBusProxy::new(&conn)
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create D-Bus proxy: {}", e)))?;
+        let has_owner = dbus
+            .name_has_owner(LOGIND_BUS_NAME.try_into().expect("valid bus name"))
+            .await
+            .unwrap_or(false);
+        if !has_owner {
+            return Err(Error::monitor_unavailable(
+                "Sleep",
+                "org.freedesktop.login1 is not running",
+                "The backlight is not turned off before suspend",
+            ));
+        }
+
+        let manager = LogindManagerProxy::new(&conn)
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
+        let mut signals = manager.receive_prepare_for_sleep().await.map_err(|e| {
+            Error::ipc_protocol(format!("Failed to subscribe to PrepareForSleep: {}", e))
+        })?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let watcher = tokio::spawn(async move {
+            while let Some(signal) = signals.next().await {
+                let Ok(args) = signal.args() else { continue };
+                let event = if args.start {
+                    SleepEvent::Suspending
+                } else {
+                    SleepEvent::Resumed
+                };
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let monitor = Self {
+            manager,
+            events: tokio::sync::Mutex::new(receiver),
+            inhibitor: Mutex::new(None),
+            watcher,
+        };
+        monitor.inhibit().await;
+        Ok(monitor)
+    }
+
+    /// Wait for the next suspend or resume (cancel safe, for use in select!)
+    pub async fn next_event(&self) -> SleepEvent {
+        match self.events.lock().await.recv().await {
+            Some(event) => event,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Let the pending suspend proceed
+    pub fn release_inhibitor(&self) {
+        self.inhibitor.lock().unwrap().take();
+    }
+
+    /// Whether the monitor currently delays suspend
+    pub fn is_inhibiting(&self) -> bool {
+        self.inhibitor.lock().unwrap().is_some()
+    }
+
+    /// Take a delay inhibitor unless one is already held
+    /// Called again after each resume to delay the next suspend
+    pub async fn inhibit(&self) {
+        if self.is_inhibiting() {
+            return;
+        }
+        match self
+            .manager
+            .inhibit(
+                "sleep",
+                "kbd-backlight",
+                "Turn off the keyboard backlight before sleep",
+                "delay",
+            )
+            .await
+        {
+            Ok(fd) => *self.inhibitor.lock().unwrap() = Some(fd),
+            // Without the inhibitor the light is still switched off, just not guaranteed in time
+            Err(e) => eprintln!("Warning: Failed to take sleep inhibitor: {}", e),
+        }
+    }
+}
+
+impl Drop for SleepMonitor {
+    fn drop(&mut self) {
+        self.watcher.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::PrivateBus;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::time::{timeout, Duration};
+    use zbus::interface;
+    use zbus::object_server::SignalEmitter;
+
+    struct FakeLogind {
+        inhibits: Arc<AtomicUsize>,
+    }
+
+    #[interface(name = "org.freedesktop.login1.Manager")]
+    impl FakeLogind {
+        fn inhibit(&self, what: &str, _who: &str, _why: &str, mode: &str) -> OwnedFd {
+            assert_eq!((what, mode), ("sleep", "delay"));
+            self.inhibits.fetch_add(1, Ordering::SeqCst);
+            let file = std::fs::File::open("/dev/null").unwrap();
+            std::os::fd::OwnedFd::from(file).into()
+        }
+
+        #[zbus(signal)]
+        async fn prepare_for_sleep(emitter: &SignalEmitter<'_>, start: bool) -> zbus::Result<()>;
+    }
+
+    #[tokio::test]
+    async fn test_sleep_monitor() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        assert!(SleepMonitor::new(bus.connect().await).await.is_err());
+
+        let inhibits = Arc::new(AtomicUsize::new(0));
+        let service = zbus::connection::Builder::address(bus.address())
+            .unwrap()
+            .name(LOGIND_BUS_NAME)
+            .unwrap()
+            .serve_at(
+                "/org/freedesktop/login1",
+                FakeLogind {
+                    inhibits: Arc::clone(&inhibits),
+                },
+            )
+            .unwrap()
+            .build()
+            .await
+            .expect("Failed to start mock logind");
+        let emitter = SignalEmitter::new(&service, "/org/freedesktop/login1").unwrap();
+
+        let monitor = SleepMonitor::new(bus.connect().await).await.unwrap();
+        assert!(monitor.is_inhibiting());
+        assert_eq!(inhibits.load(Ordering::SeqCst), 1);
+
+        FakeLogind::prepare_for_sleep(&emitter, true).await.unwrap();
+        let event = timeout(Duration::from_secs(5), monitor.next_event()).await;
+        assert_eq!(event.unwrap(), SleepEvent::Suspending);
+        monitor.release_inhibitor();
+        assert!(!monitor.is_inhibiting());
+
+        // A new inhibitor is taken for the next suspend
+        FakeLogind::prepare_for_sleep(&emitter, false)
+            .await
+            .unwrap();
+        let event = timeout(Duration::from_secs(5), monitor.next_event()).await;
+        assert_eq!(event.unwrap(), SleepEvent::Resumed);
+        monitor.inhibit().await;
+        assert!(monitor.is_inhibiting());
+        assert_eq!(inhibits.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,133 @@
+// Sunrise and sunset calculation
+// Uses the NOAA sunrise equation, accurate to a few minutes without any network access
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Julian date of 2000-01-01 12:00 UTC (J2000 epoch)
+const J2000: f64 = 2451545.0;
+/// Julian date of the Unix epoch
+const UNIX_EPOCH_JD: f64 = 2440587.5;
+/// Sun altitude at sunrise/sunset, including refraction and the solar disc radius
+const SUNRISE_ALTITUDE: f64 = -0.833;
+/// Obliquity of the ecliptic
+const EARTH_OBLIQUITY: f64 = 23.4397;
+
+/// Sunrise and sunset on a given day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SunTimes {
+    Normal {
+        sunrise: DateTime<Utc>,
+        sunset: DateTime<Utc>,
+    },
+    /// The sun never sets (midnight sun)
+    PolarDay,
+    /// The sun never rises
+    PolarNight,
+}
+
+/// Compute sunrise and sunset for a date at the given coordinates (degrees, east positive)
+pub fn sun_times(date: NaiveDate, latitude: f64, longitude: f64) -> SunTimes {
+    let days = date
+        .signed_duration_since(NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid date"))
+        .num_days() as f64;
+
+    // Mean solar noon
+    let j_star = days - longitude / 360.0;
+    let mean_anomaly = (357.5291 + 0.98560028 * j_star).rem_euclid(360.0);
+    let m = mean_anomaly.to_radians();
+    let center = 1.9148 * m.sin() + 0.0200 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic_longitude = (mean_anomaly + center + 180.0 + 102.9372).rem_euclid(360.0);
+    let lambda = ecliptic_longitude.to_radians();
+    let transit = J2000 + j_star + 0.0053 * m.sin() - 0.0069 * (2.0 * lambda).sin();
+
+    let declination = (lambda.sin() * EARTH_OBLIQUITY.to_radians().sin()).asin();
+    let phi = latitude.to_radians();
+    let cos_hour_angle = (SUNRISE_ALTITUDE.to_radians().sin() - phi.sin() * declination.sin())
+        / (phi.cos() * declination.cos());
+    if cos_hour_angle < -1.0 {
+        return SunTimes::PolarDay;
+    }
+    if cos_hour_angle > 1.0 {
+        return SunTimes::PolarNight;
+    }
+
+    let half_day = cos_hour_angle.acos().to_degrees() / 360.0;
+    match (
+        julian_to_utc(transit - half_day),
+        julian_to_utc(transit + half_day),
+    ) {
+        (Some(sunrise), Some(sunset)) => SunTimes::Normal { sunrise, sunset },
+        _ => SunTimes::PolarNight,
+    }
+}
+
+fn julian_to_utc(julian: f64) -> Option<DateTime<Utc>> {
+    let seconds = ((julian - UNIX_EPOCH_JD) * 86400.0).round() as i64;
+    DateTime::from_timestamp(seconds, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn assert_close(actual: DateTime<Utc>, expected: DateTime<Utc>) {
+        let diff = (actual - expected).num_minutes().abs();
+        assert!(
+            diff <= 3,
+            "{} differs from {} by {} minutes",
+            actual,
+            expected,
+            diff
+        );
+    }
+
+    #[test]
+    fn test_london_midsummer() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let SunTimes::Normal { sunrise, sunset } = sun_times(date, 51.5074, -0.1278) else {
+            panic!("expected sunrise and sunset");
+        };
+        assert_close(
+            sunrise,
+            Utc.with_ymd_and_hms(2024, 6, 21, 3, 43, 0).unwrap(),
+        );
+        assert_close(
+            sunset,
+            Utc.with_ymd_and_hms(2024, 6, 21, 20, 21, 0).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_southern_hemisphere_and_west_longitude() {
+        // Sydney, 2024-12-21: 05:41 and 20:05 AEDT
+        let date = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+        let SunTimes::Normal { sunrise, sunset } = sun_times(date, -33.8688, 151.2093) else {
+            panic!("expected sunrise and sunset");
+        };
+        assert_close(
+            sunrise,
+            Utc.with_ymd_and_hms(2024, 12, 20, 18, 41, 0).unwrap(),
+        );
+        assert_close(sunset, Utc.with_ymd_and_hms(2024, 12, 21, 9, 5, 0).unwrap());
+
+        // San Francisco, 2024-03-20: 07:13 and 19:23 PDT
+        let date = NaiveDate::from_ymd_opt(2024, 3, 20).unwrap();
+        let SunTimes::Normal { sunrise, sunset } = sun_times(date, 37.7749, -122.4194) else {
+            panic!("expected sunrise and sunset");
+        };
+        assert_close(
+            sunrise,
+            Utc.with_ymd_and_hms(2024, 3, 20, 14, 13, 0).unwrap(),
+        );
+        assert_close(sunset, Utc.with_ymd_and_hms(2024, 3, 21, 2, 23, 0).unwrap());
+    }
+
+    #[test]
+    fn test_polar_day_and_night() {
+        // Tromsø
+        let summer = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        let winter = NaiveDate::from_ymd_opt(2024, 12, 21).unwrap();
+        assert_eq!(sun_times(summer, 69.6492, 18.9553), SunTimes::PolarDay);
+        assert_eq!(sun_times(winter, 69.6492, 18.9553), SunTimes::PolarNight);
+    }
+}
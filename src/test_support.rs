@@ -0,0 +1,59 @@
+// Test helpers shared across modules
+// Spawns a private dbus-daemon so D-Bus backends can be tested against fake services
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use zbus::Connection;
+
+/// A private D-Bus daemon that lives for the duration of a test
+pub struct PrivateBus {
+    child: Child,
+    address: String,
+}
+
+impl PrivateBus {
+    /// Start a private session bus
+    /// Returns None if dbus-daemon is not installed, so tests can skip gracefully
+    pub fn start() -> Option<Self> {
+        let mut child = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--nosyslog", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+
+        let stdout = child.stdout.take()?;
+        let mut address = String::new();
+        if BufReader::new(stdout).read_line(&mut address).is_err() || address.trim().is_empty() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+
+        Some(Self {
+            child,
+            address: address.trim().to_string(),
+        })
+    }
+
+    /// Get the bus address
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Open a new client connection to the private bus
+    pub async fn connect(&self) -> Connection {
+        zbus::connection::Builder::address(self.address())
+            .expect("Invalid private bus address")
+            .build()
+            .await
+            .expect("Failed to connect to private bus")
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
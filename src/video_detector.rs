@@ -1,6 +1,121 @@
 // Video playback detection using MPRIS D-Bus interface
+use crate::config::MediaPlayerFilter;
 use crate::{Error, Result};
-use zbus::{Connection, Result as ZbusResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::OwnedValue;
+use zbus::{proxy, Connection};
+
+/// Prefix shared by all MPRIS media player bus names
+pub const MPRIS_BUS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+/// MPRIS root interface (player identification)
+#[proxy(
+    interface = "org.mpris.MediaPlayer2",
+    default_path = "/org/mpris/MediaPlayer2",
+    gen_blocking = false
+)]
+trait MediaPlayer2 {
+    /// A friendly name to identify the media player
+    #[zbus(property)]
+    fn identity(&self) -> zbus::Result<String>;
+
+    /// The basename of the player's .desktop file
+    #[zbus(property)]
+    fn desktop_entry(&self) -> zbus::Result<String>;
+}
+
+/// MPRIS player interface (playback state)
+#[proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2",
+    gen_blocking = false
+)]
+trait Player {
+    /// The current playback status
+    #[zbus(property)]
+    fn playback_status(&self) -> zbus::Result<PlaybackStatus>;
+
+    /// Metadata of the current track
+    #[zbus(property)]
+    fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+}
+
+/// MPRIS PlaybackStatus values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+impl FromStr for PlaybackStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "Playing" => Ok(PlaybackStatus::Playing),
+            "Paused" => Ok(PlaybackStatus::Paused),
+            "Stopped" => Ok(PlaybackStatus::Stopped),
+            other => Err(Error::Parse(format!(
+                "Unknown MPRIS PlaybackStatus '{}'",
+                other
+            ))),
+        }
+    }
+}
+
+impl TryFrom<OwnedValue> for PlaybackStatus {
+    type Error = zbus::Error;
+
+    fn try_from(value: OwnedValue) -> zbus::Result<Self> {
+        let status = String::try_from(value)?;
+        status
+            .parse()
+            .map_err(|e: Error| zbus::Error::Failure(e.to_string()))
+    }
+}
+
+impl fmt::Display for PlaybackStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            PlaybackStatus::Playing => "Playing",
+            PlaybackStatus::Paused => "Paused",
+            PlaybackStatus::Stopped => "Stopped",
+        };
+        f.pad(s)
+    }
+}
+
+/// Snapshot of a single MPRIS media player
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaPlayer {
+    /// Full D-Bus name (e.g. org.mpris.MediaPlayer2.mpv)
+    pub bus_name: String,
+    pub identity: Option<String>,
+    pub desktop_entry: Option<String>,
+    pub status: PlaybackStatus,
+    /// Title of the current track, if the player reports one
+    pub title: Option<String>,
+}
+
+impl MediaPlayer {
+    /// Bus name without the MPRIS prefix (e.g. "mpv" or "firefox.instance_1_42")
+    pub fn short_name(&self) -> &str {
+        self.bus_name
+            .strip_prefix(MPRIS_BUS_PREFIX)
+            .unwrap_or(&self.bus_name)
+    }
+
+    /// Check whether this player is playing and honoured by the profile filter
+    pub fn is_suppressing(&self, filter: &MediaPlayerFilter) -> bool {
+        self.status == PlaybackStatus::Playing
+            && filter.allows(&self.bus_name, self.identity.as_deref())
+    }
+}
 
 pub struct VideoDetector {
     conn: Connection,
@@ -14,31 +129,35 @@ impl VideoDetector {
         })?;
 
         eprintln!("VideoDetector: Successfully connected to D-Bus");
-        Ok(Self { conn })
+        Ok(Self::with_connection(conn))
+    }
+
+    /// Create a detector on an existing D-Bus connection
+    pub fn with_connection(conn: Connection) -> Self {
+        Self { conn }
     }
 
-    /// Check if any video is currently playing
-    pub async fn is_video_playing(&self) -> Result<bool> {
-        // List all MPRIS media players
+    /// Check if any honoured player is currently playing
+    pub async fn is_video_playing(&self, filter: &MediaPlayerFilter) -> Result<bool> {
         let players = self.list_media_players().await?;
+        Ok(players.iter().any(|player| player.is_suppressing(filter)))
+    }
 
-        if players.is_empty() {
-            return Ok(false);
-        }
+    /// List all MPRIS media players on the bus with their current state
+    /// Players that don't report a valid PlaybackStatus are skipped
+    pub async fn list_media_players(&self) -> Result<Vec<MediaPlayer>> {
+        let mut players = Vec::new();
 
-        // Check each player for playback status
-        for player in players {
-            if let Ok(status) = self.get_playback_status(&player).await {
-                if status == "Playing" {
-                    return Ok(true);
-                }
+        for name in self.list_player_names().await? {
+            if let Ok(player) = self.get_player(&name).await {
+                players.push(player);
             }
         }
 
-        Ok(false)
+        Ok(players)
     }
 
-    async fn list_media_players(&self) -> Result<Vec<String>> {
+    async fn list_player_names(&self) -> Result<Vec<String>> {
         let proxy = zbus::fdo::DBusProxy::new(&self.conn)
             .await
             .map_err(|e| Error::ipc_protocol(format!("Failed to create D-Bus proxy: {}", e)))?;
@@ -48,55 +167,280 @@ impl VideoDetector {
             .await
             .map_err(|e| Error::ipc_protocol(format!("Failed to list D-Bus names: {}", e)))?;
 
-        Ok(names
+        let mut names: Vec<String> = names
             .into_iter()
             .map(|name| name.to_string())
-            .filter(|name| name.starts_with("org.mpris.MediaPlayer2."))
-            .collect())
-    }
-
-    async fn get_playback_status(&self, service_name: &str) -> Result<String> {
-        let proxy = zbus::Proxy::new(
-            &self.conn,
-            service_name,
-            "/org/mpris/MediaPlayer2",
-            "org.freedesktop.DBus.Properties",
-        )
-        .await
-        .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
-
-        let status: ZbusResult<zbus::zvariant::OwnedValue> = proxy
-            .call("Get", &("org.mpris.MediaPlayer2.Player", "PlaybackStatus"))
-            .await;
-
-        match status {
-            Ok(value) => {
-                // Extract string from variant
-                let status_str = format!("{:?}", value);
-                // Parse the status (it's wrapped in quotes and variant)
-                if status_str.contains("Playing") {
-                    Ok("Playing".to_string())
-                } else if status_str.contains("Paused") {
-                    Ok("Paused".to_string())
-                } else {
-                    Ok("Stopped".to_string())
-                }
-            }
-            Err(e) => Err(Error::ipc_protocol(format!(
-                "Failed to get playback status: {}",
-                e
-            ))),
-        }
+            .filter(|name| name.starts_with(MPRIS_BUS_PREFIX))
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    async fn player_proxy(&self, service_name: &str) -> Result<PlayerProxy<'static>> {
+        let builder = PlayerProxy::builder(&self.conn)
+            .destination(service_name.to_string())
+            .map_err(|e| Error::ipc_protocol(format!("Invalid bus name: {}", e)))?;
+
+        builder
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))
+    }
+
+    async fn get_playback_status(&self, service_name: &str) -> Result<PlaybackStatus> {
+        let proxy = self.player_proxy(service_name).await?;
+
+        proxy
+            .playback_status()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to get playback status: {}", e)))
+    }
+
+    /// Query identity, playback status and metadata for a single player
+    pub async fn get_player(&self, service_name: &str) -> Result<MediaPlayer> {
+        let status = self.get_playback_status(service_name).await?;
+
+        let root = MediaPlayer2Proxy::builder(&self.conn)
+            .destination(service_name.to_string())
+            .map_err(|e| Error::ipc_protocol(format!("Invalid bus name: {}", e)))?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .map_err(|e| Error::ipc_protocol(format!("Failed to create proxy: {}", e)))?;
+
+        // Identity and DesktopEntry are optional in practice, so failures are not fatal
+        let identity = root.identity().await.ok().filter(|s| !s.is_empty());
+        let desktop_entry = root.desktop_entry().await.ok().filter(|s| !s.is_empty());
+
+        let title = match self.player_proxy(service_name).await?.metadata().await {
+            Ok(metadata) => metadata
+                .get("xesam:title")
+                .and_then(|v| v.try_clone().ok())
+                .and_then(|v| String::try_from(v).ok())
+                .filter(|s| !s.is_empty()),
+            Err(_) => None,
+        };
+
+        Ok(MediaPlayer {
+            bus_name: service_name.to_string(),
+            identity,
+            desktop_entry,
+            status,
+            title,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::PrivateBus;
+    use zbus::interface;
+    use zbus::zvariant::Value;
+
+    struct FakeRoot {
+        identity: String,
+        desktop_entry: String,
+    }
+
+    #[interface(name = "org.mpris.MediaPlayer2")]
+    impl FakeRoot {
+        #[zbus(property)]
+        fn identity(&self) -> String {
+            self.identity.clone()
+        }
+
+        #[zbus(property)]
+        fn desktop_entry(&self) -> String {
+            self.desktop_entry.clone()
+        }
+    }
+
+    struct FakePlayer {
+        status: String,
+        title: String,
+    }
+
+    #[interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl FakePlayer {
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            self.status.clone()
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            let mut metadata = HashMap::new();
+            metadata.insert(
+                "xesam:title".to_string(),
+                Value::from(self.title.as_str()).try_into().unwrap(),
+            );
+            metadata
+        }
+    }
+
+    /// Register a fake MPRIS player on the private bus
+    async fn spawn_player(
+        bus: &PrivateBus,
+        name: &str,
+        identity: &str,
+        status: &str,
+    ) -> Connection {
+        zbus::connection::Builder::address(bus.address())
+            .unwrap()
+            .name(format!("{}{}", MPRIS_BUS_PREFIX, name))
+            .unwrap()
+            .serve_at(
+                "/org/mpris/MediaPlayer2",
+                FakeRoot {
+                    identity: identity.to_string(),
+                    desktop_entry: name.to_string(),
+                },
+            )
+            .unwrap()
+            .serve_at(
+                "/org/mpris/MediaPlayer2",
+                FakePlayer {
+                    status: status.to_string(),
+                    title: format!("{} track", identity),
+                },
+            )
+            .unwrap()
+            .build()
+            .await
+            .expect("Failed to register fake player")
+    }
 
     #[tokio::test]
     async fn test_video_detector_creation() {
         // This might fail in test environment without D-Bus
         let _ = VideoDetector::new().await;
     }
+
+    #[test]
+    fn test_playback_status_parsing() {
+        assert_eq!(
+            "Playing".parse::<PlaybackStatus>().unwrap(),
+            PlaybackStatus::Playing
+        );
+        assert_eq!(
+            "Paused".parse::<PlaybackStatus>().unwrap(),
+            PlaybackStatus::Paused
+        );
+        assert_eq!(
+            "Stopped".parse::<PlaybackStatus>().unwrap(),
+            PlaybackStatus::Stopped
+        );
+        // Unknown values must not silently map to Stopped
+        assert!("playing".parse::<PlaybackStatus>().is_err());
+        assert!("Buffering".parse::<PlaybackStatus>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_no_players() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let detector = VideoDetector::with_connection(bus.connect().await);
+        let filter = MediaPlayerFilter::default();
+        assert!(!detector.is_video_playing(&filter).await.unwrap());
+        assert!(detector.list_media_players().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_playing_player_detected() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let _paused = spawn_player(&bus, "vlc", "VLC media player", "Paused").await;
+        let detector = VideoDetector::with_connection(bus.connect().await);
+        let filter = MediaPlayerFilter::default();
+        assert!(!detector.is_video_playing(&filter).await.unwrap());
+
+        let _playing = spawn_player(&bus, "mpv", "mpv Media Player", "Playing").await;
+        assert!(detector.is_video_playing(&filter).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_status_is_ignored() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let _player = spawn_player(&bus, "weird", "Weird Player", "Buffering").await;
+        let detector = VideoDetector::with_connection(bus.connect().await);
+
+        assert!(detector
+            .get_playback_status("org.mpris.MediaPlayer2.weird")
+            .await
+            .is_err());
+        let filter = MediaPlayerFilter::default();
+        assert!(!detector.is_video_playing(&filter).await.unwrap());
+        assert!(detector.list_media_players().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_player_info() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let _player = spawn_player(&bus, "mpv", "mpv Media Player", "Playing").await;
+        let detector = VideoDetector::with_connection(bus.connect().await);
+
+        let player = detector
+            .get_player("org.mpris.MediaPlayer2.mpv")
+            .await
+            .unwrap();
+        assert_eq!(player.short_name(), "mpv");
+        assert_eq!(player.identity.as_deref(), Some("mpv Media Player"));
+        assert_eq!(player.desktop_entry.as_deref(), Some("mpv"));
+        assert_eq!(player.status, PlaybackStatus::Playing);
+        assert_eq!(player.title.as_deref(), Some("mpv Media Player track"));
+    }
+
+    #[tokio::test]
+    async fn test_player_filtering() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let _firefox =
+            spawn_player(&bus, "firefox.instance_1_42", "Mozilla Firefox", "Playing").await;
+        let _mpv = spawn_player(&bus, "mpv", "mpv Media Player", "Paused").await;
+        let detector = VideoDetector::with_connection(bus.connect().await);
+
+        let players = detector.list_media_players().await.unwrap();
+        assert_eq!(players.len(), 2);
+
+        // Ignoring firefox by bus-name suffix leaves only the paused mpv
+        let ignore_firefox = MediaPlayerFilter {
+            include: vec![],
+            exclude: vec!["firefox".to_string()],
+        };
+        assert!(!detector.is_video_playing(&ignore_firefox).await.unwrap());
+
+        // Ignoring by Identity works as well
+        let ignore_identity = MediaPlayerFilter {
+            include: vec![],
+            exclude: vec!["mozilla firefox".to_string()],
+        };
+        assert!(!detector.is_video_playing(&ignore_identity).await.unwrap());
+
+        // Include-lists restrict which players count
+        let only_mpv = MediaPlayerFilter {
+            include: vec!["mpv".to_string()],
+            exclude: vec![],
+        };
+        assert!(!detector.is_video_playing(&only_mpv).await.unwrap());
+
+        let only_firefox = MediaPlayerFilter {
+            include: vec!["firefox".to_string()],
+            exclude: vec![],
+        };
+        assert!(detector.is_video_playing(&only_firefox).await.unwrap());
+    }
 }